@@ -0,0 +1,488 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::TileStorage;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
+use bevy_inspector_egui::prelude::*;
+use bevy_spritesheet_animation::prelude::*;
+use rand::{rngs::StdRng, Rng};
+
+use crate::animation::{AnimatedCharacter, AnimationSets, CharacterAnimationPlugin};
+use crate::combat::{actor_name, chebyshev_distance, AttackIntent, AttackKind, CombatProfile, Dead, Faction};
+use crate::map::{tile_coords_to_world, tile_grid::{TileGrid, NEIGHBOURS}, world_to_tile_coords, ActorTile};
+use crate::monsters::{AiBehaviour, Monster};
+use crate::player::Facing;
+use crate::rng::RunRng;
+use crate::stats::{Health, StatusEffects, StatusKind};
+use crate::turn::{TurnSystems, TurnTick};
+use crate::weather::Weather;
+
+///
+/// What a monster is currently doing. Decided once per turn by the behaviour of the monster,
+/// then turned into a move or an attack.
+///
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub enum AiState {
+    #[default]
+    Idle,
+    /// Walking to a tile around home.
+    Wander { destination: IVec2 },
+    /// Going after a hostile actor, towards the tile it was last seen on.
+    Chase { target: Entity, last_seen: IVec2 },
+    /// Moving away from a threat.
+    Flee { threat: IVec2 },
+    /// Standing on its post.
+    Guard,
+    /// Walking back home.
+    Return,
+}
+
+///
+/// Memory of an AI-driven actor.
+///
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct AiBrain {
+    /// Tile the actor wanders around or guards, its spawn tile.
+    pub home: IVec2,
+}
+
+#[derive(Resource, Reflect, InspectorOptions, Debug, Clone)]
+#[reflect(Resource, InspectorOptions)]
+pub struct AiConfig {
+    /// How far monsters see, in tiles.
+    pub sight_radius: i32,
+    /// Wandering monsters stay this close to home.
+    pub home_radius: i32,
+    /// Guards and wanderers stop chasing beyond this distance from home.
+    pub leash_radius: i32,
+    /// Monsters flee below this fraction of their maximum health.
+    pub flee_health: f32,
+    /// Chance per idle turn to start wandering, in percent.
+    pub wander_chance: i32,
+    /// Tiles explored by the pathfinding before giving up.
+    pub max_path_nodes: usize,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        AiConfig {
+            sight_radius: 8,
+            home_radius: 5,
+            leash_radius: 12,
+            flee_health: 0.25,
+            wander_chance: 30,
+            max_path_nodes: 256,
+        }
+    }
+}
+
+///
+/// Everything a behaviour knows when it decides what to do this turn.
+///
+pub struct AiContext<'a> {
+    pub tile: IVec2,
+    pub brain: &'a AiBrain,
+    pub state: AiState,
+    pub config: &'a AiConfig,
+    /// Current health over maximum health.
+    pub health_fraction: f32,
+    /// Closest hostile actor in sight.
+    pub visible_enemy: Option<(Entity, IVec2)>,
+    /// Monsters of the same kind in sight.
+    pub allies: Vec<IVec2>,
+    /// An enemy one of the allies is chasing.
+    pub ally_target: Option<(Entity, IVec2)>,
+}
+
+impl AiContext<'_> {
+    fn is_hurt(&self) -> bool {
+        self.health_fraction < self.config.flee_health
+    }
+
+    fn chase_visible(&self) -> Option<AiState> {
+        self.visible_enemy.map(|(target, last_seen)| AiState::Chase { target, last_seen })
+    }
+
+    fn flee_visible(&self) -> Option<AiState> {
+        self.visible_enemy.map(|(_, threat)| AiState::Flee { threat })
+    }
+
+    ///
+    /// Keeps walking to the last known position of the target after losing sight of it.
+    ///
+    fn keep_chasing(&self) -> Option<AiState> {
+        match self.state {
+            AiState::Chase { last_seen, .. } if last_seen != self.tile => Some(self.state),
+            _ => None,
+        }
+    }
+
+    fn wander(&self, rng: &mut StdRng) -> AiState {
+        if let AiState::Wander { destination } = self.state
+            && destination != self.tile
+        {
+            return self.state;
+        }
+        if chebyshev_distance(self.tile, self.brain.home) > self.config.home_radius {
+            return AiState::Return;
+        }
+        if rng.random_range(0..100) < self.config.wander_chance {
+            let radius = self.config.home_radius;
+            let offset = IVec2::new(rng.random_range(-radius..=radius), rng.random_range(-radius..=radius));
+            return AiState::Wander { destination: self.brain.home + offset };
+        }
+        AiState::Idle
+    }
+}
+
+///
+/// Picks the next state of an actor from its context.
+///
+pub type DecideFn = fn(&AiContext, &mut StdRng) -> AiState;
+
+///
+/// Decision function of every behaviour. Plugins can replace a behaviour with
+/// [`AiAppExt::register_ai_behaviour`].
+///
+#[derive(Resource, Default)]
+pub struct AiBehaviours {
+    deciders: HashMap<AiBehaviour, DecideFn>,
+}
+
+pub trait AiAppExt {
+    fn register_ai_behaviour(&mut self, behaviour: AiBehaviour, decide: DecideFn) -> &mut Self;
+}
+
+impl AiAppExt for App {
+    fn register_ai_behaviour(&mut self, behaviour: AiBehaviour, decide: DecideFn) -> &mut Self {
+        self.init_resource::<AiBehaviours>();
+        self.world_mut()
+            .resource_mut::<AiBehaviours>()
+            .deciders
+            .insert(behaviour, decide);
+        self
+    }
+}
+
+pub fn decide_wander(ctx: &AiContext, rng: &mut StdRng) -> AiState {
+    if ctx.is_hurt()
+        && let Some(flee) = ctx.flee_visible()
+    {
+        return flee;
+    }
+    if let Some((target, last_seen)) = ctx.visible_enemy
+        && chebyshev_distance(last_seen, ctx.brain.home) <= ctx.config.leash_radius
+    {
+        return AiState::Chase { target, last_seen };
+    }
+    ctx.keep_chasing().unwrap_or_else(|| ctx.wander(rng))
+}
+
+pub fn decide_chase(ctx: &AiContext, rng: &mut StdRng) -> AiState {
+    if ctx.is_hurt()
+        && let Some(flee) = ctx.flee_visible()
+    {
+        return flee;
+    }
+    ctx.chase_visible()
+        .or_else(|| ctx.keep_chasing())
+        .unwrap_or_else(|| ctx.wander(rng))
+}
+
+pub fn decide_flee(ctx: &AiContext, rng: &mut StdRng) -> AiState {
+    ctx.flee_visible().unwrap_or_else(|| ctx.wander(rng))
+}
+
+pub fn decide_guard(ctx: &AiContext, _rng: &mut StdRng) -> AiState {
+    if ctx.is_hurt()
+        && let Some(flee) = ctx.flee_visible()
+    {
+        return flee;
+    }
+    if let Some((target, last_seen)) = ctx.visible_enemy
+        && chebyshev_distance(last_seen, ctx.brain.home) <= ctx.config.leash_radius
+    {
+        return AiState::Chase { target, last_seen };
+    }
+    if ctx.tile != ctx.brain.home {
+        return AiState::Return;
+    }
+    AiState::Guard
+}
+
+pub fn decide_pack(ctx: &AiContext, rng: &mut StdRng) -> AiState {
+    if ctx.is_hurt()
+        && let Some(flee) = ctx.flee_visible()
+    {
+        return flee;
+    }
+    if let Some(chase) = ctx.chase_visible() {
+        return chase;
+    }
+    // The pack hunts together: an enemy seen by one member is chased by all.
+    if let Some((target, last_seen)) = ctx.ally_target {
+        return AiState::Chase { target, last_seen };
+    }
+    if let Some(chase) = ctx.keep_chasing() {
+        return chase;
+    }
+    // Stragglers regroup around the closest member of the pack.
+    if let Some(ally) = ctx.allies.iter().min_by_key(|ally| chebyshev_distance(ctx.tile, **ally))
+        && chebyshev_distance(ctx.tile, *ally) > 2
+    {
+        return AiState::Wander { destination: *ally };
+    }
+    ctx.wander(rng)
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        // Monsters play their walk and idle clips from the animation sets.
+        if !app.is_plugin_added::<CharacterAnimationPlugin>() {
+            app.add_plugins(CharacterAnimationPlugin);
+        }
+        app.init_resource::<AiConfig>()
+            .register_type::<AiConfig>()
+            .register_type::<AiState>()
+            .register_type::<AiBrain>()
+            .register_ai_behaviour(AiBehaviour::Wander, decide_wander)
+            .register_ai_behaviour(AiBehaviour::Chase, decide_chase)
+            .register_ai_behaviour(AiBehaviour::Flee, decide_flee)
+            .register_ai_behaviour(AiBehaviour::Guard, decide_guard)
+            .register_ai_behaviour(AiBehaviour::Pack, decide_pack)
+            .add_systems(Update, (attach_brains, take_monster_turns.after(TurnSystems)).chain())
+            .add_systems(EguiPrimaryContextPass, ai_inspector_ui);
+    }
+}
+
+///
+/// Actors with a behaviour get a brain, their current tile becomes their home.
+///
+fn attach_brains(
+    mut commands: Commands,
+    actors: Query<(Entity, &Transform), (With<AiBehaviour>, Without<AiBrain>)>,
+) {
+    for (entity, transform) in actors.iter() {
+        commands.entity(entity).insert((
+            AiBrain { home: world_to_tile_coords(transform.translation) },
+            AiState::default(),
+        ));
+    }
+}
+
+///
+/// Every AI-driven actor acts once per elapsed turn: it decides its state, then moves one
+/// tile or attacks.
+///
+fn take_monster_turns(
+    mut turns: MessageReader<TurnTick>,
+    behaviours: Res<AiBehaviours>,
+    config: Res<AiConfig>,
+    weather: Res<Weather>,
+    tile_grid: TileGrid,
+    animation_sets: Res<AnimationSets>,
+    mut run_rng: ResMut<RunRng>,
+    // Tilemaps are excluded so that moving actors does not conflict with the tile grid.
+    mut monsters: Query<
+        (
+            Entity,
+            &AiBehaviour,
+            &AiBrain,
+            &mut AiState,
+            &mut Transform,
+            &mut Facing,
+            &Health,
+            &StatusEffects,
+            &CombatProfile,
+            &Faction,
+            Option<&Monster>,
+            Option<&AnimatedCharacter>,
+            Option<&mut SpritesheetAnimation>,
+        ),
+        (Without<Dead>, Without<TileStorage>),
+    >,
+    others: Query<(Entity, ActorTile, &Faction), (Without<Dead>, Without<AiBrain>)>,
+    mut attacks: MessageWriter<AttackIntent>,
+) {
+    let elapsed = turns.read().count();
+    if elapsed == 0 {
+        return;
+    }
+    let sight_radius = weather.sight(config.sight_radius);
+
+    for _ in 0..elapsed {
+        // Snapshot of every actor at the start of the turn.
+        let mut actors: Vec<(Entity, IVec2, Faction, Option<String>, AiState)> = others
+            .iter()
+            .map(|(entity, position, faction)| (entity, position.tile(), *faction, None, AiState::Idle))
+            .collect();
+        actors.extend(monsters.iter().map(|(entity, _, _, state, transform, _, _, _, _, faction, monster, _, _)| {
+            (entity, world_to_tile_coords(transform.translation), *faction, monster.map(|m| m.id.clone()), *state)
+        }));
+        let mut occupied: HashSet<IVec2> = actors.iter().map(|(_, tile, ..)| *tile).collect();
+        let monster_entities: Vec<Entity> = monsters.iter().map(|(entity, ..)| entity).collect();
+
+        for entity in monster_entities {
+            let Ok((_, behaviour, brain, mut state, mut transform, mut facing, health, effects, profile, faction, monster, character, animation)) =
+                monsters.get_mut(entity)
+            else {
+                continue;
+            };
+            if effects.has(StatusKind::Stunned) {
+                continue;
+            }
+            let tile = world_to_tile_coords(transform.translation);
+            let in_sight = |other: IVec2| {
+                chebyshev_distance(tile, other) <= sight_radius && tile_grid.line_of_sight(tile, other)
+            };
+
+            let visible_enemy = actors
+                .iter()
+                .filter(|(other, other_tile, other_faction, ..)| {
+                    *other != entity && faction.is_hostile_to(other_faction) && in_sight(*other_tile)
+                })
+                .min_by_key(|(_, other_tile, ..)| chebyshev_distance(tile, *other_tile))
+                .map(|(other, other_tile, ..)| (*other, *other_tile));
+            let kin: Vec<&(Entity, IVec2, Faction, Option<String>, AiState)> = actors
+                .iter()
+                .filter(|(other, other_tile, _, other_id, _)| {
+                    *other != entity && other_id.is_some() && *other_id == monster.map(|m| m.id.clone()) && in_sight(*other_tile)
+                })
+                .collect();
+            let ally_target = kin.iter().find_map(|(.., ally_state)| match ally_state {
+                AiState::Chase { target, last_seen } => Some((*target, *last_seen)),
+                _ => None,
+            });
+
+            let context = AiContext {
+                tile,
+                brain,
+                state: *state,
+                config: &config,
+                health_fraction: health.current as f32 / health.max.max(1) as f32,
+                visible_enemy,
+                allies: kin.iter().map(|(_, ally_tile, ..)| *ally_tile).collect(),
+                ally_target,
+            };
+            let decide = behaviours.deciders.get(behaviour).copied().unwrap_or(decide_wander);
+            let next_state = decide(&context, run_rng.rng());
+            if *state != next_state {
+                *state = next_state;
+            }
+
+            let step = match next_state {
+                AiState::Idle | AiState::Guard => None,
+                AiState::Wander { destination } => {
+                    let step = next_step(&tile_grid, &config, &occupied, tile, destination);
+                    if step.is_none() {
+                        // The destination cannot be reached or is taken: pick another one later.
+                        *state = AiState::Idle;
+                    }
+                    step
+                }
+                AiState::Return => next_step(&tile_grid, &config, &occupied, tile, brain.home),
+                AiState::Flee { threat } => flee_step(&tile_grid, &occupied, tile, threat),
+                AiState::Chase { target, last_seen } => {
+                    let distance = chebyshev_distance(tile, last_seen);
+                    let target_here = visible_enemy.is_some_and(|(visible, _)| visible == target);
+                    if target_here && distance <= profile.melee.range {
+                        attacks.write(AttackIntent { attacker: entity, target, kind: AttackKind::Melee });
+                        None
+                    } else if target_here && profile.ranged.is_some_and(|ranged| distance <= ranged.range) {
+                        attacks.write(AttackIntent { attacker: entity, target, kind: AttackKind::Ranged });
+                        None
+                    } else {
+                        let step = next_step(&tile_grid, &config, &occupied, tile, last_seen);
+                        if step.is_none() && !target_here {
+                            // The last known position of a lost target cannot be reached.
+                            *state = AiState::Idle;
+                        }
+                        step
+                    }
+                }
+            };
+
+            if let Some(step) = step {
+                occupied.remove(&tile);
+                occupied.insert(step);
+                transform.translation = tile_coords_to_world(step, transform.translation.z);
+                *facing = Facing::from_direction((step - tile).as_vec2());
+                if let (Some(character), Some(mut animation)) = (character, animation) {
+                    character.play(&animation_sets, &mut animation, facing.idle_animation());
+                }
+            }
+        }
+    }
+}
+
+///
+/// Next tile on the path to `goal`, unless another actor stands on it.
+///
+fn next_step(
+    tile_grid: &TileGrid,
+    config: &AiConfig,
+    occupied: &HashSet<IVec2>,
+    from: IVec2,
+    goal: IVec2,
+) -> Option<IVec2> {
+    let path = tile_grid.find_path(from, goal, config.max_path_nodes)?;
+    path.get(1).copied().filter(|step| !occupied.contains(step))
+}
+
+///
+/// Free neighbouring tile that is the furthest away from the threat.
+///
+fn flee_step(
+    tile_grid: &TileGrid,
+    occupied: &HashSet<IVec2>,
+    from: IVec2,
+    threat: IVec2,
+) -> Option<IVec2> {
+    NEIGHBOURS
+        .iter()
+        .map(|offset| from + *offset)
+        .filter(|step| !occupied.contains(step) && tile_grid.can_step(from, *step))
+        .filter(|step| chebyshev_distance(*step, threat) > chebyshev_distance(from, threat))
+        .max_by_key(|step| (*step - threat).length_squared())
+}
+
+///
+/// Debug panel listing the AI state of every monster.
+///
+fn ai_inspector_ui(
+    mut contexts: EguiContexts,
+    monsters: Query<(&AiBehaviour, &AiState, &AiBrain, &Transform, &Health, Option<&Name>)>,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Monster AI").default_open(false).show(ctx, |ui| {
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            egui::Grid::new("monster_ai_grid").striped(true).show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Behaviour");
+                ui.strong("State");
+                ui.strong("Tile");
+                ui.strong("Home");
+                ui.strong("HP");
+                ui.end_row();
+                for (behaviour, state, brain, transform, health, name) in monsters.iter() {
+                    let tile = world_to_tile_coords(transform.translation);
+                    ui.label(actor_name(name));
+                    ui.label(format!("{:?}", behaviour));
+                    ui.label(format!("{:?}", state));
+                    ui.label(format!("{}, {}", tile.x, tile.y));
+                    ui.label(format!("{}, {}", brain.home.x, brain.home.y));
+                    ui.label(format!("{}/{}", health.current, health.max));
+                    ui.end_row();
+                }
+            });
+        });
+    });
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_spritesheet_animation::prelude::*;
+use serde::Deserialize;
+
+use crate::assets::RonAssetAppExt;
+
+// Used when a clip does not specify its own speed.
+const DEFAULT_FPS: f32 = 10.0;
+
+///
+/// Animation set of a character, loaded from a `*.anim.ron` file. A set describes a
+/// sprite sheet and the named clips that can be played from it.
+///
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct AnimationSetDef {
+    /// Unique name, used to prefix the animations registered in the [`AnimationLibrary`].
+    pub name: String,
+    /// Path of the sprite sheet image, relative to the assets folder.
+    pub sheet: String,
+    pub columns: usize,
+    pub rows: usize,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub clips: Vec<ClipDef>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClipDef {
+    pub name: String,
+    /// Row of the sheet to play. When `frames` is empty the whole row is used, otherwise
+    /// `frames` are column indices within this row.
+    #[serde(default)]
+    pub row: Option<usize>,
+    /// Frame indices. Absolute atlas indices when no `row` is given.
+    #[serde(default)]
+    pub frames: Vec<usize>,
+    #[serde(default = "default_fps")]
+    pub fps: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+    /// Named events fired when the clip reaches a frame (footsteps, attack hits...).
+    #[serde(default)]
+    pub events: Vec<ClipEventDef>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClipEventDef {
+    /// Position of the frame inside the clip.
+    pub frame: usize,
+    pub name: String,
+}
+
+fn default_fps() -> f32 {
+    DEFAULT_FPS
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+impl ClipDef {
+    fn atlas_frames(&self, columns: usize) -> Vec<usize> {
+        match self.row {
+            Some(row) if self.frames.is_empty() => (0..columns).map(|column| row * columns + column).collect(),
+            Some(row) => self.frames.iter().map(|column| row * columns + column).collect(),
+            None => self.frames.clone(),
+        }
+    }
+}
+
+///
+/// Everything needed to display an animation set once it has been registered.
+///
+#[derive(Debug, Clone)]
+pub struct RegisteredAnimationSet {
+    pub image: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    pub animations: HashMap<String, AnimationId>,
+}
+
+impl RegisteredAnimationSet {
+    pub fn animation(&self, clip: &str) -> Option<AnimationId> {
+        self.animations.get(clip).copied()
+    }
+}
+
+///
+/// Animation sets that have been loaded and registered in the [`AnimationLibrary`], by asset id.
+///
+#[derive(Resource, Debug, Default)]
+pub struct AnimationSets {
+    sets: HashMap<AssetId<AnimationSetDef>, RegisteredAnimationSet>,
+    markers: HashMap<AnimationMarkerId, String>,
+}
+
+impl AnimationSets {
+    pub fn get(&self, id: impl Into<AssetId<AnimationSetDef>>) -> Option<&RegisteredAnimationSet> {
+        self.sets.get(&id.into())
+    }
+}
+
+///
+/// Put this on an entity to give it a sprite driven by an animation set. The sprite is
+/// added as soon as the set is loaded, starting with the `initial` clip.
+///
+#[derive(Component, Debug, Clone)]
+pub struct AnimatedCharacter {
+    pub set: Handle<AnimationSetDef>,
+    pub initial: String,
+}
+
+impl AnimatedCharacter {
+    ///
+    /// Plays the named clip of the character's set, unless it is already playing.
+    ///
+    pub fn play(&self, sets: &AnimationSets, animation: &mut SpritesheetAnimation, clip: &str) {
+        let Some(animation_id) = sets.get(&self.set).and_then(|set| set.animation(clip)) else { return; };
+        if animation.animation_id != animation_id {
+            animation.switch(animation_id);
+        }
+    }
+}
+
+///
+/// Sent when a clip reaches a frame carrying an event in its definition.
+///
+#[derive(Message, Debug, Clone)]
+pub struct CharacterAnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+pub struct CharacterAnimationPlugin;
+
+impl Plugin for CharacterAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SpritesheetAnimationPlugin>() {
+            app.add_plugins(SpritesheetAnimationPlugin);
+        }
+        app.add_ron_asset::<AnimationSetDef>(&["anim.ron"])
+            .init_resource::<AnimationSets>()
+            .add_message::<CharacterAnimationEvent>()
+            .add_systems(Update, (register_animation_sets, attach_character_sprites).chain())
+            .add_systems(Update, forward_animation_events);
+    }
+}
+
+///
+/// Registers the clips of newly loaded animation sets in the [`AnimationLibrary`].
+///
+fn register_animation_sets(
+    mut asset_events: MessageReader<AssetEvent<AnimationSetDef>>,
+    definitions: Res<Assets<AnimationSetDef>>,
+    asset_server: Res<AssetServer>,
+    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut library: ResMut<AnimationLibrary>,
+    mut sets: ResMut<AnimationSets>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else { continue; };
+        if sets.sets.contains_key(id) {
+            continue;
+        }
+        let Some(definition) = definitions.get(*id) else { continue; };
+
+        let spritesheet = Spritesheet::new(definition.columns, definition.rows);
+        let layout = atlas_layouts.add(
+            spritesheet.atlas_layout(definition.frame_width, definition.frame_height),
+        );
+
+        let mut animations = HashMap::new();
+        for clip_def in &definition.clips {
+            let frame_ms = (1000.0 / clip_def.fps.max(0.1)) as u32;
+            let mut clip = Clip::from_frames(clip_def.atlas_frames(definition.columns))
+                .with_duration(AnimationDuration::PerFrame(frame_ms));
+            for event in &clip_def.events {
+                let marker_id = library.new_marker();
+                clip.add_marker(marker_id, event.frame);
+                sets.markers.insert(marker_id, event.name.clone());
+            }
+            let clip_id = library.register_clip(clip);
+
+            let repetitions = if clip_def.looping { AnimationRepeat::Loop } else { AnimationRepeat::Times(1) };
+            let animation_id = library.register_animation(
+                Animation::from_clip(clip_id).with_repetitions(repetitions),
+            );
+            let qualified_name = format!("{}/{}", definition.name, clip_def.name);
+            if let Err(e) = library.name_animation(animation_id, qualified_name.as_str()) {
+                warn!("Animation {} registered twice: {:?}", qualified_name, e);
+            }
+            animations.insert(clip_def.name.clone(), animation_id);
+        }
+
+        info!("Animation set {} registered with {} clips.", definition.name, animations.len());
+        sets.sets.insert(*id, RegisteredAnimationSet {
+            image: asset_server.load(&definition.sheet),
+            layout,
+            animations,
+        });
+    }
+}
+
+///
+/// Gives a sprite to animated characters whose set is ready.
+///
+fn attach_character_sprites(
+    mut commands: Commands,
+    sets: Res<AnimationSets>,
+    characters: Query<(Entity, &AnimatedCharacter), Without<SpritesheetAnimation>>,
+) {
+    for (entity, character) in characters.iter() {
+        let Some(set) = sets.get(&character.set) else { continue; };
+        let Some(initial) = set.animation(&character.initial) else {
+            warn!("Animation set has no clip named {}", character.initial);
+            continue;
+        };
+        let atlas = TextureAtlas {
+            layout: set.layout.clone(),
+            ..default()
+        };
+        commands.entity(entity).insert((
+            Sprite::from_atlas_image(set.image.clone(), atlas),
+            SpritesheetAnimation::from_id(initial),
+        ));
+    }
+}
+
+fn forward_animation_events(
+    mut animation_events: MessageReader<AnimationEvent>,
+    sets: Res<AnimationSets>,
+    mut character_events: MessageWriter<CharacterAnimationEvent>,
+) {
+    for event in animation_events.read() {
+        if let AnimationEvent::MarkerHit { entity, marker_id, .. } = event
+            && let Some(name) = sets.markers.get(marker_id)
+        {
+            character_events.write(CharacterAnimationEvent {
+                entity: *entity,
+                name: name.clone(),
+            });
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+
+///
+/// Generic loader for game data stored as RON. Each data type registers its own
+/// instance with a distinct double extension (e.g. `anim.ron`) so the asset server
+/// can tell the files apart.
+///
+pub struct RonAssetLoader<T> {
+    extensions: Vec<&'static str>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> RonAssetLoader<T> {
+    pub fn new(extensions: &[&'static str]) -> Self {
+        RonAssetLoader {
+            extensions: extensions.to_vec(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> AssetLoader for RonAssetLoader<T>
+where
+    T: Asset + for<'de> Deserialize<'de>,
+{
+    type Asset = T;
+    type Settings = ();
+    type Error = BevyError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let asset = ron::de::from_bytes::<T>(&bytes)?;
+        Ok(asset)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &self.extensions
+    }
+}
+
+///
+/// Helper to declare a RON backed asset type in one call.
+///
+pub trait RonAssetAppExt {
+    fn add_ron_asset<T>(&mut self, extensions: &[&'static str]) -> &mut Self
+    where
+        T: Asset + for<'de> Deserialize<'de>;
+}
+
+impl RonAssetAppExt for App {
+    fn add_ron_asset<T>(&mut self, extensions: &[&'static str]) -> &mut Self
+    where
+        T: Asset + for<'de> Deserialize<'de>,
+    {
+        self.init_asset::<T>()
+            .register_asset_loader(RonAssetLoader::<T>::new(extensions))
+    }
+}
@@ -0,0 +1,523 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::events::MoveEvent;
+use crate::game_log::GameLog;
+use crate::input::{ActionState, InputAction};
+use crate::map::{tile_grid::TileGrid, world_to_tile_coords, ActorTile};
+use crate::player::Player;
+use crate::rng::RunRng;
+use crate::states::{in_play, GameState};
+use crate::stats::{Attributes, DerivedStats, GainExperience, Health};
+use crate::turn::{PassTurn, TurnSystems};
+use crate::weather::Weather;
+
+// Chance to hit before attack, defence and evasion are taken into account.
+const HIT_BASE_CHANCE: i32 = 80;
+const CRIT_BASE_CHANCE: i32 = 5;
+// Holding a direction against a monster attacks at most this often, in seconds.
+const BUMP_ATTACK_DELAY: f32 = 0.4;
+
+///
+/// Side an actor fights for. Actors of hostile factions attack each other and no actor can
+/// walk through another one.
+///
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[reflect(Component)]
+pub enum Faction {
+    Player,
+    Monster,
+    Neutral,
+}
+
+impl Faction {
+    pub fn is_hostile_to(&self, other: &Faction) -> bool {
+        matches!((self, other), (Faction::Player, Faction::Monster) | (Faction::Monster, Faction::Player))
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageType {
+    Physical,
+    Fire,
+    Cold,
+    Lightning,
+    Poison,
+    Arcane,
+}
+
+///
+/// Damage reduction per damage type, in percent. Negative values are weaknesses.
+///
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Resistances {
+    pub physical: i32,
+    pub fire: i32,
+    pub cold: i32,
+    pub lightning: i32,
+    pub poison: i32,
+    pub arcane: i32,
+}
+
+impl Resistances {
+    pub fn get(&self, damage_type: DamageType) -> i32 {
+        match damage_type {
+            DamageType::Physical => self.physical,
+            DamageType::Fire => self.fire,
+            DamageType::Cold => self.cold,
+            DamageType::Lightning => self.lightning,
+            DamageType::Poison => self.poison,
+            DamageType::Arcane => self.arcane,
+        }
+    }
+
+    ///
+    /// Damage left once the resistance is applied. Any damage that is not fully resisted
+    /// deals at least 1.
+    ///
+    pub fn reduce(&self, damage_type: DamageType, amount: i32) -> i32 {
+        let resistance = self.get(damage_type).min(100);
+        if amount <= 0 || resistance == 100 {
+            return 0;
+        }
+        (amount * (100 - resistance) / 100).max(1)
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct AttackProfile {
+    pub damage_type: DamageType,
+    /// Added to the attacker's attack stat.
+    #[serde(default)]
+    pub bonus_damage: i32,
+    /// Reach in tiles. Melee attacks use 1.
+    #[serde(default = "default_range")]
+    pub range: i32,
+}
+
+fn default_range() -> i32 {
+    1
+}
+
+///
+/// How an actor attacks. Actors without a ranged attack can only fight in melee.
+///
+#[derive(Component, Reflect, Debug, Clone, Copy, Deserialize)]
+#[reflect(Component)]
+pub struct CombatProfile {
+    pub melee: AttackProfile,
+    #[serde(default)]
+    pub ranged: Option<AttackProfile>,
+}
+
+impl Default for CombatProfile {
+    fn default() -> Self {
+        CombatProfile {
+            melee: AttackProfile {
+                damage_type: DamageType::Physical,
+                bonus_damage: 0,
+                range: 1,
+            },
+            ranged: None,
+        }
+    }
+}
+
+///
+/// Experience given to whoever kills this actor.
+///
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct ExperienceReward(pub u64);
+
+///
+/// Added to actors whose health reached zero, so they are only handled once.
+///
+#[derive(Component, Debug)]
+pub struct Dead;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackKind {
+    Melee,
+    Ranged,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct AttackIntent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub kind: AttackKind,
+}
+
+///
+/// Damage about to be dealt to an actor. Resistances are applied when it is received, so
+/// spells, traps and status effects can use it as well as weapons.
+///
+#[derive(Message, Debug, Clone, Copy)]
+pub struct ApplyDamage {
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub amount: i32,
+    pub damage_type: DamageType,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct Died {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttackRoll {
+    pub hit: bool,
+    pub critical: bool,
+    pub damage: i32,
+}
+
+///
+/// Rolls to hit, for a critical and for the damage of an attack. The damage returned is before
+/// the defender's resistances.
+///
+pub fn roll_attack(
+    rng: &mut impl Rng,
+    attacker: &DerivedStats,
+    attacker_attributes: &Attributes,
+    defender: &DerivedStats,
+    attack: &AttackProfile,
+) -> AttackRoll {
+    let to_hit = (HIT_BASE_CHANCE + (attacker.attack - defender.defence) / 2 - defender.evasion / 2).clamp(5, 95);
+    if rng.random_range(0..100) >= to_hit {
+        return AttackRoll { hit: false, critical: false, damage: 0 };
+    }
+
+    let crit_chance = (CRIT_BASE_CHANCE + attacker_attributes.dexterity / 2).clamp(0, 50);
+    let critical = rng.random_range(0..100) < crit_chance;
+
+    let max_damage = (attacker.attack + attack.bonus_damage).max(1);
+    let mut damage = rng.random_range(max_damage / 2..=max_damage);
+    if attack.damage_type == DamageType::Physical {
+        damage -= defender.defence / 2;
+    }
+    if critical {
+        damage *= 2;
+    }
+    AttackRoll { hit: true, critical, damage: damage.max(1) }
+}
+
+pub fn actor_name(name: Option<&Name>) -> String {
+    name.map(|name| name.as_str().to_string()).unwrap_or_else(|| "Something".to_string())
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunRng>()
+            .register_type::<Faction>()
+            .register_type::<Resistances>()
+            .register_type::<CombatProfile>()
+            .register_type::<ExperienceReward>()
+            .add_message::<AttackIntent>()
+            .add_message::<ApplyDamage>()
+            .add_message::<Died>()
+            .add_systems(Update, (
+                (player_bump_attack, player_ranged_attack)
+                    .run_if(in_play)
+                    .before(TurnSystems),
+                resolve_attacks,
+                apply_damage,
+                handle_deaths,
+            ).chain());
+    }
+}
+
+///
+/// Moving into a tile held by a hostile actor attacks it instead.
+///
+fn player_bump_attack(
+    mut move_events: MessageReader<MoveEvent>,
+    time: Res<Time>,
+    player_query: Query<(Entity, &Faction), (With<Player>, Without<Dead>)>,
+    actors: Query<(Entity, ActorTile, &Faction), Without<Dead>>,
+    mut last_attack: Local<f32>,
+    mut attacks: MessageWriter<AttackIntent>,
+    mut pass_turn: MessageWriter<PassTurn>,
+) {
+    let Ok((player, player_faction)) = player_query.single() else {
+        move_events.clear();
+        return;
+    };
+
+    for move_event in move_events.read() {
+        let Some(destination) = move_event.destination else { continue; };
+        let destination_tile = world_to_tile_coords(destination);
+        let target = actors.iter().find(|(entity, position, faction)| {
+            *entity != player
+                && player_faction.is_hostile_to(faction)
+                && position.tile() == destination_tile
+        });
+        let Some((target, _, _)) = target else { continue; };
+
+        let now = time.elapsed_secs();
+        if now - *last_attack < BUMP_ATTACK_DELAY {
+            continue;
+        }
+        *last_attack = now;
+        attacks.write(AttackIntent { attacker: player, target, kind: AttackKind::Melee });
+        pass_turn.write(PassTurn);
+    }
+}
+
+///
+/// The fire action shoots at the closest hostile actor in range and in sight. Rain, snow and
+/// sandstorms shorten the range to what the player can see.
+///
+fn player_ranged_attack(
+    actions: Res<ActionState>,
+    weather: Res<Weather>,
+    tile_grid: TileGrid,
+    player_query: Query<(Entity, ActorTile, &Faction, &CombatProfile), (With<Player>, Without<Dead>)>,
+    actors: Query<(Entity, ActorTile, &Faction), Without<Dead>>,
+    mut log: ResMut<GameLog>,
+    mut attacks: MessageWriter<AttackIntent>,
+    mut pass_turn: MessageWriter<PassTurn>,
+) {
+    if !actions.just_pressed(InputAction::Fire) {
+        return;
+    }
+    let Ok((player, player_position, player_faction, profile)) = player_query.single() else { return; };
+    let Some(ranged) = profile.ranged else {
+        log.info("You have no ranged weapon.");
+        return;
+    };
+
+    let player_tile = player_position.tile();
+    let target = actors
+        .iter()
+        .filter(|(entity, _, faction)| *entity != player && player_faction.is_hostile_to(faction))
+        .map(|(entity, position, _)| (entity, position.tile()))
+        .filter(|(_, tile)| chebyshev_distance(player_tile, *tile) <= weather.sight(ranged.range))
+        .filter(|(_, tile)| tile_grid.line_of_sight(player_tile, *tile))
+        .min_by_key(|(_, tile)| chebyshev_distance(player_tile, *tile));
+
+    match target {
+        Some((target, _)) => {
+            attacks.write(AttackIntent { attacker: player, target, kind: AttackKind::Ranged });
+            pass_turn.write(PassTurn);
+        }
+        None => log.info("No target in sight."),
+    }
+}
+
+pub fn chebyshev_distance(a: IVec2, b: IVec2) -> i32 {
+    let delta = (a - b).abs();
+    delta.x.max(delta.y)
+}
+
+fn resolve_attacks(
+    mut attacks: MessageReader<AttackIntent>,
+    tile_grid: TileGrid,
+    attackers: Query<(ActorTile, &DerivedStats, &Attributes, &CombatProfile, Option<&Name>), Without<Dead>>,
+    defenders: Query<(ActorTile, &DerivedStats, Option<&Name>), Without<Dead>>,
+    mut run_rng: ResMut<RunRng>,
+    mut log: ResMut<GameLog>,
+    mut damage: MessageWriter<ApplyDamage>,
+) {
+    for attack in attacks.read() {
+        let Ok((attacker_position, attacker_stats, attacker_attributes, profile, attacker_name)) =
+            attackers.get(attack.attacker) else { continue; };
+        let Ok((defender_position, defender_stats, defender_name)) = defenders.get(attack.target) else { continue; };
+
+        let attacker_tile = attacker_position.tile();
+        let defender_tile = defender_position.tile();
+        let distance = chebyshev_distance(attacker_tile, defender_tile);
+        let attack_profile = match attack.kind {
+            AttackKind::Melee => profile.melee,
+            AttackKind::Ranged => {
+                let Some(ranged) = profile.ranged else { continue; };
+                if !tile_grid.line_of_sight(attacker_tile, defender_tile) {
+                    continue;
+                }
+                ranged
+            }
+        };
+        if distance > attack_profile.range {
+            continue;
+        }
+
+        let attacker_name = actor_name(attacker_name);
+        let defender_name = actor_name(defender_name);
+        let roll = roll_attack(run_rng.rng(), attacker_stats, attacker_attributes, defender_stats, &attack_profile);
+        if !roll.hit {
+            log.combat(format!("{} misses {}.", attacker_name, defender_name));
+            continue;
+        }
+        if roll.critical {
+            log.combat(format!("{} lands a critical hit on {}!", attacker_name, defender_name));
+        }
+        damage.write(ApplyDamage {
+            source: Some(attack.attacker),
+            target: attack.target,
+            amount: roll.damage,
+            damage_type: attack_profile.damage_type,
+        });
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: MessageReader<ApplyDamage>,
+    mut targets: Query<(&mut Health, Option<&Resistances>, Option<&Name>), Without<Dead>>,
+    mut log: ResMut<GameLog>,
+    mut died: MessageWriter<Died>,
+) {
+    // `Dead` is only inserted once the commands run, later hits of this run must not kill the
+    // same target again.
+    let mut killed: HashSet<Entity> = HashSet::new();
+    for damage in damage_events.read() {
+        if killed.contains(&damage.target) {
+            continue;
+        }
+        let Ok((mut health, resistances, name)) = targets.get_mut(damage.target) else { continue; };
+        let amount = resistances.copied().unwrap_or_default().reduce(damage.damage_type, damage.amount);
+        let name = actor_name(name);
+        if amount == 0 {
+            log.combat(format!("{} resists the {:?} damage.", name, damage.damage_type));
+            continue;
+        }
+        health.current -= amount;
+        log.combat(format!("{} takes {} {:?} damage.", name, amount, damage.damage_type));
+
+        if health.is_dead() {
+            killed.insert(damage.target);
+            commands.entity(damage.target).insert(Dead);
+            died.write(Died { entity: damage.target, killer: damage.source });
+        }
+    }
+}
+
+///
+/// Removes dead monsters and rewards their killer. When the player dies the game is over.
+///
+fn handle_deaths(
+    mut commands: Commands,
+    mut deaths: MessageReader<Died>,
+    victims: Query<(Option<&Name>, Option<&ExperienceReward>, Has<Player>)>,
+    mut log: ResMut<GameLog>,
+    mut experience: MessageWriter<GainExperience>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for death in deaths.read() {
+        let Ok((name, reward, is_player)) = victims.get(death.entity) else { continue; };
+        if is_player {
+            log.danger("You die...");
+            next_state.set(GameState::GameOver);
+            continue;
+        }
+
+        log.danger(format!("{} dies.", actor_name(name)));
+        if let (Some(killer), Some(reward)) = (death.killer, reward) {
+            experience.write(GainExperience { entity: killer, amount: reward.0 });
+        }
+        commands.entity(death.entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn attack(damage_type: DamageType) -> AttackProfile {
+        AttackProfile { damage_type, bonus_damage: 0, range: 1 }
+    }
+
+    fn stats(attack: i32, defence: i32, evasion: i32) -> DerivedStats {
+        DerivedStats { attack, defence, evasion, ..default() }
+    }
+
+    #[test]
+    fn resistances_reduce_damage_by_percent() {
+        let resistances = Resistances { fire: 50, cold: 100, lightning: 150, poison: -50, arcane: 90, ..default() };
+        assert_eq!(resistances.reduce(DamageType::Physical, 10), 10);
+        assert_eq!(resistances.reduce(DamageType::Fire, 10), 5);
+        assert_eq!(resistances.reduce(DamageType::Cold, 10), 0);
+        assert_eq!(resistances.reduce(DamageType::Lightning, 10), 0);
+        assert_eq!(resistances.reduce(DamageType::Poison, 10), 15);
+        // Damage that is not fully resisted always deals something.
+        assert_eq!(resistances.reduce(DamageType::Arcane, 5), 1);
+        assert_eq!(resistances.reduce(DamageType::Fire, 0), 0);
+    }
+
+    #[test]
+    fn attack_rolls_stay_within_their_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let attributes = Attributes { dexterity: 0, ..default() };
+        let (attacker, defender) = (stats(10, 0, 0), stats(0, 0, 0));
+        let rolls: Vec<AttackRoll> =
+            (0..2000).map(|_| roll_attack(&mut rng, &attacker, &attributes, &defender, &attack(DamageType::Fire))).collect();
+
+        let hits = rolls.iter().filter(|roll| roll.hit).count();
+        assert!((1400..=1800).contains(&hits), "{} hits out of 2000 at 80%", hits);
+        for roll in &rolls {
+            match (roll.hit, roll.critical) {
+                (false, _) => assert_eq!((roll.critical, roll.damage), (false, 0)),
+                (true, false) => assert!((5..=10).contains(&roll.damage)),
+                (true, true) => assert!((10..=20).contains(&roll.damage)),
+            }
+        }
+        assert!(rolls.iter().any(|roll| roll.critical));
+    }
+
+    #[test]
+    fn hit_chance_is_clamped() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let attributes = Attributes::default();
+        let elusive = stats(0, 0, 500);
+        let hits = (0..2000)
+            .filter(|_| roll_attack(&mut rng, &stats(1, 0, 0), &attributes, &elusive, &attack(DamageType::Physical)).hit)
+            .count();
+        assert!((50..=150).contains(&hits), "{} hits out of 2000 at 5%", hits);
+    }
+
+    #[test]
+    fn defence_only_reduces_physical_damage() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let attributes = Attributes { dexterity: 0, ..default() };
+        let (attacker, defender) = (stats(4, 0, 0), stats(0, 20, 0));
+        for _ in 0..200 {
+            let physical = roll_attack(&mut rng, &attacker, &attributes, &defender, &attack(DamageType::Physical));
+            if physical.hit {
+                assert_eq!(physical.damage, 1);
+            }
+            let fire = roll_attack(&mut rng, &attacker, &attributes, &defender, &attack(DamageType::Fire));
+            if fire.hit && !fire.critical {
+                assert!((2..=4).contains(&fire.damage));
+            }
+        }
+    }
+
+    #[test]
+    fn a_target_only_dies_once() {
+        let mut world = World::new();
+        world.init_resource::<GameLog>();
+        world.init_resource::<Messages<ApplyDamage>>();
+        world.init_resource::<Messages<Died>>();
+        let target = world.spawn(Health { current: 5, max: 5 }).id();
+        for source in [world.spawn_empty().id(), world.spawn_empty().id()] {
+            world.write_message(ApplyDamage { source: Some(source), target, amount: 10, damage_type: DamageType::Physical });
+        }
+        world.run_system_once(apply_damage).unwrap();
+
+        assert_eq!(world.resource::<Messages<Died>>().len(), 1);
+        assert!(world.entity(target).contains::<Dead>());
+        assert_eq!(world.entity(target).get::<Health>().unwrap().current, -5);
+    }
+}
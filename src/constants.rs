@@ -4,6 +4,9 @@ use bevy::math::UVec2;
 pub const WINDOW_WIDTH: u32 = 1024;
 pub const WINDOW_HEIGHT: u32 = 768;
 
+// Size of a map tile in pixels
+pub const TILE_SIZE_PX: f32 = 32.0;
+
 // Chunks and Overworld map size
 pub const CHUNK_SIZE: UVec2 = UVec2 { x: 16, y: 16 };
 // Render chunk sizes are set to 4 render chunks per user specified chunk.
@@ -13,3 +16,12 @@ pub const RENDER_CHUNK_SIZE: UVec2 = UVec2 {
 };
 pub const OVERWORLD_SIZE_WIDTH: u32 = 320;
 pub const OVERWORLD_SIZE_HEIGHT: u32 = 240;
+
+// Configuration files written at runtime (not loaded through the asset server)
+pub const INPUT_BINDINGS_PATH: &str = "assets/config/input_bindings.ron";
+// Tiled automapping rules applied to generated maps
+pub const AUTOMAP_RULES_PATH: &str = "assets/tiled_map_assets/rules.txt";
+// Tilesets whose tile properties give the collision of the Tiled maps
+pub const TILED_TILESETS_DIR: &str = "assets/tiled_map_assets/tilesets";
+// Custom types of the Tiled objects, written at startup for the Tiled project to import
+pub const TILED_TYPES_EXPORT_PATH: &str = "assets/tiled_map_assets/void-destiny-types.json";
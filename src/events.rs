@@ -1,4 +1,5 @@
-use bevy::math::Vec3;
+use bevy::ecs::entity::Entity;
+use bevy::math::{IVec2, Vec3};
 //use bevy::ecs::event::Event;
 use bevy::ecs::message::Message;
 
@@ -15,4 +16,39 @@ pub struct MoveLegal {
 }
 
 #[derive(Message)]
-pub struct EdgeDetectionEvent {}
\ No newline at end of file
+pub struct EdgeDetectionEvent {}
+
+///
+/// Sent when the tiles of a chunk have been spawned. `chunk` is in chunk coordinates.
+///
+#[derive(Message)]
+pub struct ChunkLoaded {
+    pub chunk: IVec2,
+}
+
+///
+/// Sent when the ground tiles of a spawned chunk are swapped for other ones, so that what is
+/// drawn from them follows.
+///
+#[derive(Message)]
+pub struct GroundChanged {
+    pub chunk: IVec2,
+}
+
+///
+/// Sent when a chunk is despawned, so that whatever lies on it can be saved or frozen.
+///
+#[derive(Message)]
+pub struct ChunkUnloaded {
+    pub chunk: IVec2,
+}
+
+///
+/// Sent when the player steps on a `Trigger` object of a Tiled map. `name` is the name of the
+/// object in Tiled.
+///
+#[derive(Message)]
+pub struct MapTriggered {
+    pub trigger: Entity,
+    pub name: String,
+}
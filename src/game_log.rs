@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
+
+use crate::turn::TurnClock;
+
+// Older entries are dropped once the log holds this many lines.
+const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogKind {
+    Info,
+    Combat,
+    Danger,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub turn: u64,
+    pub kind: LogKind,
+    pub text: String,
+}
+
+///
+/// Messages shown to the player in the scrolling log at the bottom of the screen.
+///
+#[derive(Resource, Debug, Default)]
+pub struct GameLog {
+    entries: VecDeque<LogEntry>,
+    current_turn: u64,
+}
+
+impl GameLog {
+    pub fn push(&mut self, kind: LogKind, text: impl Into<String>) {
+        let text = text.into();
+        info!("{}", text);
+        if self.entries.len() == MAX_LOG_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            turn: self.current_turn,
+            kind,
+            text,
+        });
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(LogKind::Info, text);
+    }
+
+    pub fn combat(&mut self, text: impl Into<String>) {
+        self.push(LogKind::Combat, text);
+    }
+
+    pub fn danger(&mut self, text: impl Into<String>) {
+        self.push(LogKind::Danger, text);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+}
+
+pub struct GameLogPlugin;
+
+impl Plugin for GameLogPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.init_resource::<GameLog>()
+            .add_systems(Update, follow_turns)
+            .add_systems(EguiPrimaryContextPass, game_log_ui);
+    }
+}
+
+fn follow_turns(
+    clock: Res<TurnClock>,
+    mut log: ResMut<GameLog>,
+) {
+    if clock.is_changed() {
+        log.current_turn = clock.turn;
+    }
+}
+
+fn game_log_ui(
+    mut contexts: EguiContexts,
+    log: Res<GameLog>,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Messages")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+        .default_size([420.0, 140.0])
+        .resizable(true)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for entry in log.entries() {
+                        let color = match entry.kind {
+                            LogKind::Info => egui::Color32::LIGHT_GRAY,
+                            LogKind::Combat => egui::Color32::from_rgb(240, 200, 120),
+                            LogKind::Danger => egui::Color32::from_rgb(240, 90, 90),
+                        };
+                        ui.colored_label(color, format!("[{}] {}", entry.turn, entry.text));
+                    }
+                });
+        });
+    Ok(())
+}
@@ -0,0 +1,467 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+
+use bevy::{
+    input::{
+        gamepad::{Gamepad, GamepadAxis, GamepadButton},
+        keyboard::KeyCode,
+        mouse::{MouseButton, MouseScrollUnit, MouseWheel},
+        ButtonInput, InputSystems,
+    },
+    prelude::*,
+};
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::INPUT_BINDINGS_PATH;
+
+// Analog sticks need to be pushed past this value before they count as a pressed direction.
+const GAMEPAD_AXIS_THRESHOLD: f32 = 0.5;
+
+///
+/// Every action the game reacts to. Systems must query the [`ActionState`] for these
+/// instead of looking at raw `KeyCode`s so that bindings can be changed by the player.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Reflect)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    ZoomIn,
+    ZoomOut,
+    Interact,
+    Fire,
+    Confirm,
+    Cancel,
+    CastSpell1,
+    CastSpell2,
+    CastSpell3,
+    CastSpell4,
+    CastSpell5,
+    OpenInventory,
+    Wait,
+    OpenKeybindings,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 22] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::MoveUpLeft,
+        InputAction::MoveUpRight,
+        InputAction::MoveDownLeft,
+        InputAction::MoveDownRight,
+        InputAction::ZoomIn,
+        InputAction::ZoomOut,
+        InputAction::Interact,
+        InputAction::Fire,
+        InputAction::Confirm,
+        InputAction::Cancel,
+        InputAction::CastSpell1,
+        InputAction::CastSpell2,
+        InputAction::CastSpell3,
+        InputAction::CastSpell4,
+        InputAction::CastSpell5,
+        InputAction::OpenInventory,
+        InputAction::Wait,
+        InputAction::OpenKeybindings,
+    ];
+
+    /// Hotbar slots, in order.
+    pub const CAST_SPELL: [InputAction; 5] = [
+        InputAction::CastSpell1,
+        InputAction::CastSpell2,
+        InputAction::CastSpell3,
+        InputAction::CastSpell4,
+        InputAction::CastSpell5,
+    ];
+
+    ///
+    /// Direction contributed by a movement action, `None` for every other action.
+    ///
+    pub fn move_direction(&self) -> Option<Vec2> {
+        match self {
+            InputAction::MoveUp => Some(Vec2::new(0.0, 1.0)),
+            InputAction::MoveDown => Some(Vec2::new(0.0, -1.0)),
+            InputAction::MoveLeft => Some(Vec2::new(-1.0, 0.0)),
+            InputAction::MoveRight => Some(Vec2::new(1.0, 0.0)),
+            InputAction::MoveUpLeft => Some(Vec2::new(-1.0, 1.0)),
+            InputAction::MoveUpRight => Some(Vec2::new(1.0, 1.0)),
+            InputAction::MoveDownLeft => Some(Vec2::new(-1.0, -1.0)),
+            InputAction::MoveDownRight => Some(Vec2::new(1.0, -1.0)),
+            _ => None,
+        }
+    }
+}
+
+///
+/// A single physical input that can trigger an action.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    MouseWheelUp,
+    MouseWheelDown,
+    Gamepad(GamepadButton),
+    GamepadAxisPositive(GamepadAxis),
+    GamepadAxisNegative(GamepadAxis),
+}
+
+///
+/// Maps each action to the inputs that trigger it. Loaded from and saved to
+/// `INPUT_BINDINGS_PATH` so players can rebind their controls.
+///
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub bindings: BTreeMap<InputAction, Vec<InputBinding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use InputBinding::*;
+
+        let mut bindings = BTreeMap::new();
+        bindings.insert(InputAction::MoveUp, vec![
+            Key(KeyCode::KeyW), Key(KeyCode::ArrowUp), Key(KeyCode::Numpad8),
+            Gamepad(GamepadButton::DPadUp), GamepadAxisPositive(GamepadAxis::LeftStickY),
+        ]);
+        bindings.insert(InputAction::MoveDown, vec![
+            Key(KeyCode::KeyS), Key(KeyCode::ArrowDown), Key(KeyCode::Numpad2),
+            Gamepad(GamepadButton::DPadDown), GamepadAxisNegative(GamepadAxis::LeftStickY),
+        ]);
+        bindings.insert(InputAction::MoveLeft, vec![
+            Key(KeyCode::KeyA), Key(KeyCode::ArrowLeft), Key(KeyCode::Numpad4),
+            Gamepad(GamepadButton::DPadLeft), GamepadAxisNegative(GamepadAxis::LeftStickX),
+        ]);
+        bindings.insert(InputAction::MoveRight, vec![
+            Key(KeyCode::KeyD), Key(KeyCode::ArrowRight), Key(KeyCode::Numpad6),
+            Gamepad(GamepadButton::DPadRight), GamepadAxisPositive(GamepadAxis::LeftStickX),
+        ]);
+        bindings.insert(InputAction::MoveUpLeft, vec![Key(KeyCode::Numpad7)]);
+        bindings.insert(InputAction::MoveUpRight, vec![Key(KeyCode::Numpad9)]);
+        bindings.insert(InputAction::MoveDownLeft, vec![Key(KeyCode::Numpad1)]);
+        bindings.insert(InputAction::MoveDownRight, vec![Key(KeyCode::Numpad3)]);
+        bindings.insert(InputAction::ZoomIn, vec![
+            Key(KeyCode::KeyE), MouseWheelUp, Gamepad(GamepadButton::RightTrigger),
+        ]);
+        bindings.insert(InputAction::ZoomOut, vec![
+            Key(KeyCode::KeyQ), MouseWheelDown, Gamepad(GamepadButton::LeftTrigger),
+        ]);
+        // Confirm has Enter and the south button, one press must not also interact.
+        bindings.insert(InputAction::Interact, vec![
+            Key(KeyCode::KeyF), Gamepad(GamepadButton::RightTrigger2),
+        ]);
+        bindings.insert(InputAction::Fire, vec![
+            Key(KeyCode::KeyR), Gamepad(GamepadButton::East),
+        ]);
+        bindings.insert(InputAction::Confirm, vec![
+            Mouse(MouseButton::Left), Key(KeyCode::Enter), Gamepad(GamepadButton::South),
+        ]);
+        bindings.insert(InputAction::Cancel, vec![
+            Key(KeyCode::Escape), Mouse(MouseButton::Right),
+        ]);
+        bindings.insert(InputAction::CastSpell1, vec![Key(KeyCode::Digit1)]);
+        bindings.insert(InputAction::CastSpell2, vec![Key(KeyCode::Digit2)]);
+        bindings.insert(InputAction::CastSpell3, vec![Key(KeyCode::Digit3)]);
+        bindings.insert(InputAction::CastSpell4, vec![Key(KeyCode::Digit4)]);
+        bindings.insert(InputAction::CastSpell5, vec![Key(KeyCode::Digit5)]);
+        bindings.insert(InputAction::OpenInventory, vec![
+            Key(KeyCode::KeyI), Key(KeyCode::Tab), Gamepad(GamepadButton::North),
+        ]);
+        bindings.insert(InputAction::Wait, vec![
+            Key(KeyCode::Space), Key(KeyCode::Numpad5), Gamepad(GamepadButton::West),
+        ]);
+        bindings.insert(InputAction::OpenKeybindings, vec![
+            Key(KeyCode::F1), Gamepad(GamepadButton::Select),
+        ]);
+
+        InputBindings { bindings }
+    }
+}
+
+impl InputBindings {
+    ///
+    /// Reads the bindings file, falling back to the defaults when it is missing or invalid.
+    /// Actions missing from the file keep their default bindings.
+    ///
+    pub fn load_or_default(path: &str) -> Self {
+        let mut bindings = InputBindings::default();
+        match fs::read_to_string(path) {
+            Ok(content) => match ron::from_str::<InputBindings>(&content) {
+                Ok(loaded) => bindings.bindings.extend(loaded.bindings),
+                Err(e) => warn!("Invalid input bindings file {}: {}. Using defaults.", path, e),
+            },
+            Err(_) => info!("No input bindings file at {}, using defaults.", path),
+        }
+        bindings
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, action: InputAction) -> &[InputBinding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+///
+/// State of every action for the current frame. Filled in `PreUpdate` by [`update_action_state`].
+///
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    just_released: HashSet<InputAction>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    ///
+    /// Sum of every pressed movement action. Each component is in the range -1..=1.
+    ///
+    pub fn move_vector(&self) -> Vec2 {
+        let direction: Vec2 = self.pressed
+            .iter()
+            .filter_map(InputAction::move_direction)
+            .sum();
+        direction.clamp(Vec2::NEG_ONE, Vec2::ONE)
+    }
+}
+
+///
+/// Keybinding screen state. While an action is waiting for a new input, gameplay systems
+/// still see the usual bindings; the next key pressed replaces the first binding of that action.
+///
+#[derive(Resource, Debug, Default)]
+pub struct KeybindingsMenu {
+    pub open: bool,
+    pub waiting_for: Option<InputAction>,
+    pub status: String,
+}
+
+/// System set in which the [`ActionState`] is refreshed. Systems reading actions in `PreUpdate`
+/// must run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSystems;
+
+pub struct InputActionsPlugin;
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.insert_resource(InputBindings::load_or_default(INPUT_BINDINGS_PATH))
+            .init_resource::<ActionState>()
+            .init_resource::<KeybindingsMenu>()
+            .register_type::<InputAction>()
+            .add_systems(
+                PreUpdate,
+                update_action_state.in_set(ActionSystems).after(InputSystems),
+            )
+            .add_systems(Update, (toggle_keybindings_menu, capture_rebinding).chain())
+            .add_systems(EguiPrimaryContextPass, keybindings_ui);
+    }
+}
+
+fn binding_pressed(
+    binding: &InputBinding,
+    keyboard: &ButtonInput<KeyCode>,
+    mouse: &ButtonInput<MouseButton>,
+    wheel: f32,
+    gamepads: &Query<&Gamepad>,
+) -> bool {
+    match binding {
+        InputBinding::Key(key) => keyboard.pressed(*key),
+        InputBinding::Mouse(button) => mouse.pressed(*button),
+        InputBinding::MouseWheelUp => wheel > 0.0,
+        InputBinding::MouseWheelDown => wheel < 0.0,
+        InputBinding::Gamepad(button) => gamepads.iter().any(|gamepad| gamepad.pressed(*button)),
+        InputBinding::GamepadAxisPositive(axis) => gamepads
+            .iter()
+            .any(|gamepad| gamepad.get(*axis).unwrap_or(0.0) > GAMEPAD_AXIS_THRESHOLD),
+        InputBinding::GamepadAxisNegative(axis) => gamepads
+            .iter()
+            .any(|gamepad| gamepad.get(*axis).unwrap_or(0.0) < -GAMEPAD_AXIS_THRESHOLD),
+    }
+}
+
+///
+/// Translates the raw keyboard, mouse and gamepad state into the [`ActionState`].
+///
+fn update_action_state(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut scroll_evr: MessageReader<MouseWheel>,
+    gamepads: Query<&Gamepad>,
+    bindings: Res<InputBindings>,
+    menu: Res<KeybindingsMenu>,
+    mut actions: ResMut<ActionState>,
+) {
+    let mut wheel = 0.0;
+    for ev in scroll_evr.read() {
+        wheel += match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y.signum(),
+        };
+    }
+
+    let previous = std::mem::take(&mut actions.pressed);
+    actions.just_pressed.clear();
+    actions.just_released.clear();
+
+    // Do not let the key being captured for a rebinding leak into gameplay.
+    if menu.waiting_for.is_some() {
+        actions.just_released.extend(previous);
+        return;
+    }
+
+    for action in InputAction::ALL {
+        let pressed = bindings
+            .get(action)
+            .iter()
+            .any(|binding| binding_pressed(binding, &keyboard, &mouse, wheel, &gamepads));
+        if pressed {
+            actions.pressed.insert(action);
+            if !previous.contains(&action) {
+                actions.just_pressed.insert(action);
+            }
+        } else if previous.contains(&action) {
+            actions.just_released.insert(action);
+        }
+    }
+}
+
+fn toggle_keybindings_menu(
+    actions: Res<ActionState>,
+    mut menu: ResMut<KeybindingsMenu>,
+) {
+    if actions.just_pressed(InputAction::OpenKeybindings) {
+        menu.open = !menu.open;
+        menu.waiting_for = None;
+    }
+}
+
+///
+/// When an action is waiting for a new binding, the next keyboard key, mouse button or
+/// gamepad button pressed becomes its primary binding. Escape cancels.
+///
+fn capture_rebinding(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut bindings: ResMut<InputBindings>,
+    mut menu: ResMut<KeybindingsMenu>,
+) {
+    let Some(action) = menu.waiting_for else { return; };
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        menu.waiting_for = None;
+        menu.status = "Rebinding cancelled.".into();
+        return;
+    }
+
+    let new_binding = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|key| InputBinding::Key(*key))
+        .or_else(|| mouse.get_just_pressed().next().map(|button| InputBinding::Mouse(*button)))
+        .or_else(|| {
+            gamepads
+                .iter()
+                .find_map(|gamepad| gamepad.get_just_pressed().next().copied())
+                .map(InputBinding::Gamepad)
+        });
+
+    if let Some(new_binding) = new_binding {
+        let action_bindings = bindings.bindings.entry(action).or_default();
+        action_bindings.retain(|binding| *binding != new_binding);
+        action_bindings.insert(0, new_binding);
+        menu.status = format!("{:?} bound to {}", action, binding_label(&new_binding));
+        menu.waiting_for = None;
+    }
+}
+
+fn binding_label(binding: &InputBinding) -> String {
+    match binding {
+        InputBinding::Key(key) => format!("{:?}", key),
+        InputBinding::Mouse(button) => format!("Mouse {:?}", button),
+        InputBinding::MouseWheelUp => "Wheel up".into(),
+        InputBinding::MouseWheelDown => "Wheel down".into(),
+        InputBinding::Gamepad(button) => format!("Pad {:?}", button),
+        InputBinding::GamepadAxisPositive(axis) => format!("Pad {:?}+", axis),
+        InputBinding::GamepadAxisNegative(axis) => format!("Pad {:?}-", axis),
+    }
+}
+
+fn keybindings_ui(
+    mut contexts: EguiContexts,
+    mut menu: ResMut<KeybindingsMenu>,
+    mut bindings: ResMut<InputBindings>,
+) -> Result {
+    if !menu.open {
+        return Ok(());
+    }
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Key bindings").show(ctx, |ui| {
+        egui::Grid::new("keybindings_grid").striped(true).show(ui, |ui| {
+            for action in InputAction::ALL {
+                ui.label(format!("{:?}", action));
+                let labels: Vec<String> = bindings.get(action).iter().map(binding_label).collect();
+                ui.label(labels.join(", "));
+                let waiting = menu.waiting_for == Some(action);
+                let text = if waiting { "Press a key..." } else { "Rebind" };
+                if ui.add(egui::Button::new(text)).clicked() {
+                    menu.waiting_for = Some(action);
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.add(egui::Button::new("Save")).clicked() {
+                menu.status = match bindings.save(INPUT_BINDINGS_PATH) {
+                    Ok(()) => format!("Saved to {}", INPUT_BINDINGS_PATH),
+                    Err(e) => format!("Could not save bindings: {}", e),
+                };
+            }
+            if ui.add(egui::Button::new("Reset to defaults")).clicked() {
+                *bindings = InputBindings::default();
+                menu.status = "Bindings reset to defaults.".into();
+            }
+            if ui.add(egui::Button::new("Close")).clicked() {
+                menu.open = false;
+                menu.waiting_for = None;
+            }
+        });
+        if !menu.status.is_empty() {
+            ui.label(&menu.status);
+        }
+    });
+    Ok(())
+}
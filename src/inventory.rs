@@ -0,0 +1,721 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass, EguiTextureHandle};
+use serde::Deserialize;
+
+use crate::assets::RonAssetAppExt;
+use crate::combat::{actor_name, Dead};
+use crate::constants::TILE_SIZE_PX;
+use crate::events::{ChunkLoaded, ChunkUnloaded};
+use crate::game_log::GameLog;
+use crate::input::{ActionState, InputAction};
+use crate::map::{tile_coords_to_world, tile_to_chunk_coords, world_to_tile_coords};
+use crate::player::Player;
+use crate::states::in_play;
+use crate::stats::{AttributeBonus, Attributes, Health, Mana, StatusEffect, StatusEffects};
+use crate::turn::{PassTurn, TurnSystems};
+
+const ITEM_LIBRARY_PATH: &str = "items/items.items.ron";
+// Depth of the items lying on the ground, above the tilemap and below the characters.
+const GROUND_ITEM_Z: f32 = 2.0;
+const INVENTORY_ICON_SIZE: f32 = 24.0;
+
+///
+/// Every item of the game, loaded from a `*.items.ron` file.
+///
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct ItemLibrary {
+    pub items: Vec<ItemDef>,
+}
+
+impl ItemLibrary {
+    pub fn get(&self, id: &str) -> Option<&ItemDef> {
+        self.items.iter().find(|item| item.id == id)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemDef {
+    pub id: String,
+    pub name: String,
+    /// Icon path, relative to the assets folder. Also used to draw the item on the ground.
+    pub icon: String,
+    /// Weight of a single item.
+    pub weight: f32,
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    /// Slot the item can be equipped in, if any.
+    #[serde(default)]
+    pub slot: Option<EquipmentSlot>,
+    /// Attributes granted while the item is equipped.
+    #[serde(default = "no_attributes")]
+    pub attributes: Attributes,
+    /// Sprite sheet drawn over the character while equipped. It must use the same layout as
+    /// the character sheet, the layer follows the character animation frame by frame.
+    #[serde(default)]
+    pub sprite_layer: Option<String>,
+    /// What happens when the item is used. Used items are consumed.
+    #[serde(default)]
+    pub use_effect: Option<ItemUse>,
+}
+
+fn default_max_stack() -> u32 {
+    1
+}
+
+fn no_attributes() -> Attributes {
+    Attributes::ZERO
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum ItemUse {
+    Heal { amount: i32 },
+    RestoreMana { amount: i32 },
+    ApplyStatus(StatusEffect),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum EquipmentSlot {
+    Head,
+    Body,
+    Legs,
+    Feet,
+    MainHand,
+    OffHand,
+    Ring,
+    Amulet,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 8] = [
+        EquipmentSlot::Head,
+        EquipmentSlot::Body,
+        EquipmentSlot::Legs,
+        EquipmentSlot::Feet,
+        EquipmentSlot::MainHand,
+        EquipmentSlot::OffHand,
+        EquipmentSlot::Ring,
+        EquipmentSlot::Amulet,
+    ];
+
+    ///
+    /// Depth of the sprite layer relative to the character, so that e.g. the weapon is drawn
+    /// over the armour.
+    ///
+    fn layer_depth(&self) -> f32 {
+        match self {
+            EquipmentSlot::Legs => 0.01,
+            EquipmentSlot::Feet => 0.02,
+            EquipmentSlot::Body => 0.03,
+            EquipmentSlot::Amulet => 0.04,
+            EquipmentSlot::Ring => 0.04,
+            EquipmentSlot::Head => 0.05,
+            EquipmentSlot::OffHand => 0.06,
+            EquipmentSlot::MainHand => 0.07,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: &str, count: u32) -> Self {
+        ItemStack { item: item.to_string(), count }
+    }
+}
+
+///
+/// Items carried by an actor. Equipped items are not in the inventory but still count
+/// towards the weight limit.
+///
+#[derive(Component, Debug, Clone)]
+pub struct Inventory {
+    pub items: Vec<ItemStack>,
+    pub max_weight: f32,
+}
+
+impl Inventory {
+    pub fn new(max_weight: f32, items: Vec<ItemStack>) -> Self {
+        Inventory { items, max_weight }
+    }
+
+    pub fn weight(&self, library: &ItemLibrary) -> f32 {
+        self.items
+            .iter()
+            .filter_map(|stack| library.get(&stack.item).map(|def| def.weight * stack.count as f32))
+            .sum()
+    }
+
+    ///
+    /// Adds items, filling the existing stacks before opening new ones.
+    ///
+    pub fn add(&mut self, def: &ItemDef, mut count: u32) {
+        for stack in self.items.iter_mut().filter(|stack| stack.item == def.id) {
+            let moved = count.min(def.max_stack.saturating_sub(stack.count));
+            stack.count += moved;
+            count -= moved;
+        }
+        while count > 0 {
+            let moved = count.min(def.max_stack.max(1));
+            self.items.push(ItemStack::new(&def.id, moved));
+            count -= moved;
+        }
+    }
+
+    ///
+    /// Takes up to `count` items from the stack at `index`, removing the stack once empty.
+    ///
+    pub fn take(&mut self, index: usize, count: u32) -> Option<ItemStack> {
+        let stack = self.items.get_mut(index)?;
+        let taken = count.min(stack.count);
+        stack.count -= taken;
+        let item = stack.item.clone();
+        if stack.count == 0 {
+            self.items.remove(index);
+        }
+        Some(ItemStack { item, count: taken })
+    }
+}
+
+///
+/// How many of `count` items fit in the weight left to an actor. Weightless items always fit.
+///
+fn fitting_count(def: &ItemDef, free_weight: f32, count: u32) -> u32 {
+    if def.weight > 0.0 {
+        ((free_weight / def.weight).floor().max(0.0) as u32).min(count)
+    } else {
+        count
+    }
+}
+
+///
+/// Items worn by an actor. Their attributes are summed into the actor's [`AttributeBonus`].
+///
+#[derive(Component, Debug, Default, Clone)]
+#[require(AttributeBonus)]
+pub struct Equipment {
+    pub slots: BTreeMap<EquipmentSlot, String>,
+}
+
+impl Equipment {
+    pub fn weight(&self, library: &ItemLibrary) -> f32 {
+        self.slots.values().filter_map(|id| library.get(id)).map(|def| def.weight).sum()
+    }
+}
+
+///
+/// Items lying on a tile.
+///
+#[derive(Component, Debug, Clone)]
+pub struct GroundItem {
+    pub stack: ItemStack,
+}
+
+///
+/// Sprite drawn over an actor for one of its equipped items.
+///
+#[derive(Component, Debug)]
+struct EquipmentLayer;
+
+///
+/// Ground items of the chunks that are not spawned, restored when their chunk comes back.
+///
+#[derive(Resource, Debug, Default)]
+pub struct ChunkItems {
+    stashed: HashMap<IVec2, Vec<(IVec2, ItemStack)>>,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct InventoryScreen {
+    pub open: bool,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct ItemLibraryHandle(pub Handle<ItemLibrary>);
+
+#[derive(Resource, Debug, Default)]
+struct ItemIcons {
+    textures: HashMap<String, (Handle<Image>, egui::TextureId)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InventoryAction {
+    /// Picks up what lies on the actor's tile.
+    PickUp,
+    Use(usize),
+    Equip(usize),
+    Unequip(EquipmentSlot),
+    Drop(usize),
+}
+
+///
+/// Asks for an actor to do something with its inventory. Every action takes a turn.
+///
+#[derive(Message, Debug, Clone, Copy)]
+pub struct InventoryRequest {
+    pub actor: Entity,
+    pub action: InventoryAction,
+}
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.add_ron_asset::<ItemLibrary>(&["items.ron"])
+            .init_resource::<ItemLibraryHandle>()
+            .init_resource::<ItemIcons>()
+            .init_resource::<ChunkItems>()
+            .init_resource::<InventoryScreen>()
+            .add_message::<InventoryRequest>()
+            .add_systems(Startup, load_item_library)
+            .add_systems(Update, (
+                (toggle_inventory_screen, pick_up_input).run_if(in_play),
+                handle_inventory_requests,
+                apply_equipment_bonus,
+                rebuild_equipment_layers,
+            ).chain().before(TurnSystems))
+            .add_systems(Update, (stash_chunk_items, restore_chunk_items))
+            .add_systems(PostUpdate, follow_character_frames)
+            .add_systems(EguiPrimaryContextPass, inventory_ui);
+    }
+}
+
+fn load_item_library(
+    asset_server: Res<AssetServer>,
+    mut library_handle: ResMut<ItemLibraryHandle>,
+) {
+    library_handle.0 = asset_server.load(ITEM_LIBRARY_PATH);
+}
+
+pub fn spawn_ground_item(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    def: &ItemDef,
+    stack: ItemStack,
+    tile: IVec2,
+) -> Entity {
+    let mut sprite = Sprite::from_image(asset_server.load(&def.icon));
+    sprite.custom_size = Some(Vec2::splat(TILE_SIZE_PX * 0.75));
+    commands
+        .spawn((
+            Name::new(def.name.clone()),
+            GroundItem { stack },
+            sprite,
+            Transform::from_translation(tile_coords_to_world(tile, GROUND_ITEM_Z)),
+        ))
+        .id()
+}
+
+fn toggle_inventory_screen(
+    actions: Res<ActionState>,
+    mut screen: ResMut<InventoryScreen>,
+) {
+    if actions.just_pressed(InputAction::OpenInventory) {
+        screen.open = !screen.open;
+    }
+}
+
+fn pick_up_input(
+    actions: Res<ActionState>,
+    player_query: Query<Entity, (With<Player>, With<Inventory>, Without<Dead>)>,
+    mut requests: MessageWriter<InventoryRequest>,
+) {
+    if !actions.just_pressed(InputAction::Interact) {
+        return;
+    }
+    if let Ok(player) = player_query.single() {
+        requests.write(InventoryRequest { actor: player, action: InventoryAction::PickUp });
+    }
+}
+
+fn handle_inventory_requests(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    mut requests: MessageReader<InventoryRequest>,
+    mut actors: Query<
+        (&Transform, &mut Inventory, Option<&mut Equipment>, &mut Health, &mut Mana, &mut StatusEffects, Option<&Name>),
+        Without<Dead>,
+    >,
+    mut ground_items: Query<(Entity, &Transform, &mut GroundItem)>,
+    player_query: Query<(), With<Player>>,
+    mut log: ResMut<GameLog>,
+    mut pass_turn: MessageWriter<PassTurn>,
+) {
+    let Some(library) = libraries.get(&library_handle.0) else {
+        requests.clear();
+        return;
+    };
+
+    for request in requests.read() {
+        let Ok((transform, mut inventory, mut equipment, mut health, mut mana, mut effects, name)) =
+            actors.get_mut(request.actor)
+        else {
+            continue;
+        };
+        let name = actor_name(name);
+        let tile = world_to_tile_coords(transform.translation);
+        let carried = inventory.weight(library) + equipment.as_ref().map_or(0.0, |e| e.weight(library));
+
+        let acted = match request.action {
+            InventoryAction::PickUp => {
+                let mut free_weight = inventory.max_weight - carried;
+                let mut picked = false;
+                for (entity, item_transform, mut ground_item) in ground_items.iter_mut() {
+                    if world_to_tile_coords(item_transform.translation) != tile {
+                        continue;
+                    }
+                    let Some(def) = library.get(&ground_item.stack.item) else { continue; };
+                    let fitting = fitting_count(def, free_weight, ground_item.stack.count);
+                    if fitting == 0 {
+                        log.info(format!("{} cannot carry {}, too heavy.", name, def.name));
+                        continue;
+                    }
+                    inventory.add(def, fitting);
+                    free_weight -= def.weight * fitting as f32;
+                    ground_item.stack.count -= fitting;
+                    if ground_item.stack.count == 0 {
+                        commands.entity(entity).despawn();
+                    }
+                    log.info(format!("{} picks up {} x{}.", name, def.name, fitting));
+                    picked = true;
+                }
+                picked
+            }
+            InventoryAction::Use(index) => {
+                let Some(def) = inventory.items.get(index).and_then(|stack| library.get(&stack.item)) else { continue; };
+                let Some(use_effect) = &def.use_effect else {
+                    log.info(format!("{} cannot be used.", def.name));
+                    continue;
+                };
+                match use_effect {
+                    ItemUse::Heal { amount } => {
+                        health.current = (health.current + amount).min(health.max);
+                    }
+                    ItemUse::RestoreMana { amount } => {
+                        mana.current = (mana.current + amount).min(mana.max);
+                    }
+                    ItemUse::ApplyStatus(status) => effects.apply(*status),
+                }
+                inventory.take(index, 1);
+                log.info(format!("{} uses {}.", name, def.name));
+                true
+            }
+            InventoryAction::Equip(index) => {
+                let Some(equipment) = equipment.as_mut() else { continue; };
+                let Some(def) = inventory.items.get(index).and_then(|stack| library.get(&stack.item)) else { continue; };
+                let Some(slot) = def.slot else {
+                    log.info(format!("{} cannot be equipped.", def.name));
+                    continue;
+                };
+                inventory.take(index, 1);
+                if let Some(previous) = equipment.slots.insert(slot, def.id.clone())
+                    && let Some(previous) = library.get(&previous)
+                {
+                    inventory.add(previous, 1);
+                }
+                log.info(format!("{} equips {}.", name, def.name));
+                true
+            }
+            InventoryAction::Unequip(slot) => {
+                let Some(equipment) = equipment.as_mut() else { continue; };
+                let Some(def) = equipment.slots.remove(&slot).and_then(|id| library.get(&id)) else { continue; };
+                inventory.add(def, 1);
+                log.info(format!("{} removes {}.", name, def.name));
+                true
+            }
+            InventoryAction::Drop(index) => {
+                let Some(def) = inventory.items.get(index).and_then(|stack| library.get(&stack.item)) else { continue; };
+                let Some(stack) = inventory.take(index, u32::MAX) else { continue; };
+                log.info(format!("{} drops {} x{}.", name, def.name, stack.count));
+                spawn_ground_item(&mut commands, &asset_server, def, stack, tile);
+                true
+            }
+        };
+
+        if acted && player_query.contains(request.actor) {
+            pass_turn.write(PassTurn);
+        }
+    }
+}
+
+fn apply_equipment_bonus(
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    mut library_events: MessageReader<AssetEvent<ItemLibrary>>,
+    mut actors: Query<(Ref<Equipment>, &mut AttributeBonus)>,
+) {
+    let Some(library) = libraries.get(&library_handle.0) else { return; };
+    // Equipment given before the library finished loading is only applied once it is there.
+    let library_changed = library_events.read().count() > 0;
+
+    for (equipment, mut bonus) in actors.iter_mut() {
+        if !equipment.is_changed() && !library_changed {
+            continue;
+        }
+        bonus.0 = equipment
+            .slots
+            .values()
+            .filter_map(|id| library.get(id))
+            .fold(Attributes::ZERO, |total, def| total.add(&def.attributes));
+    }
+}
+
+///
+/// Respawns the sprite layers of the actors whose equipment changed.
+///
+fn rebuild_equipment_layers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    mut library_events: MessageReader<AssetEvent<ItemLibrary>>,
+    actors: Query<(Entity, Ref<Equipment>, Option<&Children>)>,
+    layers: Query<(), With<EquipmentLayer>>,
+) {
+    let Some(library) = libraries.get(&library_handle.0) else { return; };
+    let library_changed = library_events.read().count() > 0;
+
+    for (entity, equipment, children) in actors.iter() {
+        if !equipment.is_changed() && !library_changed {
+            continue;
+        }
+        for child in children.into_iter().flatten() {
+            if layers.contains(*child) {
+                commands.entity(*child).despawn();
+            }
+        }
+        for (slot, id) in equipment.slots.iter() {
+            let Some(layer) = library.get(id).and_then(|def| def.sprite_layer.as_ref()) else { continue; };
+            commands.spawn((
+                EquipmentLayer,
+                Sprite::from_image(asset_server.load(layer)),
+                Transform::from_xyz(0.0, 0.0, slot.layer_depth()),
+                ChildOf(entity),
+            ));
+        }
+    }
+}
+
+///
+/// Equipment layers show the same atlas frame as the character they are attached to.
+///
+fn follow_character_frames(
+    characters: Query<&Sprite, Without<EquipmentLayer>>,
+    mut layers: Query<(&ChildOf, &mut Sprite), With<EquipmentLayer>>,
+) {
+    for (child_of, mut sprite) in layers.iter_mut() {
+        let Ok(character) = characters.get(child_of.parent()) else { continue; };
+        if sprite.texture_atlas != character.texture_atlas {
+            sprite.texture_atlas = character.texture_atlas.clone();
+        }
+        sprite.flip_x = character.flip_x;
+    }
+}
+
+///
+/// Ground items of an unloaded chunk are saved and despawned with it.
+///
+fn stash_chunk_items(
+    mut commands: Commands,
+    mut unloaded: MessageReader<ChunkUnloaded>,
+    ground_items: Query<(Entity, &Transform, &GroundItem)>,
+    mut chunk_items: ResMut<ChunkItems>,
+) {
+    for event in unloaded.read() {
+        for (entity, transform, ground_item) in ground_items.iter() {
+            let tile = world_to_tile_coords(transform.translation);
+            if tile_to_chunk_coords(tile) != event.chunk {
+                continue;
+            }
+            chunk_items
+                .stashed
+                .entry(event.chunk)
+                .or_default()
+                .push((tile, ground_item.stack.clone()));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn restore_chunk_items(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    mut loaded: MessageReader<ChunkLoaded>,
+    mut chunk_items: ResMut<ChunkItems>,
+) {
+    let Some(library) = libraries.get(&library_handle.0) else { return; };
+    for event in loaded.read() {
+        let Some(items) = chunk_items.stashed.remove(&event.chunk) else { continue; };
+        for (tile, stack) in items {
+            let Some(def) = library.get(&stack.item) else { continue; };
+            spawn_ground_item(&mut commands, &asset_server, def, stack, tile);
+        }
+    }
+}
+
+fn inventory_ui(
+    mut contexts: EguiContexts,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    player_query: Query<(Entity, &Inventory, Option<&Equipment>), With<Player>>,
+    mut screen: ResMut<InventoryScreen>,
+    mut icons: ResMut<ItemIcons>,
+    mut requests: MessageWriter<InventoryRequest>,
+) -> Result {
+    if !screen.open {
+        return Ok(());
+    }
+    let Ok((player, inventory, equipment)) = player_query.single() else { return Ok(()); };
+    let Some(library) = libraries.get(&library_handle.0) else { return Ok(()); };
+
+    // Icons must be registered with egui before the context is borrowed for drawing.
+    let shown = inventory
+        .items
+        .iter()
+        .map(|stack| &stack.item)
+        .chain(equipment.into_iter().flat_map(|equipment| equipment.slots.values()));
+    for id in shown {
+        let Some(def) = library.get(id) else { continue; };
+        if !icons.textures.contains_key(id) {
+            let handle: Handle<Image> = asset_server.load(&def.icon);
+            let texture_id = contexts.add_image(EguiTextureHandle::Strong(handle.clone()));
+            icons.textures.insert(id.clone(), (handle, texture_id));
+        }
+    }
+    let icon = |ui: &mut egui::Ui, id: &str| {
+        match icons.textures.get(id).filter(|(handle, _)| asset_server.is_loaded(handle)) {
+            Some((_, texture_id)) => {
+                ui.image(egui::load::SizedTexture::new(*texture_id, [INVENTORY_ICON_SIZE, INVENTORY_ICON_SIZE]));
+            }
+            None => {
+                ui.label("");
+            }
+        }
+    };
+
+    let carried = inventory.weight(library) + equipment.map_or(0.0, |e| e.weight(library));
+    let mut open = screen.open;
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Inventory").open(&mut open).show(ctx, |ui| {
+        ui.label(format!("Weight: {:.1} / {:.1}", carried, inventory.max_weight));
+        ui.separator();
+
+        egui::Grid::new("inventory_grid").striped(true).show(ui, |ui| {
+            for (index, stack) in inventory.items.iter().enumerate() {
+                let Some(def) = library.get(&stack.item) else { continue; };
+                icon(ui, &def.id);
+                ui.label(format!("{} x{}", def.name, stack.count));
+                ui.label(format!("{:.1}", def.weight * stack.count as f32));
+                if def.use_effect.is_some() && ui.button("Use").clicked() {
+                    requests.write(InventoryRequest { actor: player, action: InventoryAction::Use(index) });
+                }
+                if def.slot.is_some() && equipment.is_some() && ui.button("Equip").clicked() {
+                    requests.write(InventoryRequest { actor: player, action: InventoryAction::Equip(index) });
+                }
+                if ui.button("Drop").clicked() {
+                    requests.write(InventoryRequest { actor: player, action: InventoryAction::Drop(index) });
+                }
+                ui.end_row();
+            }
+        });
+
+        let Some(equipment) = equipment else { return; };
+        ui.separator();
+        ui.heading("Equipment");
+        egui::Grid::new("equipment_grid").striped(true).show(ui, |ui| {
+            for slot in EquipmentSlot::ALL {
+                ui.label(format!("{:?}", slot));
+                match equipment.slots.get(&slot).and_then(|id| library.get(id)) {
+                    Some(def) => {
+                        icon(ui, &def.id);
+                        ui.label(&def.name);
+                        if ui.button("Remove").clicked() {
+                            requests.write(InventoryRequest { actor: player, action: InventoryAction::Unequip(slot) });
+                        }
+                    }
+                    None => {
+                        ui.label("");
+                        ui.label("-");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+    screen.open = open;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, weight: f32, max_stack: u32) -> ItemDef {
+        ItemDef {
+            id: id.to_string(),
+            name: id.to_string(),
+            icon: String::new(),
+            weight,
+            max_stack,
+            slot: None,
+            attributes: Attributes::ZERO,
+            sprite_layer: None,
+            use_effect: None,
+        }
+    }
+
+    fn library() -> ItemLibrary {
+        ItemLibrary {
+            items: vec![item("arrow", 0.1, 20), item("sword", 4.0, 1), item("feather", 0.0, 99), item("helm", 2.5, 1)],
+        }
+    }
+
+    #[test]
+    fn weight_counts_stacks_and_equipment() {
+        let library = library();
+        let items = vec![ItemStack::new("arrow", 15), ItemStack::new("sword", 1), ItemStack::new("feather", 5)];
+        let inventory = Inventory::new(10.0, items);
+        assert!((inventory.weight(&library) - 5.5).abs() < 1e-4);
+
+        let equipment = Equipment { slots: BTreeMap::from([(EquipmentSlot::Head, "helm".to_string())]) };
+        assert!((equipment.weight(&library) - 2.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn only_the_items_under_the_limit_fit() {
+        let library = library();
+        let [arrow, sword, feather] = ["arrow", "sword", "feather"].map(|id| library.get(id).unwrap());
+        assert_eq!(fitting_count(sword, 9.0, 3), 2);
+        assert_eq!(fitting_count(sword, 3.9, 1), 0);
+        assert_eq!(fitting_count(arrow, 1.05, 20), 10);
+        assert_eq!(fitting_count(arrow, 5.0, 20), 20);
+        // Equipment can take an actor over the limit, nothing fits then.
+        assert_eq!(fitting_count(arrow, -1.0, 20), 0);
+        assert_eq!(fitting_count(feather, 0.0, 50), 50);
+    }
+
+    #[test]
+    fn adding_fills_stacks_before_opening_new_ones() {
+        let library = library();
+        let arrow = library.get("arrow").unwrap();
+        let mut inventory = Inventory::new(10.0, vec![ItemStack::new("arrow", 15)]);
+        inventory.add(arrow, 30);
+        assert_eq!(inventory.items, vec![ItemStack::new("arrow", 20), ItemStack::new("arrow", 20), ItemStack::new("arrow", 5)]);
+
+        assert_eq!(inventory.take(2, 10), Some(ItemStack::new("arrow", 5)));
+        assert_eq!(inventory.items.len(), 2);
+    }
+}
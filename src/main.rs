@@ -14,16 +14,58 @@ use bevy::{
     window::{PresentMode, WindowResolution},
 };
 
+mod animation;
+mod assets;
 mod constants;
+mod game_log;
+mod input;
+mod rng;
 mod tile_type;
 use constants::*;
 
 mod player;
 use player::*;
+use input::InputActionsPlugin;
+
+mod stats;
+use stats::StatsPlugin;
+
+mod turn;
+use turn::TurnPlugin;
+
+mod world_clock;
+use world_clock::WorldClockPlugin;
+
+mod weather;
+use weather::WeatherPlugin;
+
+mod combat;
+use combat::CombatPlugin;
+use game_log::GameLogPlugin;
+
+mod spells;
+use spells::SpellsPlugin;
+
+mod inventory;
+use inventory::InventoryPlugin;
+
+mod monsters;
+use monsters::MonstersPlugin;
+
+mod ai;
+use ai::AiPlugin;
 
 mod map;
 use crate::map::{
+    automap::AutomapPlugin,
+    autotile::AutotilePlugin,
+    collision::CollisionPlugin,
+    dungeon::DungeonPlugin,
+    ground_atlas::GroundAtlasPlugin,
+    map_objects::MapObjectsPlugin,
     overworld_map::OverWorldMapPlugin,
+    tile_animation::TileAnimationPlugin,
+    tile_grid::TileGridPlugin,
     world_map::WorldMapPlugin,
     world_gen_island::WorldGenIslandPlugin,
 };
@@ -38,6 +80,10 @@ fn main() {
     App::new()
         .add_message::<MoveEvent>()
         .add_message::<MoveLegal>()
+        .add_message::<ChunkLoaded>()
+        .add_message::<ChunkUnloaded>()
+        .add_message::<GroundChanged>()
+        .add_message::<MapTriggered>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Void destiny - The roguelike game!".into(),
@@ -50,10 +96,29 @@ fn main() {
         .set(ImagePlugin::default_nearest()),
         )
         .init_state::<GameState>()
+        .add_plugins(InputActionsPlugin)
+        .add_plugins(TurnPlugin)
+        .add_plugins(WorldClockPlugin)
+        .add_plugins(WeatherPlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(GameLogPlugin)
+        .add_plugins(CombatPlugin)
+        .add_plugins(SpellsPlugin)
+        .add_plugins(InventoryPlugin)
+        .add_plugins(MonstersPlugin)
+        .add_plugins(AiPlugin)
+        .add_plugins(AutomapPlugin)
+        .add_plugins(GroundAtlasPlugin)
+        .add_plugins(AutotilePlugin)
+        .add_plugins(TileAnimationPlugin)
+        .add_plugins(TileGridPlugin)
         //.add_plugins(PlayerPlugin)
         //.add_plugins(WorldMapPlugin)
         //.add_plugins(OverWorldMapPlugin)
         .add_plugins(WorldGenIslandPlugin)
+        .add_plugins(MapObjectsPlugin)
+        .add_plugins(CollisionPlugin)
+        .add_plugins(DungeonPlugin)
         .run();
 }
 
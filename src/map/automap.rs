@@ -0,0 +1,700 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::constants::AUTOMAP_RULES_PATH;
+
+// Tileset Tiled provides for the special tiles of rule maps.
+const AUTOMAP_TILESET: &str = "automap-tiles";
+// Tiled stores the flip flags of a tile in the high bits of its gid.
+const GID_FLAGS: u32 = 0xE000_0000;
+
+///
+/// Tile of a tileset, identified by the tileset name (the file stem of its `.tsx`) and the
+/// index of the tile in it.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TileRef {
+    tileset: String,
+    id: u32,
+}
+
+///
+/// What a cell of an input layer accepts. The special tiles of the automap tileset come first,
+/// in the order Tiled defines them.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CellMatch {
+    Empty,
+    Ignore,
+    NonEmpty,
+    /// Any tile that is not used by the input layers of the rule for that layer.
+    Other,
+    Negate,
+    Tile(TileRef),
+}
+
+impl CellMatch {
+    fn from_tile(tile: TileRef) -> Self {
+        if tile.tileset != AUTOMAP_TILESET {
+            return CellMatch::Tile(tile);
+        }
+        match tile.id {
+            0 => CellMatch::Empty,
+            1 => CellMatch::Ignore,
+            2 => CellMatch::NonEmpty,
+            3 => CellMatch::Other,
+            4 => CellMatch::Negate,
+            _ => CellMatch::Ignore,
+        }
+    }
+}
+
+///
+/// Named tile layers of a single tileset, all of the same size, that rules are applied to.
+/// Positions have `y` going up like the tilemaps, while rule maps are authored with `y` going
+/// down: a rule tile painted above another one applies to the tile above.
+///
+#[derive(Debug, Clone)]
+pub struct TileLayers {
+    pub width: i32,
+    pub height: i32,
+    pub tileset: String,
+    layers: BTreeMap<String, Vec<Option<u32>>>,
+}
+
+impl TileLayers {
+    pub fn new(width: i32, height: i32, tileset: &str) -> Self {
+        TileLayers {
+            width,
+            height,
+            tileset: tileset.to_string(),
+            layers: BTreeMap::new(),
+        }
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width && pos.y < self.height
+    }
+
+    pub fn get(&self, layer: &str, pos: IVec2) -> Option<u32> {
+        if !self.contains(pos) {
+            return None;
+        }
+        self.layers.get(layer)?[(pos.y * self.width + pos.x) as usize]
+    }
+
+    ///
+    /// Sets a tile, creating the layer when it does not exist yet.
+    ///
+    pub fn set(&mut self, layer: &str, pos: IVec2, tile: Option<u32>) {
+        if !self.contains(pos) {
+            return;
+        }
+        let size = (self.width * self.height) as usize;
+        let cells = self.layers.entry(layer.to_string()).or_insert_with(|| vec![None; size]);
+        cells[(pos.y * self.width + pos.x) as usize] = tile;
+    }
+
+    fn matches(&self, layer: &str, pos: IVec2, entry: &CellMatch, others: &HashSet<TileRef>) -> bool {
+        let tile = self.get(layer, pos);
+        match entry {
+            CellMatch::Empty => tile.is_none(),
+            CellMatch::Ignore | CellMatch::Negate => true,
+            CellMatch::NonEmpty => tile.is_some(),
+            CellMatch::Other => tile.is_some_and(|id| {
+                !others.contains(&TileRef { tileset: self.tileset.clone(), id })
+            }),
+            CellMatch::Tile(expected) => expected.tileset == self.tileset && tile == Some(expected.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct InputCell {
+    layer: String,
+    offset: IVec2,
+    accepted: Vec<CellMatch>,
+}
+
+#[derive(Debug, Clone)]
+struct OutputCell {
+    layer: String,
+    offset: IVec2,
+    /// `None` erases the tile.
+    tile: Option<TileRef>,
+}
+
+///
+/// One rule of a rule map: the input cells that must match around a position and the
+/// alternative outputs, one of them picked at random when several exist.
+///
+#[derive(Debug, Clone)]
+struct Rule {
+    inputs: Vec<InputCell>,
+    /// Tiles used by the inputs of each layer, which `Other` does not match.
+    used_tiles: HashMap<String, HashSet<TileRef>>,
+    outputs: Vec<Vec<OutputCell>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct RuleOptions {
+    /// Inputs may lie outside of the map, where they read as empty cells.
+    match_outside_map: bool,
+    /// Empty input cells of a rule only match empty cells instead of anything.
+    strict_empty: bool,
+    /// A rule does not write over tiles it has already written.
+    no_overlapping_output: bool,
+}
+
+#[derive(Debug, Clone)]
+struct RuleMap {
+    path: String,
+    options: RuleOptions,
+    rules: Vec<Rule>,
+}
+
+///
+/// Automapping rules in Tiled's format, loaded from the rule file listing the rule maps at
+/// `AUTOMAP_RULES_PATH`. Generated maps go through them to get the same decoration as the
+/// maps drawn in Tiled.
+///
+#[derive(Resource, Debug, Clone, Default)]
+pub struct AutomapRules {
+    maps: Vec<RuleMap>,
+}
+
+impl AutomapRules {
+    ///
+    /// Loads the rule maps listed in a rules file. Missing or invalid files are reported and
+    /// skipped, the game then runs with fewer rules.
+    ///
+    pub fn load_or_default(path: &str) -> Self {
+        let mut rules = AutomapRules::default();
+        rules.load_rules_file(Path::new(path));
+        info!("Loaded {} automapping rule maps from {}.", rules.maps.len(), path);
+        rules
+    }
+
+    fn load_rules_file(&mut self, path: &Path) {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Cannot read automapping rules {}: {}.", path.display(), e);
+                return;
+            }
+        };
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for line in content.lines().map(str::trim) {
+            // Map name filters (`[pattern]`) are not supported, the rules apply to every map.
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") || line.starts_with('[') {
+                continue;
+            }
+            let entry = directory.join(line);
+            if line.ends_with(".txt") {
+                self.load_rules_file(&entry);
+                continue;
+            }
+            match fs::read_to_string(&entry).map_err(|e| e.to_string()).and_then(|content| parse_rule_map(&content)) {
+                Ok((options, rules)) => self.maps.push(RuleMap { path: entry.display().to_string(), options, rules }),
+                Err(e) => warn!("Invalid automapping rule map {}: {}.", entry.display(), e),
+            }
+        }
+    }
+
+    ///
+    /// Applies every rule map in order. Each rule goes over the whole map before the next one,
+    /// so later rules see what earlier ones wrote.
+    ///
+    pub fn apply(&self, target: &mut TileLayers, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for map in &self.maps {
+            let mut applied = 0;
+            for rule in &map.rules {
+                applied += apply_rule(rule, &map.options, target, &mut rng);
+            }
+            if applied > 0 {
+                debug!("Automapping rules {} applied {} times.", map.path, applied);
+            }
+        }
+    }
+}
+
+fn apply_rule(rule: &Rule, options: &RuleOptions, target: &mut TileLayers, rng: &mut StdRng) -> usize {
+    if rule.outputs.is_empty() {
+        return 0;
+    }
+    let mut written: HashSet<(String, IVec2)> = HashSet::new();
+    let mut applied = 0;
+    for y in 0..target.height {
+        for x in 0..target.width {
+            let pos = IVec2::new(x, y);
+            if !rule_matches(rule, options, target, pos) {
+                continue;
+            }
+            let output = &rule.outputs[rng.random_range(0..rule.outputs.len())];
+            if options.no_overlapping_output
+                && output.iter().any(|cell| written.contains(&(cell.layer.clone(), pos + cell.offset)))
+            {
+                continue;
+            }
+            for cell in output {
+                let tile = match &cell.tile {
+                    Some(tile) if tile.tileset == target.tileset => Some(tile.id),
+                    // Tiles of another tileset cannot be drawn on these layers.
+                    Some(_) => continue,
+                    None => None,
+                };
+                target.set(&cell.layer, pos + cell.offset, tile);
+                written.insert((cell.layer.clone(), pos + cell.offset));
+            }
+            applied += 1;
+        }
+    }
+    applied
+}
+
+fn rule_matches(rule: &Rule, options: &RuleOptions, target: &TileLayers, pos: IVec2) -> bool {
+    let no_tiles = HashSet::new();
+    rule.inputs.iter().all(|input| {
+        if input.accepted.is_empty() && !options.strict_empty {
+            return true;
+        }
+        let cell_pos = pos + input.offset;
+        if !target.contains(cell_pos) && !options.match_outside_map {
+            return false;
+        }
+        if input.accepted.is_empty() {
+            return target.get(&input.layer, cell_pos).is_none();
+        }
+        let others = rule.used_tiles.get(&input.layer).unwrap_or(&no_tiles);
+        let negated = input.accepted.contains(&CellMatch::Negate);
+        let matched = input
+            .accepted
+            .iter()
+            .filter(|entry| **entry != CellMatch::Negate)
+            .any(|entry| target.matches(&input.layer, cell_pos, entry, others));
+        matched != negated
+    })
+}
+
+///
+/// Tile layer of a `.tmx` file, cells are gids in rows from the top.
+///
+struct TmxLayer {
+    name: String,
+    cells: Vec<u32>,
+}
+
+struct TmxMap {
+    width: i32,
+    height: i32,
+    /// First gid and name of every tileset.
+    tilesets: Vec<(u32, String)>,
+    properties: HashMap<String, String>,
+    layers: Vec<TmxLayer>,
+}
+
+impl TmxMap {
+    fn tile(&self, gid: u32) -> Option<TileRef> {
+        let gid = gid & !GID_FLAGS;
+        if gid == 0 {
+            return None;
+        }
+        let (first_gid, name) = self.tilesets.iter().filter(|(first_gid, _)| *first_gid <= gid).max_by_key(|(first_gid, _)| *first_gid)?;
+        Some(TileRef { tileset: name.clone(), id: gid - first_gid })
+    }
+
+    fn property(&self, name: &str) -> bool {
+        self.properties.get(name).is_some_and(|value| value == "true")
+    }
+}
+
+///
+/// Value of an attribute of an XML tag of a Tiled file.
+///
+pub(super) fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
+
+///
+/// Reads the parts of a `.tmx` file rule maps use: tilesets, map properties and tile layers
+/// in CSV encoding.
+///
+fn parse_tmx(content: &str) -> Result<TmxMap, String> {
+    let map_tag = content.find("<map ").map(|start| &content[start..]).ok_or("no <map> element")?;
+    let map_tag = &map_tag[..map_tag.find('>').ok_or("unterminated <map> element")?];
+    let parse_size = |name: &str| -> Result<i32, String> {
+        attribute(map_tag, name).and_then(|value| value.parse().ok()).ok_or(format!("missing map {}", name))
+    };
+    let mut map = TmxMap {
+        width: parse_size("width")?,
+        height: parse_size("height")?,
+        tilesets: Vec::new(),
+        properties: HashMap::new(),
+        layers: Vec::new(),
+    };
+
+    let mut rest = content;
+    // Properties found before the first layer belong to the map.
+    let mut in_layer = false;
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>').ok_or("unterminated element")? + start;
+        let tag = &rest[start..=end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with("<tileset ") {
+            let first_gid = attribute(tag, "firstgid").and_then(|value| value.parse().ok()).ok_or("tileset without firstgid")?;
+            let source = attribute(tag, "source").or(attribute(tag, "name")).ok_or("tileset without source")?;
+            let name = Path::new(source).file_stem().and_then(|stem| stem.to_str()).unwrap_or(source);
+            map.tilesets.push((first_gid, name.to_string()));
+        } else if tag.starts_with("<layer ") {
+            in_layer = true;
+            map.layers.push(TmxLayer {
+                name: attribute(tag, "name").unwrap_or_default().to_string(),
+                cells: Vec::new(),
+            });
+        } else if tag.starts_with("<property ") && !in_layer {
+            if let (Some(name), Some(value)) = (attribute(tag, "name"), attribute(tag, "value")) {
+                map.properties.insert(name.to_string(), value.to_string());
+            }
+        } else if tag.starts_with("<data ") {
+            if attribute(tag, "encoding") != Some("csv") {
+                return Err("only CSV layer data is supported".to_string());
+            }
+            let data_end = rest.find("</data>").ok_or("unterminated <data> element")?;
+            let cells = rest[..data_end]
+                .split(',')
+                .map(|value| value.trim().parse::<u32>().map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if cells.len() != (map.width * map.height) as usize {
+                return Err("layer size does not match the map".to_string());
+            }
+            if let Some(layer) = map.layers.last_mut() {
+                layer.cells = cells;
+            }
+            rest = &rest[data_end..];
+        }
+    }
+    Ok(map)
+}
+
+///
+/// Layer names of a rule map: `input[index]_<layer>`, `output[index]_<layer>` and the
+/// optional `regions`, `regions_input` and `regions_output`.
+///
+enum RuleLayer<'a> {
+    Input(&'a str),
+    Output { index: &'a str, layer: &'a str },
+    Regions { input: bool, output: bool },
+}
+
+fn rule_layer(name: &str) -> Option<RuleLayer<'_>> {
+    match name {
+        "regions" => return Some(RuleLayer::Regions { input: true, output: true }),
+        "regions_input" => return Some(RuleLayer::Regions { input: true, output: false }),
+        "regions_output" => return Some(RuleLayer::Regions { input: false, output: true }),
+        _ => {}
+    }
+    let (prefix, layer) = name.split_once('_')?;
+    if let Some(_index) = prefix.strip_prefix("input") {
+        // Input layers with different indices are alternatives, which is the same as listing
+        // every accepted tile on each cell.
+        return Some(RuleLayer::Input(layer));
+    }
+    prefix.strip_prefix("output").map(|index| RuleLayer::Output { index, layer })
+}
+
+fn parse_rule_map(content: &str) -> Result<(RuleOptions, Vec<Rule>), String> {
+    let map = parse_tmx(content)?;
+    let options = RuleOptions {
+        match_outside_map: map.property("MatchOutsideMap"),
+        strict_empty: map.property("StrictEmpty"),
+        no_overlapping_output: map.property("NoOverlappingOutput"),
+    };
+
+    let width = map.width;
+    let index = |pos: IVec2| (pos.y * width + pos.x) as usize;
+    let positions: Vec<IVec2> = (0..map.height).flat_map(|y| (0..width).map(move |x| IVec2::new(x, y))).collect();
+
+    // Cells belonging to a rule. Without a regions layer, they are the cells used by any input
+    // or output layer.
+    let mut input_region: HashSet<IVec2> = HashSet::new();
+    let mut output_region: HashSet<IVec2> = HashSet::new();
+    let mut has_regions = (false, false);
+    for layer in &map.layers {
+        if let Some(RuleLayer::Regions { input, output }) = rule_layer(&layer.name) {
+            has_regions = (has_regions.0 || input, has_regions.1 || output);
+            for pos in positions.iter().filter(|pos| layer.cells[index(**pos)] != 0) {
+                if input {
+                    input_region.insert(*pos);
+                }
+                if output {
+                    output_region.insert(*pos);
+                }
+            }
+        }
+    }
+    for layer in &map.layers {
+        let is_input = matches!(rule_layer(&layer.name), Some(RuleLayer::Input(_)));
+        let is_output = matches!(rule_layer(&layer.name), Some(RuleLayer::Output { .. }));
+        if !is_input && !is_output {
+            continue;
+        }
+        for pos in positions.iter().filter(|pos| layer.cells[index(**pos)] != 0) {
+            if !has_regions.0 {
+                input_region.insert(*pos);
+            }
+            if !has_regions.1 {
+                output_region.insert(*pos);
+            }
+        }
+    }
+
+    // Each connected group of region cells is one rule, ordered by their first cell.
+    let region: HashSet<IVec2> = input_region.union(&output_region).copied().collect();
+    let mut seen: HashSet<IVec2> = HashSet::new();
+    let mut rules = Vec::new();
+    for start in positions.iter().filter(|pos| region.contains(pos)) {
+        if !seen.insert(*start) {
+            continue;
+        }
+        let mut cells = vec![*start];
+        let mut pending = vec![*start];
+        while let Some(current) = pending.pop() {
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = current + offset;
+                if region.contains(&next) && seen.insert(next) {
+                    cells.push(next);
+                    pending.push(next);
+                }
+            }
+        }
+        if let Some(rule) = build_rule(&map, &cells, &input_region, &output_region) {
+            rules.push(rule);
+        }
+    }
+    Ok((options, rules))
+}
+
+fn build_rule(map: &TmxMap, cells: &[IVec2], input_region: &HashSet<IVec2>, output_region: &HashSet<IVec2>) -> Option<Rule> {
+    let anchor = *cells.iter().min_by_key(|pos| (pos.y, pos.x))?;
+    // Rule maps have rows going down, the layers they apply to have `y` going up.
+    let offset = |pos: IVec2| IVec2::new(pos.x - anchor.x, anchor.y - pos.y);
+    let index = |pos: IVec2| (pos.y * map.width + pos.x) as usize;
+
+    let mut inputs: BTreeMap<(String, i32, i32), Vec<CellMatch>> = BTreeMap::new();
+    let mut used_tiles: HashMap<String, HashSet<TileRef>> = HashMap::new();
+    let mut outputs: BTreeMap<String, Vec<OutputCell>> = BTreeMap::new();
+    for layer in &map.layers {
+        match rule_layer(&layer.name) {
+            Some(RuleLayer::Input(target)) => {
+                for pos in cells.iter().filter(|pos| input_region.contains(pos)) {
+                    let key = (target.to_string(), offset(*pos).x, offset(*pos).y);
+                    let accepted = inputs.entry(key).or_default();
+                    if let Some(tile) = map.tile(layer.cells[index(*pos)]) {
+                        if tile.tileset != AUTOMAP_TILESET {
+                            used_tiles.entry(target.to_string()).or_default().insert(tile.clone());
+                        }
+                        accepted.push(CellMatch::from_tile(tile));
+                    }
+                }
+            }
+            Some(RuleLayer::Output { index: output_index, layer: target }) => {
+                let output = outputs.entry(output_index.to_string()).or_default();
+                for pos in cells.iter().filter(|pos| output_region.contains(pos)) {
+                    let Some(tile) = map.tile(layer.cells[index(*pos)]) else { continue; };
+                    let tile = match CellMatch::from_tile(tile) {
+                        CellMatch::Tile(tile) => Some(tile),
+                        CellMatch::Empty => None,
+                        _ => continue,
+                    };
+                    output.push(OutputCell { layer: target.to_string(), offset: offset(*pos), tile });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Cells without any input tile only matter with StrictEmpty, a rule needs at least one
+    // cell that really tests something.
+    if inputs.values().all(Vec::is_empty) {
+        return None;
+    }
+    Some(Rule {
+        inputs: inputs
+            .into_iter()
+            .map(|((layer, x, y), accepted)| InputCell { layer, offset: IVec2::new(x, y), accepted })
+            .collect(),
+        used_tiles,
+        outputs: outputs.into_values().filter(|output| !output.is_empty()).collect(),
+    })
+}
+
+pub struct AutomapPlugin;
+
+impl Plugin for AutomapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AutomapRules::load_or_default(AUTOMAP_RULES_PATH));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Gids of the test rule maps: the "ground" tileset first, then the automap tiles.
+    const AUTOMAP_FIRST_GID: u32 = 101;
+
+    fn rule_map(width: i32, height: i32, layers: &[(&str, &[u32])]) -> String {
+        let mut content = format!(
+            "<map version=\"1.10\" orientation=\"orthogonal\" width=\"{width}\" height=\"{height}\" tilewidth=\"16\" tileheight=\"16\">\n \
+             <tileset firstgid=\"1\" source=\"ground.tsx\"/>\n \
+             <tileset firstgid=\"{AUTOMAP_FIRST_GID}\" source=\":/automap-tiles.tsx\"/>\n"
+        );
+        for (name, cells) in layers {
+            let cells: Vec<String> = cells.iter().map(u32::to_string).collect();
+            content += &format!(
+                " <layer name=\"{name}\" width=\"{width}\" height=\"{height}\">\n  <data encoding=\"csv\">\n{}\n</data>\n </layer>\n",
+                cells.join(",")
+            );
+        }
+        content + "</map>\n"
+    }
+
+    fn rules(content: &str) -> AutomapRules {
+        let (options, rules) = parse_rule_map(content).unwrap();
+        AutomapRules { maps: vec![RuleMap { path: "test".to_string(), options, rules }] }
+    }
+
+    fn ground(id: u32) -> TileRef {
+        TileRef { tileset: "ground".to_string(), id }
+    }
+
+    #[test]
+    fn attribute_reads_whole_attribute_names() {
+        let tag = r#"<map tilewidth="16" width="3" name="">"#;
+        assert_eq!(attribute(tag, "width"), Some("3"));
+        assert_eq!(attribute(tag, "tilewidth"), Some("16"));
+        assert_eq!(attribute(tag, "name"), Some(""));
+        assert_eq!(attribute(tag, "height"), None);
+    }
+
+    #[test]
+    fn automap_tiles_become_cell_matches() {
+        let automap = |id| CellMatch::from_tile(TileRef { tileset: AUTOMAP_TILESET.to_string(), id });
+        assert_eq!(automap(0), CellMatch::Empty);
+        assert_eq!(automap(1), CellMatch::Ignore);
+        assert_eq!(automap(2), CellMatch::NonEmpty);
+        assert_eq!(automap(3), CellMatch::Other);
+        assert_eq!(automap(4), CellMatch::Negate);
+        assert_eq!(automap(9), CellMatch::Ignore);
+        assert_eq!(CellMatch::from_tile(ground(3)), CellMatch::Tile(ground(3)));
+    }
+
+    #[test]
+    fn cell_matches_read_the_layers() {
+        let mut layers = TileLayers::new(2, 1, "ground");
+        let (tile, empty) = (IVec2::ZERO, IVec2::X);
+        layers.set("ground", tile, Some(1));
+        let used = HashSet::from([ground(2)]);
+        let matches = |pos, entry: CellMatch| layers.matches("ground", pos, &entry, &used);
+
+        assert!(matches(empty, CellMatch::Empty));
+        assert!(!matches(tile, CellMatch::Empty));
+        assert!(matches(empty, CellMatch::Ignore) && matches(tile, CellMatch::Ignore));
+        assert!(matches(tile, CellMatch::NonEmpty));
+        assert!(!matches(empty, CellMatch::NonEmpty));
+        assert!(matches(tile, CellMatch::Other));
+        assert!(!matches(empty, CellMatch::Other));
+        assert!(!layers.matches("ground", tile, &CellMatch::Other, &HashSet::from([ground(1)])));
+        assert!(matches(empty, CellMatch::Negate) && matches(tile, CellMatch::Negate));
+        assert!(matches(tile, CellMatch::Tile(ground(1))));
+        assert!(!matches(tile, CellMatch::Tile(ground(2))));
+        assert!(!matches(tile, CellMatch::Tile(TileRef { tileset: "other".to_string(), id: 1 })));
+    }
+
+    #[test]
+    fn negated_cells_match_anything_but_their_tiles() {
+        // Tile 1 that has no tile 2 on its right turns into tile 3.
+        let negate = AUTOMAP_FIRST_GID + 4;
+        let content = rule_map(2, 2, &[
+            ("input_ground", &[2, 3, 0, 0]),
+            ("input2_ground", &[0, negate, 0, 0]),
+            ("output_ground", &[4, 0, 0, 0]),
+        ]);
+        let mut layers = TileLayers::new(3, 1, "ground");
+        for (x, id) in [1, 2, 1].into_iter().enumerate() {
+            layers.set("ground", IVec2::new(x as i32, 0), Some(id));
+        }
+        rules(&content).apply(&mut layers, 0);
+        assert_eq!(layers.get("ground", IVec2::new(0, 0)), Some(1));
+        assert_eq!(layers.get("ground", IVec2::new(2, 0)), Some(1));
+
+        layers.set("ground", IVec2::new(1, 0), Some(1));
+        rules(&content).apply(&mut layers, 0);
+        assert_eq!(layers.get("ground", IVec2::new(0, 0)), Some(3));
+    }
+
+    #[test]
+    fn rules_apply_with_rows_going_up() {
+        // Tile 1 with an empty cell painted below it in the rule map becomes tile 5, the cell
+        // above it becomes tile 6.
+        let empty = AUTOMAP_FIRST_GID;
+        let content = rule_map(1, 3, &[
+            ("input_ground", &[0, 2, empty]),
+            ("output_ground", &[7, 6, 0]),
+        ]);
+        let rules = rules(&content);
+        assert_eq!(rules.maps[0].rules.len(), 1);
+
+        let mut layers = TileLayers::new(2, 3, "ground");
+        for y in 0..3 {
+            layers.set("ground", IVec2::new(0, y), Some(1));
+        }
+        layers.set("ground", IVec2::new(1, 1), Some(1));
+        rules.apply(&mut layers, 0);
+
+        let column = |x| (0..3).map(|y| layers.get("ground", IVec2::new(x, y))).collect::<Vec<_>>();
+        // The tiles of the first column have no empty cell under them inside the map.
+        assert_eq!(column(0), vec![Some(1), Some(1), Some(1)]);
+        assert_eq!(column(1), vec![None, Some(5), Some(6)]);
+    }
+
+    #[test]
+    fn cave_rules_dress_a_room() {
+        let rules = AutomapRules::load_or_default(AUTOMAP_RULES_PATH);
+        // A 3 x 2 room of tile 100 painted the way the rule map expects: wall faces above the
+        // room, edges on its sides and below it, and rock around.
+        let rows: [[u32; 7]; 7] = [
+            [33, 33, 33, 33, 33, 33, 33],
+            [33, 33, 33, 33, 33, 33, 33],
+            [33, 67, 68, 68, 68, 69, 33],
+            [33, 99, 100, 100, 100, 101, 33],
+            [33, 99, 100, 100, 100, 101, 33],
+            [33, 33, 132, 132, 132, 33, 33],
+            [33, 33, 33, 33, 33, 33, 33],
+        ];
+        let mut layers = TileLayers::new(7, 7, "FDR_Caves");
+        for (row, tiles) in rows.iter().enumerate() {
+            for (x, tile) in tiles.iter().enumerate() {
+                layers.set("walls", IVec2::new(x as i32, 6 - row as i32), Some(*tile));
+            }
+        }
+        rules.apply(&mut layers, 0);
+
+        let row = |y| (0..7).map(|x| layers.get("walls", IVec2::new(x, y)).unwrap_or(0)).collect::<Vec<_>>();
+        assert_eq!(row(6), vec![33, 3, 4, 4, 4, 5, 33]);
+        assert_eq!(row(5), vec![33, 35, 36, 36, 36, 37, 33]);
+        assert_eq!(row(4), vec![33, 67, 68, 68, 68, 69, 33]);
+        assert_eq!(row(3), vec![33, 99, 100, 100, 100, 101, 33]);
+        assert_eq!(row(2), vec![33, 99, 100, 100, 100, 101, 33]);
+        assert_eq!(row(1), vec![33, 131, 132, 132, 132, 133, 33]);
+        assert_eq!(row(0), vec![33; 7]);
+    }
+}
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::constants::{CHUNK_SIZE, RENDER_CHUNK_SIZE, TILE_SIZE_PX};
+use crate::events::{ChunkLoaded, GroundChanged};
+use crate::map::automap::attribute;
+use crate::map::tile_grid::{TileOverlay, TileProperties};
+use crate::map::{tile_hash, tile_to_chunk_coords};
+use crate::tile_type::GroundTiles;
+
+// Between the ground and the ground items.
+const TRANSITION_Z: f32 = 0.5;
+// Neighbours in the order of the slots of a Tiled wang id: top, top right, right, bottom
+// right, bottom, bottom left, left, top left. Odd slots are corners, even slots are edges.
+const WANG_NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(0, 1),
+    IVec2::new(1, 1),
+    IVec2::new(1, 0),
+    IVec2::new(1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(-1, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, 1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WangType {
+    Corner,
+    Edge,
+    /// Corners and edges, the 47 tile blob sets.
+    Mixed,
+}
+
+impl WangType {
+    fn uses_slot(&self, slot: usize) -> bool {
+        match self {
+            WangType::Corner => slot % 2 == 1,
+            WangType::Edge => slot.is_multiple_of(2),
+            WangType::Mixed => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TerrainTile {
+    id: u32,
+    /// Terrain of each wang slot, `None` where the tile is transparent.
+    wang: [Option<u8>; 8],
+    probability: f32,
+}
+
+///
+/// Terrains of a Tiled tileset and the tiles drawing the transitions between them. Both the
+/// wang sets of current Tiled versions and the legacy terrain types (`terrain-v7.tsx`) are read.
+///
+#[derive(Debug, Clone)]
+pub struct TerrainSet {
+    pub kind: WangType,
+    pub tile_size: Vec2,
+    terrains: Vec<String>,
+    tiles: Vec<TerrainTile>,
+}
+
+impl TerrainSet {
+    ///
+    /// Reads a `.tsx` file. `wangset` picks a wang set by name, without it the legacy terrain
+    /// types are used, or the first wang set when there are none.
+    ///
+    pub fn load(path: &str, wangset: Option<&str>) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let tileset_tag = content.find("<tileset ").map(|start| &content[start..]).ok_or("no <tileset> element")?;
+        let parse_size = |name: &str| -> Result<f32, String> {
+            attribute(tileset_tag, name).and_then(|value| value.parse().ok()).ok_or(format!("missing tileset {}", name))
+        };
+        let mut set = TerrainSet {
+            kind: WangType::Corner,
+            tile_size: Vec2::new(parse_size("tilewidth")?, parse_size("tileheight")?),
+            terrains: Vec::new(),
+            tiles: Vec::new(),
+        };
+
+        let legacy = wangset.is_none() && content.contains("<terraintypes>");
+        let mut in_set = false;
+        let mut rest = content.as_str();
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..].find('>').ok_or("unterminated element")? + start;
+            let tag = &rest[start..=end];
+            rest = &rest[end + 1..];
+
+            if legacy {
+                if tag.starts_with("<terrain ") {
+                    set.terrains.push(attribute(tag, "name").unwrap_or_default().to_string());
+                } else if tag.starts_with("<tile ") && let Some(corners) = attribute(tag, "terrain") {
+                    set.tiles.push(TerrainTile {
+                        id: attribute(tag, "id").and_then(|value| value.parse().ok()).ok_or("tile without id")?,
+                        wang: legacy_corners(corners),
+                        probability: attribute(tag, "probability").and_then(|value| value.parse().ok()).unwrap_or(1.0),
+                    });
+                }
+                continue;
+            }
+
+            if tag.starts_with("<wangset ") {
+                if wangset.is_some_and(|name| attribute(tag, "name") != Some(name)) {
+                    continue;
+                }
+                in_set = true;
+                set.kind = match attribute(tag, "type") {
+                    Some("edge") => WangType::Edge,
+                    Some("mixed") => WangType::Mixed,
+                    _ => WangType::Corner,
+                };
+            } else if tag.starts_with("</wangset") && in_set {
+                break;
+            } else if in_set && tag.starts_with("<wangcolor ") {
+                set.terrains.push(attribute(tag, "name").unwrap_or_default().to_string());
+            } else if in_set && tag.starts_with("<wangtile ") {
+                let wang_id = attribute(tag, "wangid").ok_or("wang tile without wangid")?;
+                let mut wang = [None; 8];
+                for (slot, color) in wang_id.split(',').take(8).enumerate() {
+                    // Wang colors start at 1, 0 is no color.
+                    wang[slot] = color.trim().parse::<u8>().ok().filter(|color| *color > 0).map(|color| color - 1);
+                }
+                set.tiles.push(TerrainTile {
+                    id: attribute(tag, "tileid").and_then(|value| value.parse().ok()).ok_or("wang tile without tileid")?,
+                    wang,
+                    probability: 1.0,
+                });
+            }
+        }
+
+        if set.tiles.is_empty() {
+            return Err("no terrain tiles".to_string());
+        }
+        Ok(set)
+    }
+
+    fn terrain(&self, name: &str) -> Option<u8> {
+        self.terrains.iter().position(|terrain| terrain == name).map(|index| index as u8)
+    }
+
+    ///
+    /// Tile drawing exactly the given slots. When several tiles fit, `roll` picks one
+    /// according to their probability.
+    ///
+    fn pick(&self, wang: [Option<u8>; 8], roll: u32) -> Option<u32> {
+        let candidates: Vec<&TerrainTile> = self
+            .tiles
+            .iter()
+            .filter(|tile| (0..8).filter(|slot| self.kind.uses_slot(*slot)).all(|slot| tile.wang[slot] == wang[slot]))
+            .collect();
+        let total: f32 = candidates.iter().map(|tile| tile.probability).sum();
+        if total <= 0.0 {
+            return candidates.first().map(|tile| tile.id);
+        }
+        let mut remaining = (roll % 10_000) as f32 / 10_000.0 * total;
+        for tile in &candidates {
+            remaining -= tile.probability;
+            if remaining < 0.0 {
+                return Some(tile.id);
+            }
+        }
+        candidates.last().map(|tile| tile.id)
+    }
+}
+
+///
+/// Legacy terrain corners are listed top left, top right, bottom left, bottom right.
+///
+fn legacy_corners(corners: &str) -> [Option<u8>; 8] {
+    let mut wang = [None; 8];
+    for (corner, slot) in corners.split(',').zip([7, 1, 5, 3]) {
+        wang[slot] = corner.trim().parse().ok();
+    }
+    wang
+}
+
+///
+/// Overworld grounds drawn with one terrain of the transition tileset.
+///
+#[derive(Debug, Clone)]
+pub struct TerrainLayer {
+    pub terrain: String,
+    pub grounds: Vec<GroundTiles>,
+}
+
+///
+/// Transition tileset and the terrain of every overworld ground. `layers` go from the bottom
+/// to the top: a terrain is drawn over the tiles of the terrains below it that it touches.
+///
+#[derive(Resource, Debug, Clone)]
+pub struct AutotileConfig {
+    /// `.tsx` file, read from disk.
+    pub tileset: String,
+    /// Image of the tileset, loaded through the asset server.
+    pub texture: String,
+    pub wangset: Option<String>,
+    pub layers: Vec<TerrainLayer>,
+}
+
+impl Default for AutotileConfig {
+    fn default() -> Self {
+        AutotileConfig {
+            tileset: "assets/maps/terrain-v7.tsx".to_string(),
+            texture: "maps/terrain-v7.png".to_string(),
+            wangset: None,
+            layers: default_layers(),
+        }
+    }
+}
+
+fn default_layers() -> Vec<TerrainLayer> {
+    // GroundTiles has a `None` variant, so its names are only imported here.
+    use GroundTiles::*;
+
+    let layer = |terrain: &str, grounds: &[GroundTiles]| TerrainLayer {
+        terrain: terrain.to_string(),
+        grounds: grounds.to_vec(),
+    };
+    vec![
+        layer("Water_Deep", &[DarkShallowWater]),
+        layer("Water", &[MediumShallowWater, LightShallowWater]),
+        layer("Sand", &[LightDirt, LightSandyMountain]),
+        layer("Grass_Light", &[LightGrass]),
+        layer("Grass", &[MediumGrass]),
+        layer("Grass_Dark", &[DarkGrass]),
+        layer("Rock_Gray", &[LightRockSnowyMountain]),
+        layer("Snow_1", &[DarkSnowyMountain]),
+    ]
+}
+
+///
+/// Loaded transition tileset with, for each ground, its layer and the terrain drawing it.
+///
+#[derive(Resource, Debug, Default)]
+pub struct Autotiler {
+    set: Option<TerrainSet>,
+    texture: Handle<Image>,
+    grounds: HashMap<GroundTiles, (usize, u8)>,
+}
+
+impl Autotiler {
+    fn new(config: &AutotileConfig, asset_server: &AssetServer) -> Self {
+        let set = match TerrainSet::load(&config.tileset, config.wangset.as_deref()) {
+            Ok(set) => set,
+            Err(e) => {
+                warn!("Invalid terrain tileset {}: {}. Terrain transitions are disabled.", config.tileset, e);
+                return Autotiler::default();
+            }
+        };
+        let mut grounds = HashMap::new();
+        for (index, layer) in config.layers.iter().enumerate() {
+            let Some(terrain) = set.terrain(&layer.terrain) else {
+                warn!("Terrain {} is not in {}.", layer.terrain, config.tileset);
+                continue;
+            };
+            for ground in &layer.grounds {
+                grounds.insert(*ground, (index, terrain));
+            }
+        }
+        Autotiler {
+            set: Some(set),
+            texture: asset_server.load(&config.texture),
+            grounds,
+        }
+    }
+
+    ///
+    /// Transition tile of a tile from the layers of the tile and its 8 neighbours, `None` when
+    /// no neighbour has a terrain above the tile's own. Only the highest terrain touching the
+    /// tile is drawn.
+    ///
+    fn transition(&self, tile: IVec2, ground_at: impl Fn(IVec2) -> Option<GroundTiles>) -> Option<u32> {
+        let set = self.set.as_ref()?;
+        let layer_at = |pos: IVec2| ground_at(pos).and_then(|ground| self.grounds.get(&ground).copied());
+        let (own_layer, _) = layer_at(tile)?;
+        let neighbours = WANG_NEIGHBOURS.map(|offset| layer_at(tile + offset));
+
+        // A corner is shared with the two edge neighbours and the diagonal one, an edge with the
+        // neighbour on the other side.
+        let mut slots: [Option<(usize, u8)>; 8] = [None; 8];
+        for (slot, value) in slots.iter_mut().enumerate() {
+            let touching: &[usize] = if slot % 2 == 1 { &[(slot + 7) % 8, slot, (slot + 1) % 8] } else { &[slot] };
+            *value = touching.iter().filter_map(|index| neighbours[*index]).max_by_key(|(layer, _)| *layer);
+        }
+        let (top_layer, top_terrain) = slots
+            .iter()
+            .enumerate()
+            .filter(|(slot, _)| set.kind.uses_slot(*slot))
+            .filter_map(|(_, value)| *value)
+            .max_by_key(|(layer, _)| *layer)?;
+        if top_layer <= own_layer {
+            return None;
+        }
+
+        let wang = slots.map(|value| value.filter(|(layer, _)| *layer == top_layer).map(|_| top_terrain));
+        set.pick(wang, tile_hash(tile))
+    }
+}
+
+///
+/// Transition tilemap drawn over the ground of a chunk.
+///
+#[derive(Component, Debug)]
+struct TransitionLayer;
+
+pub struct AutotilePlugin;
+
+impl Plugin for AutotilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AutotileConfig>()
+            .init_resource::<Autotiler>()
+            .add_systems(Startup, load_autotiler)
+            .add_systems(Update, autotile_chunks);
+    }
+}
+
+fn load_autotiler(
+    config: Res<AutotileConfig>,
+    asset_server: Res<AssetServer>,
+    mut autotiler: ResMut<Autotiler>,
+) {
+    *autotiler = Autotiler::new(&config, &asset_server);
+}
+
+///
+/// Draws the transitions of the chunks that were just loaded and redraws the border tiles of
+/// their spawned neighbours, which could not see the new chunk until now. Chunks whose ground
+/// changed are redrawn with their border.
+///
+fn autotile_chunks(
+    mut commands: Commands,
+    autotiler: Res<Autotiler>,
+    mut loaded: MessageReader<ChunkLoaded>,
+    mut ground_changed: MessageReader<GroundChanged>,
+    mut pending: Local<Vec<IVec2>>,
+    grounds: Query<(&Transform, &TileStorage), (Without<TileOverlay>, Without<TileProperties>)>,
+    mut overlays: Query<(Entity, &Transform, &mut TileStorage), With<TransitionLayer>>,
+    mut textures: Query<&mut TileTextureIndex>,
+) {
+    pending.extend(loaded.read().map(|event| event.chunk));
+    let changed: Vec<IVec2> = ground_changed.read().map(|event| event.chunk).collect();
+    let Some(set) = autotiler.set.as_ref() else {
+        pending.clear();
+        return;
+    };
+    if pending.is_empty() && changed.is_empty() {
+        return;
+    }
+
+    let chunk_size = IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32);
+    let chunk_of = |transform: &Transform| (transform.translation.truncate() / (chunk_size.as_vec2() * TILE_SIZE_PX)).round().as_ivec2();
+    let ground_chunks: HashMap<IVec2, &TileStorage> = grounds.iter().map(|(transform, storage)| (chunk_of(transform), storage)).collect();
+    let ground_at = |tile: IVec2| {
+        let chunk = tile_to_chunk_coords(tile);
+        let local = tile - chunk * chunk_size;
+        let tile_entity = ground_chunks.get(&chunk)?.get(&TilePos { x: local.x as u32, y: local.y as u32 })?;
+        textures.get(tile_entity).ok().map(|texture| GroundTiles::from(texture.0))
+    };
+
+    // Tiles of chunks spawned this frame are only there once the commands are applied.
+    let ready: Vec<IVec2> = pending.iter().copied().filter(|chunk| ground_chunks.contains_key(chunk)).collect();
+    pending.retain(|chunk| !ground_chunks.contains_key(chunk));
+
+    // Ring of tiles around a chunk, in the neighbouring chunks.
+    let ring = |origin: IVec2| {
+        (-1..=chunk_size.y)
+            .flat_map(move |y| (-1..=chunk_size.x).map(move |x| IVec2::new(x, y)))
+            .filter(move |local| !(0..chunk_size.x).contains(&local.x) || !(0..chunk_size.y).contains(&local.y))
+            .map(move |local| origin + local)
+    };
+    let mut new_layers = Vec::new();
+    let mut redrawn: HashSet<IVec2> = HashSet::new();
+    for chunk in ready {
+        let origin = chunk * chunk_size;
+        let mut tiles = Vec::new();
+        for y in 0..chunk_size.y {
+            for x in 0..chunk_size.x {
+                let local = IVec2::new(x, y);
+                if let Some(index) = autotiler.transition(origin + local, ground_at) {
+                    tiles.push((local, index));
+                }
+            }
+        }
+        new_layers.push((chunk, tiles));
+        redrawn.extend(ring(origin));
+    }
+    for chunk in changed.into_iter().filter(|chunk| ground_chunks.contains_key(chunk)) {
+        let origin = chunk * chunk_size;
+        redrawn.extend((0..chunk_size.y).flat_map(|y| (0..chunk_size.x).map(move |x| origin + IVec2::new(x, y))));
+        redrawn.extend(ring(origin));
+    }
+    let changes: Vec<(IVec2, Option<u32>)> = redrawn.into_iter().map(|tile| (tile, autotiler.transition(tile, ground_at))).collect();
+
+    let tile_size = TilemapTileSize { x: set.tile_size.x, y: set.tile_size.y };
+    let scale = TILE_SIZE_PX / set.tile_size.x;
+    for (chunk, tiles) in new_layers {
+        let tilemap_entity = commands.spawn_empty().id();
+        let mut tile_storage = TileStorage::empty(CHUNK_SIZE.into());
+        for (local, index) in tiles {
+            let tile_pos = TilePos { x: local.x as u32, y: local.y as u32 };
+            let tile_entity = commands
+                .spawn(TileBundle {
+                    position: tile_pos,
+                    tilemap_id: TilemapId(tilemap_entity),
+                    texture_index: TileTextureIndex(index),
+                    ..Default::default()
+                })
+                .id();
+            commands.entity(tilemap_entity).add_child(tile_entity);
+            tile_storage.set(&tile_pos, tile_entity);
+        }
+        let origin = (chunk * chunk_size).as_vec2() * TILE_SIZE_PX;
+        commands.entity(tilemap_entity).insert((
+            TilemapBundle {
+                grid_size: tile_size.into(),
+                size: CHUNK_SIZE.into(),
+                storage: tile_storage,
+                texture: TilemapTexture::Single(autotiler.texture.clone()),
+                tile_size,
+                transform: Transform::from_translation(origin.extend(TRANSITION_Z)).with_scale(Vec3::new(scale, scale, 1.0)),
+                render_settings: TilemapRenderSettings {
+                    render_chunk_size: RENDER_CHUNK_SIZE,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            TransitionLayer,
+            TileOverlay,
+        ));
+    }
+
+    let mut overlay_chunks: HashMap<IVec2, (Entity, Mut<TileStorage>)> = overlays
+        .iter_mut()
+        .map(|(entity, transform, storage)| (chunk_of(transform), (entity, storage)))
+        .collect();
+    for (tile, index) in changes {
+        let chunk = tile_to_chunk_coords(tile);
+        let Some((tilemap_entity, storage)) = overlay_chunks.get_mut(&chunk) else { continue; };
+        let local = tile - chunk * chunk_size;
+        let tile_pos = TilePos { x: local.x as u32, y: local.y as u32 };
+        match (storage.get(&tile_pos), index) {
+            (Some(tile_entity), Some(index)) => {
+                if let Ok(mut texture) = textures.get_mut(tile_entity) {
+                    texture.0 = index;
+                }
+            }
+            (Some(tile_entity), None) => {
+                commands.entity(tile_entity).despawn();
+                storage.remove(&tile_pos);
+            }
+            (None, Some(index)) => {
+                let tile_entity = commands
+                    .spawn(TileBundle {
+                        position: tile_pos,
+                        tilemap_id: TilemapId(*tilemap_entity),
+                        texture_index: TileTextureIndex(index),
+                        ..Default::default()
+                    })
+                    .id();
+                commands.entity(*tilemap_entity).add_child(tile_entity);
+                storage.set(&tile_pos, tile_entity);
+            }
+            (None, None) => {}
+        }
+    }
+}
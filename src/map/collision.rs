@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy_ecs_tiled::prelude::*;
+
+use crate::constants::TILED_TILESETS_DIR;
+use crate::events::{MoveEvent, MoveLegal};
+use crate::map::automap::attribute;
+use crate::map::tile_grid::{TileGrid, TileOverlay, TileProperties};
+use crate::map::world_to_tile_coords;
+
+///
+/// Collision of the Tiled tilesets, by the asset path of their image. Read from the tile
+/// properties of the `.tsx` files:
+/// - `walkable` (bool): false blocks the tile, as does `collides` set to true or any collision
+///   shape drawn on the tile.
+/// - `opaque` (bool): the tile stops sight and projectiles.
+/// - `move_cost` (int): cost of walking onto the tile, 1 when missing.
+///
+#[derive(Resource, Debug, Default)]
+pub struct TilesetCollisions {
+    tilesets: HashMap<String, TileProperties>,
+}
+
+impl TilesetCollisions {
+    ///
+    /// Reads every `.tsx` file of a directory. Tilesets that cannot be read are reported and
+    /// their tiles are all walkable.
+    ///
+    pub fn load_or_default(directory: &str) -> Self {
+        let mut collisions = TilesetCollisions::default();
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Cannot read the tilesets in {}: {}.", directory, e);
+                return collisions;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.extension().is_none_or(|extension| extension != "tsx") {
+                continue;
+            }
+            match read_tileset(&path) {
+                Ok((image, properties)) => {
+                    collisions.tilesets.insert(image, properties);
+                }
+                Err(e) => warn!("Invalid tileset {}: {}.", path.display(), e),
+            }
+        }
+        info!("Loaded the collisions of {} tilesets from {}.", collisions.tilesets.len(), directory);
+        collisions
+    }
+
+    pub fn get(&self, image: &str) -> Option<&TileProperties> {
+        self.tilesets.get(image)
+    }
+}
+
+///
+/// Asset path of the image of a tileset and the collision of its tiles.
+///
+fn read_tileset(path: &Path) -> Result<(String, TileProperties), String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let image_tag = content.find("<image ").map(|start| &content[start..]).ok_or("no <image> element")?;
+    let source = attribute(image_tag, "source").ok_or("image without source")?;
+    // Asset paths are relative to the assets directory.
+    let image = path.parent().unwrap_or(Path::new("")).join(source);
+    let image = image.strip_prefix("assets").unwrap_or(&image).to_string_lossy().replace('\\', "/");
+
+    let mut properties = TileProperties::default();
+    let mut tile = None;
+    let mut rest = content.as_str();
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>').ok_or("unterminated element")? + start;
+        let tag = &rest[start..=end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with("<tile ") {
+            let id: u32 = attribute(tag, "id").and_then(|value| value.parse().ok()).ok_or("tile without id")?;
+            tile = (!tag.ends_with("/>")).then_some(id);
+        } else if tag.starts_with("</tile>") {
+            tile = None;
+        } else if let Some(id) = tile {
+            if tag.starts_with("<objectgroup") {
+                properties.blocked.insert(id);
+            } else if tag.starts_with("<property ") {
+                let value = attribute(tag, "value").unwrap_or_default();
+                match attribute(tag, "name") {
+                    Some("walkable") if value == "false" => {
+                        properties.blocked.insert(id);
+                    }
+                    Some("collides") if value == "true" => {
+                        properties.blocked.insert(id);
+                    }
+                    Some("opaque") if value == "true" => {
+                        properties.opaque.insert(id);
+                    }
+                    Some("move_cost") => {
+                        let cost = value.parse().map_err(|_| format!("invalid move_cost of tile {}", id))?;
+                        properties.costs.insert(id, cost);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok((image, properties))
+}
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TilesetCollisions::load_or_default(TILED_TILESETS_DIR))
+            .add_systems(Update, (attach_tiled_collisions, move_event_listener).chain());
+    }
+}
+
+///
+/// Gives the tilemaps of the Tiled maps the collision of their tileset. Tilemaps outside of a
+/// Tiled map (overworld chunks, generated dungeons) are left alone.
+///
+fn attach_tiled_collisions(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    collisions: Res<TilesetCollisions>,
+    tilemaps: Query<(Entity, &TilemapTexture), (With<TileStorage>, Without<TileProperties>, Without<TileOverlay>)>,
+    parents: Query<&ChildOf>,
+    tiled_maps: Query<(), With<TiledMap>>,
+) {
+    for (entity, texture) in tilemaps.iter() {
+        if !parents.iter_ancestors(entity).any(|ancestor| tiled_maps.contains(ancestor)) {
+            continue;
+        }
+        let properties = match texture {
+            TilemapTexture::Single(image) => asset_server
+                .get_path(image.id())
+                .and_then(|path| collisions.get(&path.path().to_string_lossy().replace('\\', "/")).cloned()),
+            _ => None,
+        };
+        commands.entity(entity).insert(properties.unwrap_or_default());
+    }
+}
+
+///
+/// This method is used to check for event. The player system sends a MoveEvent and this system
+/// reads it. It then determines whether the destination tile is walkable or not. It then sends
+/// a MoveLegal event.
+/// 
+/// Diagonal moves that cross into a new tile on both axes are refused when the two tiles
+/// sharing the corner are both blocked, so the player cannot squeeze between them.
+///
+fn move_event_listener(
+    mut move_events: MessageReader<MoveEvent>,
+    tile_grid: TileGrid,
+    mut move_legal: MessageWriter<MoveLegal>,
+) {
+    for move_event in move_events.read() {
+        let Some(destination) = move_event.destination else { continue; };
+        let destination_tile = world_to_tile_coords(destination);
+        // Destinations outside of every spawned chunk are ignored.
+        let Some(mut legal_move) = tile_grid.walkable(destination_tile) else { continue; };
+
+        if let Some(origin) = move_event.origin {
+            let origin_tile = world_to_tile_coords(origin);
+            if origin_tile.x != destination_tile.x && origin_tile.y != destination_tile.y {
+                let corner_x = IVec2::new(destination_tile.x, origin_tile.y);
+                let corner_y = IVec2::new(origin_tile.x, destination_tile.y);
+                let blocked_x = tile_grid.walkable(corner_x) == Some(false);
+                let blocked_y = tile_grid.walkable(corner_y) == Some(false);
+                if blocked_x && blocked_y {
+                    legal_move = false;
+                }
+            }
+        }
+
+        move_legal.write(MoveLegal {
+            legal_move,
+            destination: if legal_move { Some(destination) } else { None },
+        });
+    }
+}
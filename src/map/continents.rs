@@ -0,0 +1,154 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use noise::{NoiseFn, Perlin};
+use rand::prelude::*;
+
+// Height of the crust of the plates, before their borders and the noise are added.
+const CONTINENT_HEIGHT: f64 = 0.6;
+const OCEAN_HEIGHT: f64 = 0.3;
+// Tiles the plate borders wander off a straight line, and the size of their bends.
+const BORDER_WARP: f64 = 12.0;
+const BORDER_WARP_SCALE: f64 = 0.03;
+
+///
+/// How the overworld elevation is laid out. The noise alone gives scattered land, the island
+/// sinks the edges of the map into the sea, and continents collide tectonic plates.
+///
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorldShape {
+    #[default]
+    Noise,
+    Island,
+    Continents,
+}
+
+///
+/// Settings of the [`WorldShape::Continents`] generator. The map is split into Voronoi plates
+/// drifting in random directions. Continental plates stand above the sea and oceanic ones
+/// below, mountain ranges rise where plates collide and rifts open where they part. Oceanic
+/// plates colliding raise chains of islands.
+///
+#[derive(Reflect, Debug, Clone)]
+pub struct ContinentConfig {
+    pub plates: u32,
+    /// Share of the plates carrying a continent, in `0..=1`.
+    pub continental_share: f64,
+    /// Height raised where two plates collide head on.
+    pub ridge_height: f64,
+    /// Tiles from the border the ridges and rifts spread over.
+    pub ridge_width: f64,
+    /// Depth sunk where two plates drift apart.
+    pub rift_depth: f64,
+    /// Tiles over which the crust goes from one plate height to the other.
+    pub shelf_width: f64,
+    /// How much of the noise elevation is kept on top of the plates.
+    pub detail: f64,
+}
+
+impl Default for ContinentConfig {
+    fn default() -> Self {
+        ContinentConfig {
+            plates: 14,
+            continental_share: 0.45,
+            ridge_height: 0.25,
+            ridge_width: 8.0,
+            rift_depth: 0.08,
+            shelf_width: 12.0,
+            detail: 0.6,
+        }
+    }
+}
+
+struct Plate {
+    center: DVec2,
+    drift: DVec2,
+    height: f64,
+}
+
+///
+/// Lowers the edges of a row-major `noise` heightmap of `width` by `height` tiles into the
+/// sea, keeping the noise on a single island in the middle of the map.
+///
+pub fn island_elevation(noise: &[f64], width: usize, height: usize) -> Vec<f64> {
+    noise
+        .iter()
+        .enumerate()
+        .map(|(index, e)| {
+            let nx = 2.0 * (index % width) as f64 / width as f64 - 1.0;
+            let ny = 2.0 * (index / width) as f64 / height as f64 - 1.0;
+            let mask = (1.0 - (nx * nx + ny * ny)).clamp(0.0, 1.0);
+            e * 0.6 + mask * 0.4
+        })
+        .collect()
+}
+
+///
+/// Elevation of continents drawn from tectonic plates, for a row-major `noise` heightmap of
+/// `width` by `height` tiles in `0..=1`. The plates only depend on `seed`.
+///
+pub fn continent_elevation(noise: &[f64], width: usize, height: usize, config: &ContinentConfig, seed: u64) -> Vec<f64> {
+    let plates = seed_plates(width, height, config, seed);
+    if plates.len() < 2 {
+        return noise.to_vec();
+    }
+    let warp = Perlin::new(seed as u32);
+
+    noise
+        .iter()
+        .enumerate()
+        .map(|(index, e)| {
+            let tile = DVec2::new((index % width) as f64, (index / width) as f64);
+            let scaled = tile * BORDER_WARP_SCALE;
+            let position = tile
+                + DVec2::new(warp.get([scaled.x, scaled.y]), warp.get([scaled.x + 31.7, scaled.y + 17.3])) * BORDER_WARP;
+
+            // The two closest plates, the border runs halfway between them.
+            let (mut first, mut second) = (0, 1);
+            if position.distance_squared(plates[1].center) < position.distance_squared(plates[0].center) {
+                (first, second) = (1, 0);
+            }
+            for (i, plate) in plates.iter().enumerate().skip(2) {
+                let distance = position.distance_squared(plate.center);
+                if distance < position.distance_squared(plates[first].center) {
+                    (first, second) = (i, first);
+                } else if distance < position.distance_squared(plates[second].center) {
+                    second = i;
+                }
+            }
+            let (near, far) = (&plates[first], &plates[second]);
+            let across = (far.center - near.center).normalize_or_zero();
+            let border = (position - (near.center + far.center) / 2.0).dot(across).abs();
+
+            // The crust slopes towards the average of both plates at the border.
+            let shelf = 1.0 - (border / config.shelf_width).clamp(0.0, 1.0);
+            let crust = near.height + (far.height - near.height) * shelf * shelf / 2.0;
+
+            // Positive when the plates move towards each other.
+            let convergence = (near.drift - far.drift).dot(across);
+            let falloff = (-(border / config.ridge_width).powi(2)).exp();
+            let uplift = if convergence > 0.0 {
+                convergence * config.ridge_height * falloff
+            } else {
+                convergence * config.rift_depth * falloff
+            };
+
+            (crust + uplift + (e - 0.5) * config.detail).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+fn seed_plates(width: usize, height: usize, config: &ContinentConfig, seed: u64) -> Vec<Plate> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..config.plates)
+        .map(|_| {
+            let center = DVec2::new(rng.random_range(0.0..width as f64), rng.random_range(0.0..height as f64));
+            let angle = rng.random_range(0.0..std::f64::consts::TAU);
+            let continental = rng.random_bool(config.continental_share.clamp(0.0, 1.0));
+            Plate {
+                center,
+                drift: DVec2::new(angle.cos(), angle.sin()) * rng.random_range(0.3..1.0),
+                height: if continental { CONTINENT_HEIGHT } else { OCEAN_HEIGHT },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use noise::{NoiseFn, Perlin};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::map::overworld_map::MapLayer;
+use crate::map::world_gen::GeneratedWorld;
+use crate::tile_type::GroundTiles;
+
+// Decorations of the overworld objects tileset, by kind.
+const BROADLEAF_TREES: &[u32] = &[198, 199, 202, 203];
+const PINE_TREES: &[u32] = &[239, 271];
+const ROCKS: &[u32] = &[10, 11, 296, 297];
+const CACTI: &[u32] = &[90, 91, 303, 335];
+const DEAD_TREES: &[u32] = &[364, 365, 366, 367];
+// Frequency of the noise grouping the decorations into groves and clearings.
+const DENSITY_SCALE: f64 = 0.08;
+
+///
+/// Decorations growing on a ground, and the share of the tiles they cover where the density
+/// noise is average. Only the grounds the biomes pass produces have some.
+///
+fn decorations(ground: GroundTiles) -> Option<(&'static [u32], f64)> {
+    match ground {
+        GroundTiles::BrightLushForest | GroundTiles::BrightDeciduousForest => Some((BROADLEAF_TREES, 0.8)),
+        GroundTiles::BrightPineForest => Some((PINE_TREES, 0.8)),
+        GroundTiles::LightGrass | GroundTiles::MediumGrass => Some((BROADLEAF_TREES, 0.05)),
+        GroundTiles::LightDirt => Some((ROCKS, 0.06)),
+        GroundTiles::LightSandDesert => Some((CACTI, 0.05)),
+        GroundTiles::LightTemperateDesert => Some((DEAD_TREES, 0.04)),
+        _ => None,
+    }
+}
+
+///
+/// Scatters trees, rocks and cacti over the decoration layer of a world. A noise groups them,
+/// so that forests have clearings and a few groves stand on the plains. The places of
+/// interest and the buildings are kept free, and the roads and town squares have no
+/// decorations for their ground.
+///
+pub fn place_decorations(world: &mut GeneratedWorld) {
+    let mut rng = StdRng::seed_from_u64(world.seed ^ 0xDEC0_4A7E_0000_0001);
+    let noise = Perlin::new((world.seed >> 32) as u32 ^ world.seed as u32);
+    let occupied: HashSet<IVec2> = world.points_of_interest.iter().map(|poi| poi.tile).collect();
+
+    let mut placed = 0;
+    for index in 0..world.biomes.len() {
+        let tile = world.tile(index);
+        let Some((tiles, share)) = decorations(world.biomes[index]) else { continue; };
+        if occupied.contains(&tile) || world.layer_tile(MapLayer::Structures, tile).is_some() {
+            continue;
+        }
+        // From none where the noise is lowest to twice the share where it is highest.
+        let density = (noise.get([tile.x as f64 * DENSITY_SCALE, tile.y as f64 * DENSITY_SCALE]) + 1.0) / 2.0;
+        if rng.random_bool((share * density * 2.0).clamp(0.0, 1.0)) {
+            world.set_layer_tile(MapLayer::Decoration, tile, Some(tiles[rng.random_range(0..tiles.len())]));
+            placed += 1;
+        }
+    }
+    info!("Placed {} decorations in the overworld.", placed);
+}
+
+///
+/// Ground drawn under the trees of a forest, once the season has been applied to the biome.
+/// The forest stays the biome of the world, for the monsters and the settlements, and its
+/// trees are drawn on the decoration layer.
+///
+pub fn forest_floor(ground: GroundTiles) -> GroundTiles {
+    match ground {
+        GroundTiles::BrightLushForest | GroundTiles::BrightDeciduousForest => GroundTiles::MediumGrass,
+        GroundTiles::BrightPineForest => GroundTiles::DarkGrass,
+        GroundTiles::MediumFrozenPineForest => GroundTiles::MediumFrozenField,
+        GroundTiles::DarkFrozenPineForest => GroundTiles::DarkFrozenField,
+        _ => ground,
+    }
+}
@@ -0,0 +1,358 @@
+use bevy::prelude::*;
+use bevy_ecs_tiled::prelude::*;
+
+use crate::constants::TILE_SIZE_PX;
+use crate::events::{ChunkLoaded, ChunkUnloaded};
+use crate::game_log::GameLog;
+use crate::inventory::GroundItem;
+use crate::map::automap::AutomapRules;
+use crate::map::dungeon_gen::{
+    generate_dungeon, spawn_dungeon_tilemap, DungeonLayout, DungeonParams, DungeonTile, CAVE_DOOR, CAVE_STAIRS_DOWN,
+    CAVE_TILESET,
+};
+use crate::map::overworld_map::{PoiKind, PointOfInterest};
+use crate::map::world_gen::GeneratedWorld;
+use crate::map::{tile_coords_to_world, tile_to_chunk_coords, world_to_tile_coords};
+use crate::player::{Player, PlayerCamera};
+use crate::states::GameState;
+
+// Above the ground, under the ground items.
+const ENTRANCE_Z: f32 = 1.5;
+const PLAYER_Z: f32 = 10.0;
+// FDR_Caves.png is a 32 x 32 grid of 16 px tiles.
+const CAVE_TILESET_COLUMNS: u32 = 32;
+const CAVE_TILESET_TILE: u32 = 16;
+
+///
+/// Hand-made Tiled map used instead of a generated level. `arrival` is the tile the player
+/// arrives on, which also leads back up. Hand-made levels have no stairs down.
+///
+#[derive(Reflect, Debug, Clone)]
+pub struct HandMadeLevel {
+    pub kind: PoiKind,
+    pub depth: u32,
+    pub map: String,
+    pub arrival: IVec2,
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct DungeonConfig {
+    /// Deepest level of a dungeon, its stairs down are removed.
+    pub max_depth: u32,
+    pub hand_made: Vec<HandMadeLevel>,
+}
+
+impl Default for DungeonConfig {
+    fn default() -> Self {
+        DungeonConfig {
+            max_depth: 4,
+            hand_made: vec![HandMadeLevel {
+                kind: PoiKind::CaveMouth,
+                depth: 2,
+                map: "tiled_map_assets/begin_game_spawn_cave.tmx".to_string(),
+                arrival: IVec2::new(16, 8),
+            }],
+        }
+    }
+}
+
+///
+/// Overworld point of interest leading to a dungeon, spawned and despawned with its chunk.
+///
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DungeonEntrance {
+    pub poi: PointOfInterest,
+}
+
+///
+/// Marks the entities of the dungeon level currently spawned.
+///
+#[derive(Component, Debug)]
+struct LevelEntity;
+
+///
+/// Where the player is below the overworld. `depth` is 0 on the overworld.
+///
+#[derive(Resource, Debug, Default)]
+pub struct DungeonRun {
+    /// Point of interest the player went down through, the player comes back up on its tile.
+    pub entrance: Option<PointOfInterest>,
+    pub depth: u32,
+    stairs_up: Option<IVec2>,
+    stairs_down: Option<IVec2>,
+    /// Tile the player arrived on. Stairs and entrances on it are ignored until the player
+    /// steps off, so that arriving does not travel straight back.
+    arrival: Option<IVec2>,
+}
+
+#[derive(Resource, Default)]
+struct EntranceSprites {
+    image: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+}
+
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TiledPlugin>() {
+            app.add_plugins(TiledPlugin::default());
+        }
+        app.init_resource::<DungeonConfig>()
+            .init_resource::<DungeonRun>()
+            .init_resource::<EntranceSprites>()
+            .register_type::<DungeonConfig>()
+            .add_systems(Startup, load_entrance_sprites)
+            .add_systems(Update, (remove_entrances, place_entrances).chain())
+            .add_systems(Update, enter_dungeon.run_if(in_state(GameState::GameRunning)))
+            .add_systems(Update, use_stairs.run_if(in_state(GameState::Dungeon)))
+            .add_systems(OnEnter(GameState::Dungeon), spawn_first_level)
+            .add_systems(OnExit(GameState::Dungeon), leave_dungeon);
+    }
+}
+
+fn load_entrance_sprites(
+    asset_server: Res<AssetServer>,
+    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut sprites: ResMut<EntranceSprites>,
+) {
+    sprites.image = asset_server.load(CAVE_TILESET);
+    sprites.layout = atlas_layouts.add(TextureAtlasLayout::from_grid(
+        UVec2::splat(CAVE_TILESET_TILE),
+        CAVE_TILESET_COLUMNS,
+        CAVE_TILESET_COLUMNS,
+        None,
+        None,
+    ));
+}
+
+///
+/// Spawns the entrances of the points of interest of the chunks that were just loaded.
+///
+fn place_entrances(
+    mut commands: Commands,
+    world: Option<Res<GeneratedWorld>>,
+    sprites: Res<EntranceSprites>,
+    mut loaded: MessageReader<ChunkLoaded>,
+) {
+    let Some(world) = world else {
+        loaded.clear();
+        return;
+    };
+    for event in loaded.read() {
+        for poi in world.in_chunk(event.chunk).copied() {
+            let (name, index) = match poi.kind {
+                PoiKind::CaveMouth => ("Cave mouth", CAVE_STAIRS_DOWN),
+                PoiKind::Ruins => ("Ruins", CAVE_DOOR),
+                // Towns and villages are drawn by their paving.
+                PoiKind::Town | PoiKind::Village => continue,
+            };
+            let mut sprite = Sprite::from_atlas_image(
+                sprites.image.clone(),
+                TextureAtlas { layout: sprites.layout.clone(), index: index as usize },
+            );
+            sprite.custom_size = Some(Vec2::splat(TILE_SIZE_PX));
+            commands.spawn((
+                Name::new(name),
+                DungeonEntrance { poi },
+                sprite,
+                Transform::from_translation(tile_coords_to_world(poi.tile, ENTRANCE_Z)),
+            ));
+        }
+    }
+}
+
+fn remove_entrances(
+    mut commands: Commands,
+    mut unloaded: MessageReader<ChunkUnloaded>,
+    entrances: Query<(Entity, &DungeonEntrance)>,
+) {
+    for event in unloaded.read() {
+        for (entity, entrance) in entrances.iter() {
+            if tile_to_chunk_coords(entrance.poi.tile) == event.chunk {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+///
+/// Takes the player down when they step on an entrance.
+///
+fn enter_dungeon(
+    player_query: Query<&Transform, With<Player>>,
+    entrances: Query<&DungeonEntrance>,
+    mut run: ResMut<DungeonRun>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut log: ResMut<GameLog>,
+) {
+    let Ok(player_transform) = player_query.single() else { return; };
+    let tile = world_to_tile_coords(player_transform.translation);
+    if run.arrival == Some(tile) {
+        return;
+    }
+    run.arrival = None;
+
+    let Some(entrance) = entrances.iter().find(|entrance| entrance.poi.tile == tile) else { return; };
+    run.entrance = Some(entrance.poi);
+    run.depth = 1;
+    next_state.set(GameState::Dungeon);
+    log.info(match entrance.poi.kind {
+        PoiKind::CaveMouth => "You climb down into the cave.",
+        PoiKind::Ruins | PoiKind::Town | PoiKind::Village => "You go down into the ruins.",
+    });
+}
+
+fn spawn_first_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    rules: Res<AutomapRules>,
+    config: Res<DungeonConfig>,
+    mut run: ResMut<DungeonRun>,
+    mut players: Query<&mut Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut cameras: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+) {
+    let arrival = spawn_level(&mut commands, &asset_server, &rules, &config, &mut run, true);
+    place_player(arrival, &mut players, &mut cameras);
+}
+
+///
+/// Moves the player between levels when they step on stairs. The stairs up of the first
+/// level lead back to the overworld.
+///
+fn use_stairs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    rules: Res<AutomapRules>,
+    config: Res<DungeonConfig>,
+    mut run: ResMut<DungeonRun>,
+    levels: Query<Entity, With<LevelEntity>>,
+    ground_items: Query<Entity, With<GroundItem>>,
+    mut players: Query<&mut Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut cameras: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut log: ResMut<GameLog>,
+) {
+    let Ok(player_transform) = players.single() else { return; };
+    let tile = world_to_tile_coords(player_transform.translation);
+    if run.arrival == Some(tile) {
+        return;
+    }
+    run.arrival = None;
+
+    let going_down = run.stairs_down == Some(tile);
+    if !going_down && run.stairs_up != Some(tile) {
+        return;
+    }
+    if !going_down && run.depth <= 1 {
+        next_state.set(GameState::GameRunning);
+        return;
+    }
+
+    // Levels are not kept, they are generated again from their seed when the player comes back.
+    for entity in levels.iter().chain(ground_items.iter()) {
+        commands.entity(entity).despawn();
+    }
+    if going_down {
+        run.depth += 1;
+        log.info(format!("You go down to level {}.", run.depth));
+    } else {
+        run.depth -= 1;
+        log.info(format!("You go up to level {}.", run.depth));
+    }
+    let arrival = spawn_level(&mut commands, &asset_server, &rules, &config, &mut run, going_down);
+    place_player(arrival, &mut players, &mut cameras);
+}
+
+///
+/// Spawns the level at the current depth of the run and returns the tile the player arrives
+/// on: the stairs up when coming from above, the stairs down otherwise.
+///
+fn spawn_level(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    rules: &AutomapRules,
+    config: &DungeonConfig,
+    run: &mut DungeonRun,
+    from_above: bool,
+) -> IVec2 {
+    let Some(entrance) = run.entrance else { return IVec2::ZERO; };
+
+    let hand_made = config
+        .hand_made
+        .iter()
+        .find(|level| level.kind == entrance.kind && level.depth == run.depth);
+    let arrival = if let Some(hand_made) = hand_made {
+        commands.spawn((
+            Name::new(format!("Dungeon level {}", run.depth)),
+            LevelEntity,
+            TiledMap(asset_server.load(hand_made.map.clone())),
+            TilemapAnchor::None,
+            // Tiled tiles are 16 px, scaled up so that they line up with the world tiles.
+            Transform::from_scale(Vec2::splat(TILE_SIZE_PX / CAVE_TILESET_TILE as f32).extend(1.0)),
+        ));
+        run.stairs_up = Some(hand_made.arrival);
+        run.stairs_down = None;
+        hand_made.arrival
+    } else {
+        let layout = match entrance.kind {
+            PoiKind::CaveMouth => DungeonLayout::Caves,
+            PoiKind::Ruins | PoiKind::Town | PoiKind::Village => DungeonLayout::Rooms,
+        };
+        let seed = entrance.seed ^ (run.depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut level = generate_dungeon(&DungeonParams::new(layout, seed));
+        let deepest = run.depth >= config.max_depth;
+        if deepest {
+            level.set(level.exit, DungeonTile::Floor);
+        }
+        let tilemap = spawn_dungeon_tilemap(commands, asset_server, rules, &level, seed);
+        commands.entity(tilemap).insert((Name::new(format!("Dungeon level {}", run.depth)), LevelEntity));
+        run.stairs_up = Some(level.entrance);
+        run.stairs_down = (!deepest).then_some(level.exit);
+        if from_above { level.entrance } else { level.exit }
+    };
+    run.arrival = Some(arrival);
+    arrival
+}
+
+///
+/// Despawns the dungeon and puts the player back on the entrance they took. The overworld
+/// chunks come back around the player with their items and monsters.
+///
+fn leave_dungeon(
+    mut commands: Commands,
+    mut run: ResMut<DungeonRun>,
+    levels: Query<Entity, With<LevelEntity>>,
+    ground_items: Query<Entity, With<GroundItem>>,
+    mut players: Query<&mut Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut cameras: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+    mut log: ResMut<GameLog>,
+) {
+    for entity in levels.iter().chain(ground_items.iter()) {
+        commands.entity(entity).despawn();
+    }
+    let Some(entrance) = run.entrance.take() else { return; };
+    *run = DungeonRun { arrival: Some(entrance.tile), ..default() };
+    place_player(entrance.tile, &mut players, &mut cameras);
+    log.info("You are back on the surface.");
+}
+
+///
+/// Puts the player on a tile and the camera right over them, so that the tiles around the
+/// player are spawned at once.
+///
+fn place_player(
+    tile: IVec2,
+    players: &mut Query<&mut Transform, (With<Player>, Without<PlayerCamera>)>,
+    cameras: &mut Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+) {
+    let position = tile_coords_to_world(tile, PLAYER_Z);
+    for mut transform in players.iter_mut() {
+        transform.translation = position;
+    }
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
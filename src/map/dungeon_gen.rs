@@ -0,0 +1,613 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::constants::TILE_SIZE_PX;
+use crate::map::automap::{AutomapRules, TileLayers};
+use crate::map::tile_grid::TileProperties;
+
+// FDR_Caves.png is a 32 x 32 grid of 16 px tiles.
+pub const CAVE_TILESET: &str = "tiled_map_assets/tilesets/FDR_Caves.png";
+// Name of the tileset in the Tiled maps and rule maps.
+const CAVE_TILESET_NAME: &str = "FDR_Caves";
+// Layers of the hand-made caves, from the bottom.
+const DUNGEON_LAYERS: [&str; 3] = ["floor", "walls", "props"];
+const CAVE_TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16.0, y: 16.0 };
+// Indices of the FDR_Caves tiles used for each kind of dungeon tile.
+const CAVE_FLOOR: [u32; 3] = [7, 8, 9];
+// Bare rock, for walls away from any walkable tile.
+const CAVE_WALL: u32 = 33;
+// Tiles the cave rule map expects on the walls layer: the face of a wall above the floor (west
+// end, middle and east end), the edges of walls beside and below it, and the floor itself.
+// The rules grow the faces upwards and round the bottom corners.
+const CAVE_WALL_FACE: [u32; 3] = [67, 68, 69];
+const CAVE_WALL_WEST: u32 = 99;
+const CAVE_WALL_EAST: u32 = 101;
+const CAVE_WALL_SOUTH: u32 = 132;
+const CAVE_RULES_FLOOR: u32 = 100;
+pub const CAVE_DOOR: u32 = 189;
+const CAVE_STAIRS_UP: u32 = 347;
+pub const CAVE_STAIRS_DOWN: u32 = 434;
+// Generated levels smaller than this share of the map are thrown away and rolled again.
+const MIN_CAVE_FLOOR_RATIO: f32 = 0.3;
+const MAX_GENERATION_TRIES: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DungeonTile {
+    Wall,
+    Floor,
+    Door,
+    StairsUp,
+    StairsDown,
+}
+
+impl DungeonTile {
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, DungeonTile::Wall)
+    }
+
+    pub fn blocks_sight(&self) -> bool {
+        matches!(self, DungeonTile::Wall | DungeonTile::Door)
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum DungeonLayout {
+    /// Organic caves grown with a cellular automaton.
+    Caves,
+    /// Rectangular rooms from a binary space partition, joined by corridors and doors.
+    Rooms,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DungeonParams {
+    pub layout: DungeonLayout,
+    pub width: i32,
+    pub height: i32,
+    pub seed: u64,
+    /// Caves: chance for a tile to start as a wall, in percent.
+    pub fill_chance: i32,
+    /// Caves: number of automaton steps.
+    pub smoothing_steps: u32,
+    /// Rooms: smallest and largest side of a room, walls excluded.
+    pub room_size: (i32, i32),
+}
+
+impl DungeonParams {
+    pub fn new(layout: DungeonLayout, seed: u64) -> Self {
+        DungeonParams {
+            layout,
+            width: 64,
+            height: 48,
+            seed,
+            fill_chance: 45,
+            smoothing_steps: 5,
+            room_size: (4, 10),
+        }
+    }
+}
+
+///
+/// A generated level. `entrance` holds the stairs up and `exit` the stairs down, and there is
+/// always a walkable path between them.
+///
+#[derive(Debug, Clone)]
+pub struct DungeonLevel {
+    pub width: i32,
+    pub height: i32,
+    tiles: Vec<DungeonTile>,
+    pub entrance: IVec2,
+    pub exit: IVec2,
+}
+
+impl DungeonLevel {
+    fn filled(width: i32, height: i32, tile: DungeonTile) -> Self {
+        DungeonLevel {
+            width,
+            height,
+            tiles: vec![tile; (width * height) as usize],
+            entrance: IVec2::ZERO,
+            exit: IVec2::ZERO,
+        }
+    }
+
+    pub fn contains(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width && pos.y < self.height
+    }
+
+    ///
+    /// Tile at a position of the level. Everything outside is wall.
+    ///
+    pub fn get(&self, pos: IVec2) -> DungeonTile {
+        if !self.contains(pos) {
+            return DungeonTile::Wall;
+        }
+        self.tiles[(pos.y * self.width + pos.x) as usize]
+    }
+
+    pub fn set(&mut self, pos: IVec2, tile: DungeonTile) {
+        if self.contains(pos) {
+            self.tiles[(pos.y * self.width + pos.x) as usize] = tile;
+        }
+    }
+
+    fn positions(&self) -> impl Iterator<Item = IVec2> + use<> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| IVec2::new(x, y)))
+    }
+
+    ///
+    /// Walking distance from `start` to every reachable tile, `None` for the others.
+    ///
+    pub fn distances_from(&self, start: IVec2) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.tiles.len()];
+        if !self.get(start).is_walkable() {
+            return distances;
+        }
+        let mut queue = VecDeque::from([start]);
+        distances[(start.y * self.width + start.x) as usize] = Some(0);
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[(current.y * self.width + current.x) as usize].unwrap_or(0);
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = current + offset;
+                if !self.get(next).is_walkable() {
+                    continue;
+                }
+                let index = (next.y * self.width + next.x) as usize;
+                if distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    pub fn is_connected(&self, from: IVec2, to: IVec2) -> bool {
+        self.contains(to) && self.distances_from(from)[(to.y * self.width + to.x) as usize].is_some()
+    }
+
+}
+
+///
+/// Generates a level. The same parameters always give the same level.
+///
+pub fn generate_dungeon(params: &DungeonParams) -> DungeonLevel {
+    let mut level = None;
+    // A seed can give a degenerate level (tiny cave, no room fits), the next one is tried then.
+    for attempt in 0..MAX_GENERATION_TRIES {
+        let mut rng = StdRng::seed_from_u64(params.seed.wrapping_add(attempt));
+        let candidate = match params.layout {
+            DungeonLayout::Caves => generate_caves(params, &mut rng),
+            DungeonLayout::Rooms => generate_rooms(params, &mut rng),
+        };
+        if let Some(candidate) = candidate {
+            level = Some(candidate);
+            break;
+        }
+    }
+    let mut level = level.unwrap_or_else(|| fallback_level(params));
+    place_stairs(&mut level);
+    level
+}
+
+fn generate_caves(params: &DungeonParams, rng: &mut StdRng) -> Option<DungeonLevel> {
+    let mut level = DungeonLevel::filled(params.width, params.height, DungeonTile::Wall);
+    for pos in level.positions().collect::<Vec<_>>() {
+        let border = pos.x == 0 || pos.y == 0 || pos.x == params.width - 1 || pos.y == params.height - 1;
+        if !border && rng.random_range(0..100) >= params.fill_chance {
+            level.set(pos, DungeonTile::Floor);
+        }
+    }
+
+    for _ in 0..params.smoothing_steps {
+        let previous = level.clone();
+        for pos in previous.positions() {
+            let walls = (-1..=1)
+                .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+                .filter(|offset| *offset != IVec2::ZERO && previous.get(pos + *offset) == DungeonTile::Wall)
+                .count();
+            let border = pos.x == 0 || pos.y == 0 || pos.x == params.width - 1 || pos.y == params.height - 1;
+            let tile = if border || walls > 4 {
+                DungeonTile::Wall
+            } else if walls < 4 {
+                DungeonTile::Floor
+            } else {
+                previous.get(pos)
+            };
+            level.set(pos, tile);
+        }
+    }
+
+    // Only the largest cave is kept so that every floor tile is reachable.
+    let regions = floor_regions(&level);
+    let largest = regions.into_iter().max_by_key(|region| region.len())?;
+    if (largest.len() as f32) < (params.width * params.height) as f32 * MIN_CAVE_FLOOR_RATIO {
+        return None;
+    }
+    let kept: HashSet<IVec2> = largest.into_iter().collect();
+    for pos in level.positions().collect::<Vec<_>>() {
+        if level.get(pos) == DungeonTile::Floor && !kept.contains(&pos) {
+            level.set(pos, DungeonTile::Wall);
+        }
+    }
+    Some(level)
+}
+
+fn floor_regions(level: &DungeonLevel) -> Vec<Vec<IVec2>> {
+    let mut seen: HashSet<IVec2> = HashSet::new();
+    let mut regions = Vec::new();
+    for pos in level.positions() {
+        if !level.get(pos).is_walkable() || seen.contains(&pos) {
+            continue;
+        }
+        let mut region = Vec::new();
+        let mut queue = VecDeque::from([pos]);
+        seen.insert(pos);
+        while let Some(current) = queue.pop_front() {
+            region.push(current);
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let next = current + offset;
+                if level.get(next).is_walkable() && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    min: IVec2,
+    max: IVec2,
+}
+
+impl Rect {
+    fn size(&self) -> IVec2 {
+        self.max - self.min
+    }
+
+    fn center(&self) -> IVec2 {
+        (self.min + self.max) / 2
+    }
+}
+
+fn generate_rooms(params: &DungeonParams, rng: &mut StdRng) -> Option<DungeonLevel> {
+    let mut level = DungeonLevel::filled(params.width, params.height, DungeonTile::Wall);
+    let mut rooms: Vec<Rect> = Vec::new();
+    let mut room_mask: HashSet<IVec2> = HashSet::new();
+    let bounds = Rect { min: IVec2::ONE, max: IVec2::new(params.width - 1, params.height - 1) };
+    split_space(params, rng, bounds, &mut level, &mut rooms, &mut room_mask);
+    if rooms.len() < 2 {
+        return None;
+    }
+    place_doors(&mut level, &room_mask);
+    Some(level)
+}
+
+///
+/// Splits `area` in two until the parts are too small, carves a room in each leaf and joins
+/// the two halves of every split with a corridor. Returns the center of one room of the area.
+///
+fn split_space(
+    params: &DungeonParams,
+    rng: &mut StdRng,
+    area: Rect,
+    level: &mut DungeonLevel,
+    rooms: &mut Vec<Rect>,
+    room_mask: &mut HashSet<IVec2>,
+) -> Option<IVec2> {
+    let (min_room, max_room) = params.room_size;
+    // A part must fit the smallest room and its walls.
+    let min_part = min_room + 2;
+    let size = area.size();
+    let can_split_x = size.x >= min_part * 2;
+    let can_split_y = size.y >= min_part * 2;
+    let too_big = size.x > max_room + 2 || size.y > max_room + 2;
+
+    if too_big && (can_split_x || can_split_y) {
+        let split_x = if can_split_x && can_split_y { size.x >= size.y } else { can_split_x };
+        let (first, second) = if split_x {
+            let cut = rng.random_range(area.min.x + min_part..=area.max.x - min_part);
+            (Rect { min: area.min, max: IVec2::new(cut, area.max.y) }, Rect { min: IVec2::new(cut, area.min.y), max: area.max })
+        } else {
+            let cut = rng.random_range(area.min.y + min_part..=area.max.y - min_part);
+            (Rect { min: area.min, max: IVec2::new(area.max.x, cut) }, Rect { min: IVec2::new(area.min.x, cut), max: area.max })
+        };
+        let first_room = split_space(params, rng, first, level, rooms, room_mask);
+        let second_room = split_space(params, rng, second, level, rooms, room_mask);
+        if let (Some(from), Some(to)) = (first_room, second_room) {
+            carve_corridor(level, rng, from, to);
+        }
+        return first_room.or(second_room);
+    }
+
+    let max_width = (size.x - 2).min(max_room);
+    let max_height = (size.y - 2).min(max_room);
+    if max_width < min_room || max_height < min_room {
+        return None;
+    }
+    let width = rng.random_range(min_room..=max_width);
+    let height = rng.random_range(min_room..=max_height);
+    let min = IVec2::new(
+        rng.random_range(area.min.x + 1..=area.max.x - 1 - width),
+        rng.random_range(area.min.y + 1..=area.max.y - 1 - height),
+    );
+    let room = Rect { min, max: min + IVec2::new(width, height) };
+    for y in room.min.y..room.max.y {
+        for x in room.min.x..room.max.x {
+            level.set(IVec2::new(x, y), DungeonTile::Floor);
+            room_mask.insert(IVec2::new(x, y));
+        }
+    }
+    rooms.push(room);
+    Some(room.center())
+}
+
+///
+/// L-shaped corridor between two points, turning on a random axis first.
+///
+fn carve_corridor(level: &mut DungeonLevel, rng: &mut StdRng, from: IVec2, to: IVec2) {
+    let corner = if rng.random_bool(0.5) { IVec2::new(to.x, from.y) } else { IVec2::new(from.x, to.y) };
+    for (start, end) in [(from, corner), (corner, to)] {
+        let step = (end - start).signum();
+        let mut current = start;
+        while current != end {
+            if level.get(current) == DungeonTile::Wall {
+                level.set(current, DungeonTile::Floor);
+            }
+            current += step;
+        }
+        level.set(end, DungeonTile::Floor);
+    }
+}
+
+///
+/// A corridor tile touching a room becomes a door when it sits between two walls.
+///
+fn place_doors(level: &mut DungeonLevel, room_mask: &HashSet<IVec2>) {
+    let candidates: Vec<IVec2> = level
+        .positions()
+        .filter(|pos| level.get(*pos) == DungeonTile::Floor && !room_mask.contains(pos))
+        .filter(|pos| [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].iter().any(|offset| room_mask.contains(&(*pos + *offset))))
+        .filter(|pos| {
+            let walled_x = level.get(*pos + IVec2::X) == DungeonTile::Wall && level.get(*pos + IVec2::NEG_X) == DungeonTile::Wall;
+            let walled_y = level.get(*pos + IVec2::Y) == DungeonTile::Wall && level.get(*pos + IVec2::NEG_Y) == DungeonTile::Wall;
+            walled_x || walled_y
+        })
+        .collect();
+    for pos in candidates {
+        level.set(pos, DungeonTile::Door);
+    }
+}
+
+///
+/// Single open room, used when no seed gives a usable level.
+///
+fn fallback_level(params: &DungeonParams) -> DungeonLevel {
+    let mut level = DungeonLevel::filled(params.width, params.height, DungeonTile::Wall);
+    for pos in level.positions().collect::<Vec<_>>() {
+        if pos.x > 0 && pos.y > 0 && pos.x < params.width - 1 && pos.y < params.height - 1 {
+            level.set(pos, DungeonTile::Floor);
+        }
+    }
+    level
+}
+
+///
+/// Puts the entrance on the first floor tile and the exit on the floor tile furthest from it,
+/// which is reachable by construction.
+///
+fn place_stairs(level: &mut DungeonLevel) {
+    let Some(entrance) = level.positions().find(|pos| level.get(*pos) == DungeonTile::Floor) else { return; };
+    let distances = level.distances_from(entrance);
+    let exit = level
+        .positions()
+        .filter(|pos| level.get(*pos) == DungeonTile::Floor)
+        .max_by_key(|pos| distances[(pos.y * level.width + pos.x) as usize].unwrap_or(0))
+        .unwrap_or(entrance);
+    level.entrance = entrance;
+    level.exit = exit;
+    level.set(entrance, DungeonTile::StairsUp);
+    level.set(exit, DungeonTile::StairsDown);
+}
+
+///
+/// Tile of the walls layer the cave rule map expects for a wall, from the walkable tiles
+/// around it.
+///
+fn wall_tile(level: &DungeonLevel, pos: IVec2) -> u32 {
+    let walkable = |offset: IVec2| level.get(pos + offset).is_walkable();
+    if walkable(IVec2::NEG_Y) {
+        CAVE_WALL_FACE[1]
+    } else if walkable(IVec2::X) {
+        CAVE_WALL_WEST
+    } else if walkable(IVec2::NEG_X) {
+        CAVE_WALL_EAST
+    } else if walkable(IVec2::Y) {
+        CAVE_WALL_SOUTH
+    } else if walkable(IVec2::new(1, -1)) {
+        CAVE_WALL_FACE[0]
+    } else if walkable(IVec2::new(-1, -1)) {
+        CAVE_WALL_FACE[2]
+    } else {
+        CAVE_WALL
+    }
+}
+
+///
+/// Paints a level on the layers of the hand-made caves (floor, walls and props) and decorates
+/// it with the automapping rules. The walls layer only keeps tiles on walls, so that the rules
+/// never change where the level can be walked.
+///
+fn decorate_level(rules: &AutomapRules, level: &DungeonLevel, seed: u64) -> TileLayers {
+    let mut layers = TileLayers::new(level.width, level.height, CAVE_TILESET_NAME);
+    let mut rng = StdRng::seed_from_u64(seed);
+    for pos in level.positions() {
+        let tile = level.get(pos);
+        if tile.is_walkable() {
+            layers.set("floor", pos, Some(CAVE_FLOOR[rng.random_range(0..CAVE_FLOOR.len())]));
+            layers.set("walls", pos, Some(CAVE_RULES_FLOOR));
+        }
+        match tile {
+            DungeonTile::Wall => layers.set("walls", pos, Some(wall_tile(level, pos))),
+            DungeonTile::Door => layers.set("props", pos, Some(CAVE_DOOR)),
+            DungeonTile::StairsUp => layers.set("props", pos, Some(CAVE_STAIRS_UP)),
+            DungeonTile::StairsDown => layers.set("props", pos, Some(CAVE_STAIRS_DOWN)),
+            DungeonTile::Floor => {}
+        }
+    }
+    rules.apply(&mut layers, seed);
+    for pos in level.positions().filter(|pos| level.get(*pos).is_walkable()) {
+        layers.set("walls", pos, None);
+    }
+    layers
+}
+
+///
+/// Collision of a layer of a decorated level: whatever ends up on the walls layer blocks,
+/// doors also stop sight.
+///
+fn tile_properties(layers: &TileLayers, layer: &str) -> TileProperties {
+    let mut properties = TileProperties::default();
+    match layer {
+        "walls" => {
+            for y in 0..layers.height {
+                for x in 0..layers.width {
+                    if let Some(wall) = layers.get(layer, IVec2::new(x, y)) {
+                        properties.blocked.insert(wall);
+                        properties.opaque.insert(wall);
+                    }
+                }
+            }
+        }
+        "props" => {
+            properties.opaque.insert(CAVE_DOOR);
+        }
+        _ => {}
+    }
+    properties
+}
+
+///
+/// Spawns the tilemaps of a level with the FDR cave tileset, one per layer of the hand-made
+/// caves, under a single level entity which is returned. The level's tile (0, 0) is at the
+/// world origin and its tiles have the same size as the overworld ones.
+///
+pub fn spawn_dungeon_tilemap(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    rules: &AutomapRules,
+    level: &DungeonLevel,
+    seed: u64,
+) -> Entity {
+    let texture_handle: Handle<Image> = asset_server.load(CAVE_TILESET);
+    let map_size = TilemapSize { x: level.width as u32, y: level.height as u32 };
+    let layers = decorate_level(rules, level, seed);
+
+    // The 16 px cave tiles are scaled up to the size of the overworld tiles.
+    let scale = TILE_SIZE_PX / CAVE_TILE_SIZE.x;
+    let level_entity = commands
+        .spawn((Transform::from_scale(Vec3::new(scale, scale, 1.0)), Visibility::default()))
+        .id();
+
+    for (z, layer) in DUNGEON_LAYERS.into_iter().enumerate() {
+        let tilemap_entity = commands.spawn_empty().id();
+        let mut tile_storage = TileStorage::empty(map_size);
+        for pos in level.positions() {
+            let Some(texture_index) = layers.get(layer, pos) else { continue; };
+            let tile_pos = TilePos { x: pos.x as u32, y: pos.y as u32 };
+            let tile_entity = commands
+                .spawn(TileBundle {
+                    position: tile_pos,
+                    tilemap_id: TilemapId(tilemap_entity),
+                    texture_index: TileTextureIndex(texture_index),
+                    ..Default::default()
+                })
+                .id();
+            commands.entity(tilemap_entity).add_child(tile_entity);
+            tile_storage.set(&tile_pos, tile_entity);
+        }
+
+        commands.entity(tilemap_entity).insert((
+            Name::new(layer),
+            TilemapBundle {
+                grid_size: CAVE_TILE_SIZE.into(),
+                size: map_size,
+                storage: tile_storage,
+                texture: TilemapTexture::Single(texture_handle.clone()),
+                tile_size: CAVE_TILE_SIZE,
+                transform: Transform::from_xyz(0.0, 0.0, z as f32),
+                ..Default::default()
+            },
+            tile_properties(&layers, layer),
+        ));
+        commands.entity(level_entity).add_child(tilemap_entity);
+    }
+    level_entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::AUTOMAP_RULES_PATH;
+
+    const LAYOUTS: [DungeonLayout; 2] = [DungeonLayout::Caves, DungeonLayout::Rooms];
+
+    #[test]
+    fn every_walkable_tile_connects_to_the_stairs() {
+        for layout in LAYOUTS {
+            for seed in 0..10 {
+                let level = generate_dungeon(&DungeonParams::new(layout, seed));
+                assert_eq!(level.get(level.entrance), DungeonTile::StairsUp, "{:?} {}", layout, seed);
+                assert_eq!(level.get(level.exit), DungeonTile::StairsDown, "{:?} {}", layout, seed);
+                assert_ne!(level.entrance, level.exit);
+
+                let distances = level.distances_from(level.entrance);
+                for pos in level.positions().filter(|pos| level.get(*pos).is_walkable()) {
+                    let distance = distances[(pos.y * level.width + pos.x) as usize];
+                    assert!(distance.is_some(), "{:?} {}: {} cannot be reached from the stairs", layout, seed, pos);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_level() {
+        for layout in LAYOUTS {
+            let level = generate_dungeon(&DungeonParams::new(layout, 42));
+            let again = generate_dungeon(&DungeonParams::new(layout, 42));
+            assert_eq!(level.tiles, again.tiles);
+            assert_eq!((level.entrance, level.exit), (again.entrance, again.exit));
+
+            let other = generate_dungeon(&DungeonParams::new(layout, 43));
+            assert_ne!(level.tiles, other.tiles);
+        }
+    }
+
+    #[test]
+    fn decorated_room_gets_the_cave_walls() {
+        let rules = AutomapRules::load_or_default(AUTOMAP_RULES_PATH);
+        let mut level = DungeonLevel::filled(7, 7, DungeonTile::Wall);
+        for y in 2..=3 {
+            for x in 2..=4 {
+                level.set(IVec2::new(x, y), DungeonTile::Floor);
+            }
+        }
+        let layers = decorate_level(&rules, &level, 0);
+
+        let row = |y| (0..7).map(|x| layers.get("walls", IVec2::new(x, y)).unwrap_or(0)).collect::<Vec<_>>();
+        assert_eq!(row(6), vec![33, 3, 4, 4, 4, 5, 33]);
+        assert_eq!(row(5), vec![33, 35, 36, 36, 36, 37, 33]);
+        assert_eq!(row(4), vec![33, 67, 68, 68, 68, 69, 33]);
+        assert_eq!(row(3), vec![33, 99, 0, 0, 0, 101, 33]);
+        assert_eq!(row(2), vec![33, 99, 0, 0, 0, 101, 33]);
+        assert_eq!(row(1), vec![33, 131, 132, 132, 132, 133, 33]);
+        assert_eq!(row(0), vec![33; 7]);
+    }
+}
@@ -0,0 +1,287 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+///
+/// Settings of the erosion applied to the generated heightmaps. Thermal erosion slumps the
+/// slopes steeper than `talus` until they are stable, hydraulic erosion rolls rain droplets
+/// down the terrain that dig valleys and drop their sediment where they slow down.
+///
+#[derive(Reflect, Debug, Clone)]
+pub struct ErosionConfig {
+    pub enabled: bool,
+    /// Passes of thermal erosion over the whole map.
+    pub thermal_iterations: u32,
+    /// Steepest stable height difference between neighbouring tiles.
+    pub talus: f64,
+    /// Share of the excess material moved downhill by each pass.
+    pub thermal_rate: f64,
+    /// Rain droplets simulated, relative to the number of tiles.
+    pub droplets_per_tile: f64,
+    /// Steps a droplet lives before it evaporates completely.
+    pub droplet_lifetime: u32,
+    /// How much a droplet keeps its direction instead of following the slope, in `0..1`.
+    pub inertia: f64,
+    /// Sediment carried per unit of speed, water and slope.
+    pub capacity: f64,
+    /// Lowest slope used for the capacity, so droplets on flat ground still carry a little.
+    pub min_slope: f64,
+    /// Share of the free capacity dug from the ground at each step.
+    pub erode_rate: f64,
+    /// Share of the excess sediment dropped at each step.
+    pub deposit_rate: f64,
+    /// Share of the water lost at each step.
+    pub evaporation: f64,
+    pub gravity: f64,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        ErosionConfig {
+            enabled: true,
+            thermal_iterations: 20,
+            talus: 0.006,
+            thermal_rate: 0.5,
+            droplets_per_tile: 1.0,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_slope: 0.001,
+            erode_rate: 0.3,
+            deposit_rate: 0.3,
+            evaporation: 0.02,
+            gravity: 4.0,
+        }
+    }
+}
+
+///
+/// Erodes a row-major heightmap of `width` by `height` values in `0..=1`, hydraulic erosion
+/// first, then thermal erosion that slumps the banks the droplets dug. The droplets only
+/// depend on `seed`, so a heightmap always erodes the same way for a given seed.
+///
+pub fn erode(heights: &mut [f64], width: usize, height: usize, config: &ErosionConfig, seed: u64) {
+    if !config.enabled || width < 2 || height < 2 {
+        return;
+    }
+    hydraulic_erosion(heights, width, height, config, seed);
+    thermal_erosion(heights, width, height, config);
+    for value in heights.iter_mut() {
+        *value = value.clamp(0.0, 1.0);
+    }
+}
+
+///
+/// Moves material from each tile to its lower neighbours while the height difference is above
+/// the talus, in proportion to how far each one is below it.
+///
+pub fn thermal_erosion(heights: &mut [f64], width: usize, height: usize, config: &ErosionConfig) {
+    const NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    let mut deltas = vec![0.0; heights.len()];
+
+    for _ in 0..config.thermal_iterations {
+        deltas.iter_mut().for_each(|delta| *delta = 0.0);
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                let h = heights[index];
+                let mut lower = [(0, 0.0); 4];
+                let mut count = 0;
+                let mut total = 0.0;
+                let mut steepest: f64 = 0.0;
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                        continue;
+                    }
+                    let neighbour = ny as usize * width + nx as usize;
+                    let difference = h - heights[neighbour];
+                    if difference > config.talus {
+                        lower[count] = (neighbour, difference);
+                        count += 1;
+                        total += difference;
+                        steepest = steepest.max(difference);
+                    }
+                }
+                if count == 0 {
+                    continue;
+                }
+                let moved = config.thermal_rate * (steepest - config.talus) / 2.0;
+                deltas[index] -= moved;
+                for &(neighbour, difference) in &lower[..count] {
+                    deltas[neighbour] += moved * difference / total;
+                }
+            }
+        }
+        for (value, delta) in heights.iter_mut().zip(&deltas) {
+            *value += delta;
+        }
+    }
+}
+
+///
+/// Drops rain droplets at random tiles and follows them downhill. A droplet digs into the
+/// ground while it carries less sediment than it can, and deposits the excess when it slows
+/// down or climbs, until it evaporates or leaves the map.
+///
+pub fn hydraulic_erosion(heights: &mut [f64], width: usize, height: usize, config: &ErosionConfig, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let droplets = (config.droplets_per_tile * (width * height) as f64) as usize;
+    let max_x = (width - 1) as f64;
+    let max_y = (height - 1) as f64;
+
+    for _ in 0..droplets {
+        let mut position = DVec2::new(rng.random_range(0.0..max_x), rng.random_range(0.0..max_y));
+        let mut direction = DVec2::ZERO;
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..config.droplet_lifetime {
+            let (old_height, gradient) = height_and_gradient(heights, width, position);
+            direction = direction * config.inertia - gradient * (1.0 - config.inertia);
+            if direction.length_squared() < 1e-12 {
+                // Flat ground, the droplet wanders off in a random direction.
+                let angle = rng.random_range(0.0..std::f64::consts::TAU);
+                direction = DVec2::new(angle.cos(), angle.sin());
+            }
+            direction = direction.normalize();
+            let old_position = position;
+            position += direction;
+            if position.x < 0.0 || position.y < 0.0 || position.x >= max_x || position.y >= max_y {
+                break;
+            }
+
+            let (new_height, _) = height_and_gradient(heights, width, position);
+            let drop = old_height - new_height;
+            let capacity = drop.max(config.min_slope) * speed * water * config.capacity;
+            if drop < 0.0 || sediment > capacity {
+                // Uphill the droplet fills the hole behind it, otherwise drops part of the excess.
+                let deposit = if drop < 0.0 {
+                    sediment.min(-drop)
+                } else {
+                    (sediment - capacity) * config.deposit_rate
+                };
+                sediment -= deposit;
+                spread(heights, width, old_position, deposit);
+            } else {
+                // Never dig deeper than the drop, that would leave a pit behind.
+                let dug = ((capacity - sediment) * config.erode_rate).min(drop);
+                sediment += dug;
+                spread(heights, width, old_position, -dug);
+            }
+
+            speed = (speed * speed + drop * config.gravity).max(0.0).sqrt();
+            water *= 1.0 - config.evaporation;
+        }
+    }
+}
+
+///
+/// Height at a point between tiles, interpolated from the four surrounding tiles, and the
+/// slope there.
+///
+fn height_and_gradient(heights: &[f64], width: usize, position: DVec2) -> (f64, DVec2) {
+    let (x, y) = (position.x as usize, position.y as usize);
+    let (u, v) = (position.x - x as f64, position.y - y as f64);
+    let index = y * width + x;
+    let north_west = heights[index];
+    let north_east = heights[index + 1];
+    let south_west = heights[index + width];
+    let south_east = heights[index + width + 1];
+
+    let gradient = DVec2::new(
+        (north_east - north_west) * (1.0 - v) + (south_east - south_west) * v,
+        (south_west - north_west) * (1.0 - u) + (south_east - north_east) * u,
+    );
+    let height = north_west * (1.0 - u) * (1.0 - v)
+        + north_east * u * (1.0 - v)
+        + south_west * (1.0 - u) * v
+        + south_east * u * v;
+    (height, gradient)
+}
+
+///
+/// Adds `amount` to the four tiles surrounding a point, weighted by how close they are.
+///
+fn spread(heights: &mut [f64], width: usize, position: DVec2, amount: f64) {
+    let (x, y) = (position.x as usize, position.y as usize);
+    let (u, v) = (position.x - x as f64, position.y - y as f64);
+    let index = y * width + x;
+    heights[index] += amount * (1.0 - u) * (1.0 - v);
+    heights[index + 1] += amount * u * (1.0 - v);
+    heights[index + width] += amount * (1.0 - u) * v;
+    heights[index + width + 1] += amount * u * v;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 16;
+
+    ///
+    /// Cone peaking at the middle of the map, at 0.1 on its rim.
+    ///
+    fn cone() -> Vec<f64> {
+        let center = (SIZE / 2) as f64;
+        (0..SIZE * SIZE)
+            .map(|index| {
+                let (x, y) = ((index % SIZE) as f64, (index / SIZE) as f64);
+                (0.9 - ((x - center).powi(2) + (y - center).powi(2)).sqrt() * 0.08).max(0.1)
+            })
+            .collect()
+    }
+
+    fn steepest_slope(heights: &[f64]) -> f64 {
+        let mut steepest: f64 = 0.0;
+        for y in 0..SIZE {
+            for x in 0..SIZE - 1 {
+                steepest = steepest.max((heights[y * SIZE + x] - heights[y * SIZE + x + 1]).abs());
+            }
+        }
+        steepest
+    }
+
+    #[test]
+    fn disabled_erosion_leaves_the_map_alone() {
+        let mut heights = cone();
+        let config = ErosionConfig { enabled: false, ..default() };
+        erode(&mut heights, SIZE, SIZE, &config, 1);
+        assert_eq!(heights, cone());
+    }
+
+    #[test]
+    fn thermal_erosion_slumps_steep_slopes_and_keeps_the_material() {
+        let mut heights = vec![0.2; SIZE * SIZE];
+        heights[SIZE * SIZE / 2 + SIZE / 2] = 0.8;
+        let total: f64 = heights.iter().sum();
+        let before = steepest_slope(&heights);
+
+        thermal_erosion(&mut heights, SIZE, SIZE, &ErosionConfig::default());
+        assert!(steepest_slope(&heights) < before);
+        assert!((heights.iter().sum::<f64>() - total).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hydraulic_erosion_only_moves_or_washes_away_material() {
+        let mut heights = cone();
+        let total: f64 = heights.iter().sum();
+        hydraulic_erosion(&mut heights, SIZE, SIZE, &ErosionConfig::default(), 5);
+        assert_ne!(heights, cone());
+        // Sediment still carried by the droplets that left the map is lost.
+        assert!(heights.iter().sum::<f64>() <= total + 1e-9);
+    }
+
+    #[test]
+    fn erosion_is_seeded_and_stays_in_range() {
+        let config = ErosionConfig::default();
+        let (mut first, mut second, mut other) = (cone(), cone(), cone());
+        erode(&mut first, SIZE, SIZE, &config, 9);
+        erode(&mut second, SIZE, SIZE, &config, 9);
+        erode(&mut other, SIZE, SIZE, &config, 10);
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert!(first.iter().all(|height| (0.0..=1.0).contains(height)));
+    }
+}
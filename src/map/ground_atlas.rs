@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::assets::RonAssetAppExt;
+use crate::tile_type::GroundTiles;
+
+const GROUND_ATLAS_PATH: &str = "tiles/grounds_tiles.atlas.ron";
+
+///
+/// Descriptor of the image the overworld ground is drawn from. The ground tiles are drawn at
+/// the atlas index of their [`GroundTiles`] discriminant, so the descriptor is checked against
+/// the enum when it is loaded, and the image against the grid it describes. A tileset that
+/// does not line up is reported instead of silently drawing the wrong tiles.
+///
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct GroundAtlas {
+    /// Image path, relative to the assets folder.
+    pub image: String,
+    /// Size of a tile, in pixels.
+    pub tile_size: UVec2,
+    pub columns: u32,
+    pub rows: u32,
+    pub tiles: Vec<AtlasTile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AtlasTile {
+    /// Name of the [`GroundTiles`] variant.
+    pub name: String,
+    pub index: u32,
+    /// Atlas indices of the frames, for an animated tile.
+    #[serde(default)]
+    pub frames: Vec<u32>,
+    #[serde(default = "default_walkable")]
+    pub walkable: bool,
+    #[serde(default)]
+    pub opaque: bool,
+}
+
+fn default_walkable() -> bool {
+    true
+}
+
+impl GroundAtlas {
+    ///
+    /// Differences between the descriptor and [`GroundTiles`]: unknown or missing tiles,
+    /// indices outside of the grid or not matching the discriminant, and frames or
+    /// properties the game does not use for the tile.
+    ///
+    pub fn validate(&self) -> Vec<String> {
+        let grounds: HashMap<String, GroundTiles> = (0..GroundTiles::None as u32)
+            .map(GroundTiles::from)
+            .map(|ground| (format!("{:?}", ground), ground))
+            .collect();
+        let capacity = self.columns * self.rows;
+        let mut errors = Vec::new();
+        let mut listed = HashSet::new();
+
+        for tile in &self.tiles {
+            let Some(ground) = grounds.get(&tile.name).copied() else {
+                errors.push(format!("{} is not a GroundTiles variant", tile.name));
+                continue;
+            };
+            if !listed.insert(ground) {
+                errors.push(format!("{} is listed more than once", tile.name));
+            }
+            if tile.index >= capacity {
+                errors.push(format!("{} is at index {}, outside of the {} tiles of the grid", tile.name, tile.index, capacity));
+            }
+            if tile.index != ground as u32 {
+                errors.push(format!("{} is at index {} but GroundTiles::{} is {}", tile.name, tile.index, tile.name, ground as u32));
+            }
+
+            let frames: Vec<u32> = match ground.animation() {
+                Some((first, count)) => (first as u32..first as u32 + count).collect(),
+                None => Vec::new(),
+            };
+            if tile.frames != frames {
+                errors.push(format!("{} has the frames {:?}, the game animates it with {:?}", tile.name, tile.frames, frames));
+            }
+            if tile.walkable != ground.is_walkable() {
+                errors.push(format!("{} has walkable: {}, the game uses {}", tile.name, tile.walkable, ground.is_walkable()));
+            }
+            if tile.opaque != ground.blocks_sight() {
+                errors.push(format!("{} has opaque: {}, the game uses {}", tile.name, tile.opaque, ground.blocks_sight()));
+            }
+        }
+
+        for (name, ground) in grounds.iter() {
+            if !listed.contains(ground) {
+                errors.push(format!("{} is missing", name));
+            }
+        }
+        errors
+    }
+
+    ///
+    /// Size in pixels the image must have to hold the grid of the descriptor.
+    ///
+    pub fn image_size(&self) -> UVec2 {
+        self.tile_size * UVec2::new(self.columns, self.rows)
+    }
+}
+
+///
+/// Ground atlas used by the overworld, loaded at startup.
+///
+#[derive(Resource, Debug, Default)]
+pub struct GroundAtlasHandle(pub Handle<GroundAtlas>);
+
+///
+/// Loads the ground atlas and checks it, and its image, as soon as they are loaded. The
+/// check runs again when either file changes.
+///
+pub struct GroundAtlasPlugin;
+
+impl Plugin for GroundAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_ron_asset::<GroundAtlas>(&["atlas.ron"])
+            .init_resource::<GroundAtlasHandle>()
+            .add_systems(Startup, load_ground_atlas)
+            .add_systems(Update, validate_ground_atlas);
+    }
+}
+
+fn load_ground_atlas(
+    asset_server: Res<AssetServer>,
+    mut atlas_handle: ResMut<GroundAtlasHandle>,
+) {
+    atlas_handle.0 = asset_server.load(GROUND_ATLAS_PATH);
+}
+
+///
+/// Run condition of the systems drawing the overworld ground, true once the atlas is loaded.
+///
+pub fn ground_atlas_loaded(atlas_handle: Res<GroundAtlasHandle>, atlases: Res<Assets<GroundAtlas>>) -> bool {
+    atlases.contains(&atlas_handle.0)
+}
+
+fn validate_ground_atlas(
+    mut atlas_events: MessageReader<AssetEvent<GroundAtlas>>,
+    mut image_events: MessageReader<AssetEvent<Image>>,
+    asset_server: Res<AssetServer>,
+    atlas_handle: Res<GroundAtlasHandle>,
+    atlases: Res<Assets<GroundAtlas>>,
+    images: Res<Assets<Image>>,
+    mut image_handle: Local<Handle<Image>>,
+) {
+    let Some(atlas) = atlases.get(&atlas_handle.0) else { return; };
+    let atlas_changed = atlas_events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } if *id == atlas_handle.0.id())
+    });
+    if atlas_changed {
+        let errors = atlas.validate();
+        for e in &errors {
+            error!("Invalid ground atlas {}: {}.", GROUND_ATLAS_PATH, e);
+        }
+        if errors.is_empty() {
+            info!("Ground atlas {} matches the {} ground tiles.", GROUND_ATLAS_PATH, atlas.tiles.len());
+        }
+        *image_handle = asset_server.load(atlas.image.clone());
+    }
+
+    let image_changed = image_events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } if *id == image_handle.id())
+    });
+    // The image may have been loaded by the overworld before the atlas asked for it.
+    let image_ready = image_changed || (atlas_changed && images.contains(&*image_handle));
+    let Some(image) = images.get(&*image_handle).filter(|_| image_ready) else { return; };
+    if image.size() != atlas.image_size() {
+        error!(
+            "The ground image {} is {}x{} pixels, the atlas {} expects {} columns and {} rows of {}x{} pixels tiles ({}x{} pixels). The overworld will draw the wrong tiles.",
+            atlas.image,
+            image.size().x,
+            image.size().y,
+            GROUND_ATLAS_PATH,
+            atlas.columns,
+            atlas.rows,
+            atlas.tile_size.x,
+            atlas.tile_size.y,
+            atlas.image_size().x,
+            atlas.image_size().y,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///
+    /// Atlas listing every ground tile the way the game uses it.
+    ///
+    fn matching_atlas() -> GroundAtlas {
+        let tiles: Vec<AtlasTile> = (0..GroundTiles::None as u32)
+            .map(GroundTiles::from)
+            .map(|ground| AtlasTile {
+                name: format!("{:?}", ground),
+                index: ground as u32,
+                frames: ground.animation().map_or(Vec::new(), |(first, count)| (first as u32..first as u32 + count).collect()),
+                walkable: ground.is_walkable(),
+                opaque: ground.blocks_sight(),
+            })
+            .collect();
+        GroundAtlas {
+            image: "tiles/grounds_tiles.png".to_string(),
+            tile_size: UVec2::splat(32),
+            columns: 10,
+            rows: (tiles.len() as u32).div_ceil(10),
+            tiles,
+        }
+    }
+
+    fn tile(atlas: &mut GroundAtlas, ground: GroundTiles) -> &mut AtlasTile {
+        atlas.tiles.iter_mut().find(|tile| tile.name == format!("{:?}", ground)).unwrap()
+    }
+
+    #[test]
+    fn shipped_atlas_matches_the_ground_tiles() {
+        let content = std::fs::read_to_string(format!("assets/{}", GROUND_ATLAS_PATH)).unwrap();
+        let atlas: GroundAtlas = ron::from_str(&content).unwrap();
+        assert_eq!(atlas.validate(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn matching_atlas_is_valid() {
+        assert_eq!(matching_atlas().validate(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unknown_duplicate_and_missing_tiles_are_reported() {
+        let mut atlas = matching_atlas();
+        tile(&mut atlas, GroundTiles::LightGrass).name = "Lava".to_string();
+        let duplicate = atlas.tiles[0].clone();
+        atlas.tiles.push(duplicate);
+        let errors = atlas.validate();
+        assert!(errors.contains(&"Lava is not a GroundTiles variant".to_string()));
+        assert!(errors.contains(&format!("{:?} is listed more than once", GroundTiles::from(0))));
+        assert!(errors.contains(&"LightGrass is missing".to_string()));
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn indices_must_be_the_discriminants_inside_the_grid() {
+        let mut atlas = matching_atlas();
+        atlas.rows = 1;
+        let errors = atlas.validate();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|error| error.contains("outside of the 10 tiles of the grid")));
+
+        let mut atlas = matching_atlas();
+        tile(&mut atlas, GroundTiles::MediumGrass).index = GroundTiles::LightGrass as u32;
+        assert_eq!(atlas.validate().len(), 1);
+    }
+
+    #[test]
+    fn frames_and_properties_must_match_the_game() {
+        let mut atlas = matching_atlas();
+        tile(&mut atlas, GroundTiles::LightGrass).frames = vec![1, 2];
+        let water = GroundTiles::MediumShallowWater;
+        tile(&mut atlas, water).walkable = !water.is_walkable();
+        tile(&mut atlas, water).opaque = !water.blocks_sight();
+        let errors = atlas.validate();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("LightGrass has the frames [1, 2]"));
+    }
+}
@@ -0,0 +1,231 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystems;
+use bevy_ecs_tiled::prelude::*;
+
+use crate::combat::Faction;
+use crate::constants::TILED_TYPES_EXPORT_PATH;
+use crate::events::MapTriggered;
+use crate::game_log::GameLog;
+use crate::input::{ActionState, InputAction};
+use crate::inventory::{spawn_ground_item, Inventory, ItemLibrary, ItemLibraryHandle, ItemStack};
+use crate::map::world_to_tile_coords;
+use crate::player::{Player, PlayerCamera};
+use crate::states::in_play;
+
+///
+/// Where the player appears. The spawn without a name is used when its map is loaded, named
+/// spawns are the targets of doors.
+///
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component, Default)]
+pub struct PlayerSpawn {
+    pub name: String,
+}
+
+///
+/// Takes the player to the spawn named `target` when they step on it. A door with a `key`
+/// only opens when that item is in the player's inventory.
+///
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component, Default)]
+pub struct Door {
+    pub target: String,
+    pub key: String,
+}
+
+///
+/// Shows `message` in the log and sends a [`MapTriggered`] when the player steps on it.
+///
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component, Default)]
+pub struct Trigger {
+    pub message: String,
+    /// Fires only the first time.
+    pub once: bool,
+}
+
+///
+/// Character the player talks to with the interact action from a neighbouring tile. NPCs are
+/// neutral actors, they block the way but are not attacked.
+///
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component, Default)]
+pub struct Npc {
+    pub name: String,
+    pub dialogue: String,
+}
+
+///
+/// Opened with the interact action from its tile or a neighbouring one, its items are dropped
+/// at the player's feet.
+///
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component, Default)]
+pub struct Chest {
+    pub item: String,
+    pub count: u32,
+    pub opened: bool,
+}
+
+impl Default for Chest {
+    fn default() -> Self {
+        Chest {
+            item: String::new(),
+            count: 1,
+            opened: false,
+        }
+    }
+}
+
+///
+/// Gives gameplay to the objects of Tiled maps. An object whose class is one of the types
+/// above gets that component, with its fields read from the object's properties. The types
+/// are exported to `TILED_TYPES_EXPORT_PATH` at startup, import that file in the Tiled
+/// project to place them.
+///
+pub struct MapObjectsPlugin;
+
+impl Plugin for MapObjectsPlugin {
+    fn build(&self, app: &mut App) {
+        // Added before the other map plugins so that their TiledPlugin is this one.
+        if !app.is_plugin_added::<TiledPlugin>() {
+            app.add_plugins(TiledPlugin(TiledPluginConfig {
+                tiled_types_export_file: Some(TILED_TYPES_EXPORT_PATH.into()),
+                tiled_types_filter: TiledFilter::from(vec![
+                    PlayerSpawn::type_path(),
+                    Door::type_path(),
+                    Trigger::type_path(),
+                    Npc::type_path(),
+                    Chest::type_path(),
+                ]),
+            }));
+        }
+        app.register_type::<PlayerSpawn>()
+            .register_type::<Door>()
+            .register_type::<Trigger>()
+            .register_type::<Npc>()
+            .register_type::<Chest>()
+            .add_systems(Update, make_npcs_actors)
+            .add_systems(Update, (step_on_objects, interact_with_objects).run_if(in_play))
+            // Spawns are placed once their map has been positioned.
+            .add_systems(PostUpdate, place_at_spawn.after(TransformSystems::Propagate));
+    }
+}
+
+fn make_npcs_actors(
+    mut commands: Commands,
+    npcs: Query<(Entity, &Npc), Added<Npc>>,
+) {
+    for (entity, npc) in npcs.iter() {
+        commands.entity(entity).insert((Name::new(npc.name.clone()), Faction::Neutral));
+    }
+}
+
+///
+/// Puts the player on the unnamed spawn of the maps that were just loaded.
+///
+fn place_at_spawn(
+    spawns: Query<(&PlayerSpawn, &GlobalTransform), Added<PlayerSpawn>>,
+    mut players: Query<&mut Transform, (With<Player>, Without<PlayerCamera>)>,
+    mut cameras: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+) {
+    let Some((_, spawn)) = spawns.iter().find(|(spawn, _)| spawn.name.is_empty()) else { return; };
+    let position = spawn.translation();
+    for mut transform in players.iter_mut().chain(cameras.iter_mut()) {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+///
+/// Runs the doors and triggers of the tile the player just stepped on.
+///
+fn step_on_objects(
+    mut commands: Commands,
+    mut last_tile: Local<Option<IVec2>>,
+    mut players: Query<(&mut Transform, &Inventory), (With<Player>, Without<PlayerCamera>)>,
+    mut cameras: Query<&mut Transform, (With<PlayerCamera>, Without<Player>)>,
+    doors: Query<(&Door, &GlobalTransform)>,
+    spawns: Query<(&PlayerSpawn, &GlobalTransform)>,
+    triggers: Query<(Entity, &Trigger, &GlobalTransform, Option<&Name>)>,
+    mut triggered: MessageWriter<MapTriggered>,
+    mut log: ResMut<GameLog>,
+) {
+    let Ok((mut player_transform, inventory)) = players.single_mut() else { return; };
+    let tile = world_to_tile_coords(player_transform.translation);
+    if *last_tile == Some(tile) {
+        return;
+    }
+    *last_tile = Some(tile);
+
+    for (entity, trigger, _, name) in triggers.iter().filter(|(.., transform, _)| world_to_tile_coords(transform.translation()) == tile) {
+        if !trigger.message.is_empty() {
+            log.info(trigger.message.clone());
+        }
+        triggered.write(MapTriggered {
+            trigger: entity,
+            name: name.map(|name| name.to_string()).unwrap_or_default(),
+        });
+        if trigger.once {
+            commands.entity(entity).remove::<Trigger>();
+        }
+    }
+
+    let Some((door, _)) = doors.iter().find(|(_, transform)| world_to_tile_coords(transform.translation()) == tile) else { return; };
+    if !door.key.is_empty() && !inventory.items.iter().any(|stack| stack.item == door.key) {
+        log.info("The door is locked.");
+        return;
+    }
+    let Some((_, target)) = spawns.iter().find(|(spawn, _)| spawn.name == door.target) else {
+        warn!("Door leads to the unknown spawn {}.", door.target);
+        return;
+    };
+    let position = target.translation();
+    player_transform.translation.x = position.x;
+    player_transform.translation.y = position.y;
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+    // A door on the arrival tile does not send the player straight back.
+    *last_tile = Some(world_to_tile_coords(position));
+}
+
+///
+/// Talks to the neighbouring NPCs and opens the neighbouring chests.
+///
+fn interact_with_objects(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<ItemLibraryHandle>,
+    libraries: Res<Assets<ItemLibrary>>,
+    player: Single<&Transform, With<Player>>,
+    npcs: Query<(&Npc, &GlobalTransform)>,
+    mut chests: Query<(&mut Chest, &GlobalTransform)>,
+    mut log: ResMut<GameLog>,
+) {
+    if !actions.just_pressed(InputAction::Interact) {
+        return;
+    }
+    let tile = world_to_tile_coords(player.translation);
+    let within_reach = |transform: &GlobalTransform| {
+        let offset = world_to_tile_coords(transform.translation()) - tile;
+        offset.x.abs() <= 1 && offset.y.abs() <= 1
+    };
+
+    for (npc, _) in npcs.iter().filter(|(_, transform)| within_reach(transform)) {
+        log.info(format!("{}: {}", npc.name, npc.dialogue));
+    }
+
+    let Some(library) = libraries.get(&library_handle.0) else { return; };
+    for (mut chest, _) in chests.iter_mut().filter(|(chest, transform)| !chest.opened && within_reach(transform)) {
+        chest.opened = true;
+        let Some(def) = library.get(&chest.item) else {
+            log.info("The chest is empty.");
+            continue;
+        };
+        log.info(format!("You open the chest and find {} x {}.", chest.count, def.name));
+        spawn_ground_item(&mut commands, &asset_server, def, ItemStack::new(&def.id, chest.count), tile);
+    }
+}
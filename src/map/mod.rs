@@ -1,3 +1,80 @@
+use bevy::ecs::query::QueryData;
+use bevy::prelude::*;
+
+use crate::constants::{CHUNK_SIZE, TILE_SIZE_PX};
+
+pub mod automap;
+pub mod autotile;
+pub mod collision;
+pub mod continents;
+pub mod decorations;
+pub mod dungeon;
+pub mod dungeon_gen;
+pub mod erosion;
+pub mod ground_atlas;
+pub mod map_objects;
 pub mod overworld_map;
+pub mod settlements;
+pub mod tile_animation;
+pub mod tile_grid;
+pub mod wind;
 pub mod world_map;
-pub mod world_gen_island;
\ No newline at end of file
+pub mod world_gen;
+pub mod world_gen_island;
+
+///
+/// Global tile coordinates of a world position. Chunks are laid out edge to edge starting at the
+/// origin, so this does not depend on which chunk holds the tile.
+///
+pub fn world_to_tile_coords(world_pos: Vec3) -> IVec2 {
+    IVec2::new(
+        (world_pos.x / TILE_SIZE_PX).round() as i32,
+        (world_pos.y / TILE_SIZE_PX).round() as i32,
+    )
+}
+
+///
+/// World position of the center of a tile, at the given depth.
+///
+pub fn tile_coords_to_world(tile: IVec2, z: f32) -> Vec3 {
+    Vec3::new(tile.x as f32 * TILE_SIZE_PX, tile.y as f32 * TILE_SIZE_PX, z)
+}
+
+///
+/// Coordinates of the chunk holding a global tile.
+///
+pub fn tile_to_chunk_coords(tile: IVec2) -> IVec2 {
+    IVec2::new(
+        tile.x.div_euclid(CHUNK_SIZE.x as i32),
+        tile.y.div_euclid(CHUNK_SIZE.y as i32),
+    )
+}
+
+///
+/// Hash of global tile coordinates, for what must look random from tile to tile but stay the
+/// same every time a tile is drawn.
+///
+pub fn tile_hash(tile: IVec2) -> u32 {
+    (tile.x as u32).wrapping_mul(73_856_093) ^ (tile.y as u32).wrapping_mul(19_349_663)
+}
+
+///
+/// Position of an actor on the tiles. Actors placed on a Tiled map are children of the scaled
+/// map and are read from their global transform. The others are read from their transform, that
+/// moves during the turn before the global transforms are propagated.
+///
+#[derive(QueryData)]
+pub struct ActorTile {
+    transform: &'static Transform,
+    global_transform: &'static GlobalTransform,
+    child_of: Option<&'static ChildOf>,
+}
+
+impl ActorTileItem<'_, '_> {
+    pub fn tile(&self) -> IVec2 {
+        match self.child_of {
+            Some(_) => world_to_tile_coords(self.global_transform.translation()),
+            None => world_to_tile_coords(self.transform.translation),
+        }
+    }
+}
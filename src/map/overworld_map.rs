@@ -1,4 +1,4 @@
-use bevy::{math::Vec4Swizzles, prelude::*};
+use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 use rand::prelude::*;
 use noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex, Perlin, Clamp, Blend, RidgedMulti};
@@ -11,15 +11,32 @@ use bevy_inspector_egui::{
 };
 
 use crate::{constants::*};
-use crate::events::{MoveEvent, MoveLegal};
+use crate::events::{ChunkLoaded, ChunkUnloaded, GroundChanged};
+use crate::map::automap::TileLayers;
+use crate::map::continents::{ContinentConfig, WorldShape};
+use crate::map::decorations::forest_floor;
+use crate::map::erosion::ErosionConfig;
+use crate::map::ground_atlas::{GroundAtlas, GroundAtlasHandle};
+use crate::map::settlements::structure_properties;
+use crate::map::tile_animation::AnimatedTile;
+use crate::map::tile_grid::{TileGrid, TileOverlay};
+use crate::map::wind::WindConfig;
+use crate::map::world_gen::{GeneratedWorld, WorldGenPlugin};
+use crate::input::{ActionState, InputAction};
 use crate::{tile_type::*};
 use crate::states::GameState;
+use crate::world_clock::{Season, SeasonChanged, WorldClock};
 
 
-const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 32.0, y: 32.0 };
+const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: TILE_SIZE_PX, y: TILE_SIZE_PX };
 // maximum number of chunks that can exist (derived from OVERWORLD_SIZE_* and CHUNK_SIZE)
 const MAX_SPAWNED_CHUNKS: usize = ((OVERWORLD_SIZE_WIDTH as usize + CHUNK_SIZE.x as usize - 1) / CHUNK_SIZE.x as usize)
     * ((OVERWORLD_SIZE_HEIGHT as usize + CHUNK_SIZE.y as usize - 1) / CHUNK_SIZE.y as usize);
+// Name rule maps use for the tileset of tiles/grounds_tiles.png.
+const GROUND_TILESET_NAME: &str = "grounds_tiles";
+// Tileset of the trees and houses of the decoration and structure layers, of smaller tiles.
+const OBJECTS_TILESET: &str = "tiled_map_assets/tilesets/FDR_Overworld.png";
+const OBJECTS_TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16.0, y: 16.0 };
 
 
 #[derive(Reflect, Resource, InspectorOptions, Debug, Clone)]
@@ -33,6 +50,10 @@ pub struct OverWorldMapConfig {
     persistance: f64,
     amplitude: f32,
     pow_factor: f64,
+    shape: WorldShape,
+    continents: ContinentConfig,
+    erosion: ErosionConfig,
+    wind: WindConfig,
 }
 
 impl Default for OverWorldMapConfig {
@@ -50,6 +71,188 @@ impl Default for OverWorldMapConfig {
             amplitude: 0.5,
             // avoid aggressively compressing elevation distribution
             pow_factor: 1.0,
+            shape: WorldShape::default(),
+            continents: ContinentConfig::default(),
+            erosion: ErosionConfig::default(),
+            wind: WindConfig::default(),
+        }
+    }
+}
+
+impl OverWorldMapConfig {
+    ///
+    /// Seed of the generated world. Everything placed by the generator derives from it.
+    ///
+    pub fn seed(&self) -> u64 {
+        ((self.e_seed as u32 as u64) << 32) | self.m_seed as u32 as u64
+    }
+
+    pub fn shape(&self) -> WorldShape {
+        self.shape
+    }
+
+    pub fn continents(&self) -> &ContinentConfig {
+        &self.continents
+    }
+
+    pub fn erosion(&self) -> &ErosionConfig {
+        &self.erosion
+    }
+
+    pub fn wind(&self) -> &WindConfig {
+        &self.wind
+    }
+}
+
+///
+/// Elevation, moisture and temperature of an overworld tile, all in `0..=1`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Climate {
+    pub elevation: f64,
+    pub moisture: f64,
+    pub temperature: f64,
+}
+
+impl Climate {
+    ///
+    /// Ground tile picked for this climate by the overworld generator.
+    ///
+    pub fn ground(&self) -> GroundTiles {
+        GroundTiles::from(biome(self.elevation, self.moisture, self.temperature))
+    }
+}
+
+///
+/// Noise functions of the overworld built from an [`OverWorldMapConfig`]. The passes of the
+/// [`WorldGenPipeline`](crate::map::world_gen::WorldGenPipeline) read the raw climate of the tiles here.
+///
+pub struct ClimateSampler {
+    e_noise: Blend<f64, OpenSimplex, RidgedMulti<OpenSimplex>, Fbm<OpenSimplex>, 2>,
+    fbm_warp: Fbm<OpenSimplex>,
+    m_noise: OpenSimplex,
+    temp_noise: OpenSimplex,
+    pow_factor: f64,
+}
+
+impl ClimateSampler {
+    pub fn new(map_config: &OverWorldMapConfig) -> Self {
+        let open_simplex: OpenSimplex = OpenSimplex::new(map_config.e_seed as u32);
+        let ridged = RidgedMulti::<OpenSimplex>::new(map_config.e_seed as u32);
+        let fbm_main = Fbm::<OpenSimplex>::new(map_config.e_seed as u32)
+            .set_octaves(map_config.octaves as usize)
+            .set_frequency(map_config.frequency)
+            .set_persistence(map_config.persistance)
+            .set_lacunarity(map_config.lacunarity);
+        let fbm_warp = Fbm::<OpenSimplex>::new(map_config.e_seed as u32)
+            .set_octaves(map_config.octaves as usize)
+            .set_frequency(map_config.frequency)
+            .set_persistence(map_config.persistance)
+            .set_lacunarity(map_config.lacunarity);
+
+        ClimateSampler {
+            e_noise: Blend::new(open_simplex, ridged, fbm_main),
+            fbm_warp,
+            m_noise: OpenSimplex::new(map_config.m_seed as u32),
+            temp_noise: OpenSimplex::new((map_config.m_seed as u32).wrapping_add(12345)), // different seed for temperature
+            pow_factor: map_config.pow_factor,
+        }
+    }
+
+    ///
+    /// Elevation of a tile straight from the noise, before erosion.
+    ///
+    pub fn elevation(&self, tile: IVec2) -> f64 {
+        let (nx, ny) = normalized(tile);
+
+        // Domain-warp for more organic terrain
+        let warp_amp = 0.08; // tweakable
+        let warp = self.fbm_warp.get([nx * 2.0, ny * 2.0]) * warp_amp;
+        let mut e_value = self.e_noise.get([nx + warp, ny + warp]);
+
+        // multi-scale detail (kept but normalized)
+        e_value += 0.5 * self.e_noise.get([2.0 * (nx + warp), 2.0 * (ny + warp)]);
+        e_value += 0.25 * self.e_noise.get([4.0 * (nx + warp), 4.0 * (ny + warp)]);
+        e_value /= 1.0 + 0.5 + 0.25;
+        e_value = normalize_noise(e_value);
+        e_value.powf(self.pow_factor)
+    }
+
+    ///
+    /// Moisture of a tile at the given elevation.
+    ///
+    pub fn moisture(&self, tile: IVec2, e_value: f64) -> f64 {
+        let (nx, ny) = normalized(tile);
+        // Moisture: base noise, biased by elevation (lowlands wetter) and some temperature influence
+        let m_value = normalize_noise(self.m_noise.get([nx * 1.5, ny * 1.5]));
+        m_value * 0.7 + (1.0 - e_value) * 0.3 // mountains drier
+    }
+
+    ///
+    /// Temperature of a tile at the given elevation.
+    ///
+    pub fn temperature(&self, tile: IVec2, e_value: f64) -> f64 {
+        let (nx, ny) = normalized(tile);
+        // Temperature: latitude gradient + noise + elevation penalty (higher = colder)
+        let lat = 1.0 - (ny + 0.5).abs() * 1.0; // center is warm, poles cold
+        let mut t_value = lat.clamp(0.0, 1.0);
+        t_value += normalize_noise(self.temp_noise.get([nx * 2.0, ny * 2.0])) * 0.12;
+        t_value -= e_value * 0.5; // elevation cools
+        t_value.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoiKind {
+    /// Opening at the foot of a mountain.
+    CaveMouth,
+    /// Ruined building in a forest.
+    Ruins,
+    Town,
+    Village,
+}
+
+///
+/// Place of the overworld the player can visit. `seed` identifies it, so that it always leads
+/// to the same dungeon.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointOfInterest {
+    pub kind: PoiKind,
+    pub tile: IVec2,
+    pub seed: u64,
+}
+
+///
+/// Tilemaps stacked in each chunk of the overworld, from the bottom up. The generator fills
+/// the ground with the biomes and the decorations and structures through
+/// [`GeneratedWorld::set_layer_tile`]. The overlay starts empty. Only the ground counts for
+/// the [`TileGrid`], the other layers are for looks.
+///
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapLayer {
+    Ground,
+    /// Trees, rocks and cacti.
+    Decoration,
+    /// Houses of the towns and villages.
+    Structures,
+    /// Field of view and selection markers, over the items lying on the ground.
+    Overlay,
+}
+
+impl MapLayer {
+    pub const ALL: [MapLayer; 4] = [MapLayer::Ground, MapLayer::Decoration, MapLayer::Structures, MapLayer::Overlay];
+
+    ///
+    /// Depth of the tilemaps of the layer. The terrain transitions go between the ground and
+    /// the decorations.
+    ///
+    pub fn z(&self) -> f32 {
+        match self {
+            MapLayer::Ground => 0.0,
+            MapLayer::Decoration => 0.7,
+            MapLayer::Structures => 0.8,
+            MapLayer::Overlay => 4.0,
         }
     }
 }
@@ -63,18 +266,21 @@ pub struct OverWorldMapPlugin;
 
 impl Plugin for OverWorldMapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(TilemapPlugin)
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.add_plugins((TilemapPlugin, WorldGenPlugin))
             .insert_resource(ChunkManager::default())
-            .add_plugins(EguiPlugin::default())
             .add_plugins(DefaultInspectorConfigPlugin)
             .init_resource::<OverWorldMapConfig>()
             .register_type::<OverWorldMapConfig>()
-            .add_systems(Update, spawn_chunk_around_camera)
-            //.add_systems(Update, despawn_outofrange_chunks)
-            //.add_systems(Update, camera_movement)
+            // The overworld stays as it is while the player is in a dungeon.
+            .add_systems(Update, (spawn_chunk_around_camera, despawn_outofrange_chunks).chain().run_if(not(in_state(GameState::Dungeon))))
+            .add_systems(Update, apply_season.run_if(not(in_state(GameState::Dungeon))))
+            .add_systems(OnEnter(GameState::Dungeon), unload_all_chunks)
+            //.add_systems(Update, camera_movement.run_if(not(any_with_component::<Player>)))
             .add_systems(Update, reset_map.run_if(in_state(GameState::DirtyMap)))
-            .add_systems(EguiPrimaryContextPass, inspector_ui)
-            .add_systems(Update, move_event_listener);
+            .add_systems(EguiPrimaryContextPass, inspector_ui);
     }
 }
 
@@ -98,53 +304,72 @@ fn inspector_ui(world: &mut World) {
     });
 }
 
+///
+/// Despawns the whole map so that it is generated again with the new configuration. The
+/// chunks are reported as unloaded, which takes their monsters, items and entrances with them.
+///
 fn reset_map(
     mut commands: Commands,
-    chunks_query: Query<(Entity, &Transform), With<TilemapTileSize>>,
+    chunks_query: Query<(Entity, &Transform), With<TileStorage>>,
     mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_unloaded: MessageWriter<ChunkUnloaded>,
     mut next_state: ResMut<NextState<GameState>>,
 ) {
-    for (entity, _transform) in chunks_query.iter() {
-        commands.entity(entity).despawn();
-    }
+    // Generated again with the new configuration.
+    commands.remove_resource::<GeneratedWorld>();
+    despawn_all_chunks(&mut commands, &chunks_query, &mut chunk_manager, &mut chunk_unloaded);
     chunk_manager.spawned_chunks.clear();
     next_state.set(GameState::GameRunning);
     info!("Map has been reset.");
 }
 
+///
+/// Despawns every chunk when the player goes down a dungeon. The chunks are reported as
+/// unloaded so that their items and monsters are kept until the player comes back.
+///
+fn unload_all_chunks(
+    mut commands: Commands,
+    chunks_query: Query<(Entity, &Transform), With<TileStorage>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_unloaded: MessageWriter<ChunkUnloaded>,
+) {
+    despawn_all_chunks(&mut commands, &chunks_query, &mut chunk_manager, &mut chunk_unloaded);
+}
+
+fn despawn_all_chunks(
+    commands: &mut Commands,
+    chunks_query: &Query<(Entity, &Transform), With<TileStorage>>,
+    chunk_manager: &mut ChunkManager,
+    chunk_unloaded: &mut MessageWriter<ChunkUnloaded>,
+) {
+    for (entity, chunk_transform) in chunks_query.iter() {
+        let chunk_pos = chunk_transform.translation.xy();
+        let x = (chunk_pos.x / (CHUNK_SIZE.x as f32 * TILE_SIZE.x)).floor() as i32;
+        let y = (chunk_pos.y / (CHUNK_SIZE.y as f32 * TILE_SIZE.y)).floor() as i32;
+        if chunk_manager.spawned_chunks.remove(&IVec2::new(x, y)) {
+            chunk_unloaded.write(ChunkUnloaded { chunk: IVec2::new(x, y) });
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
 fn camera_movement(
     time: Res<Time>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut query: Query<(&mut Transform, &mut Projection), With<Camera>>,
 ) {
     for (mut transform, mut projection) in query.iter_mut() {
-        let mut direction = Vec3::ZERO;
-
-        if keyboard_input.pressed(KeyCode::KeyA) {
-            direction -= Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyD) {
-            direction += Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyW) {
-            direction += Vec3::new(0.0, 1.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyS) {
-            direction -= Vec3::new(0.0, 1.0, 0.0);
-        }
+        let direction = actions.move_vector().extend(0.0);
 
         let Projection::Orthographic(ortho) = &mut *projection else {
             continue;
-        };        
+        };
 
-        if keyboard_input.pressed(KeyCode::KeyZ) {
+        if actions.pressed(InputAction::ZoomOut) {
             ortho.scale += 0.1;
         }
 
-        if keyboard_input.pressed(KeyCode::KeyX) {
+        if actions.pressed(InputAction::ZoomIn) {
             ortho.scale -= 0.1;
         }
 
@@ -172,8 +397,15 @@ fn spawn_chunk_around_camera(
     asset_server: Res<AssetServer>,
     camera_query: Query<&Transform, With<Camera>>,
     mut chunk_manager: ResMut<ChunkManager>,
-    map_config: Res<OverWorldMapConfig>,
+    world: Option<Res<GeneratedWorld>>,
+    clock: Res<WorldClock>,
+    atlas_handle: Res<GroundAtlasHandle>,
+    atlases: Res<Assets<GroundAtlas>>,
+    mut chunk_loaded: MessageWriter<ChunkLoaded>,
 ) {
+    // Chunks are cut from the generated world once it is there, and drawn from the atlas.
+    let Some(world) = world else { return; };
+    let Some(atlas) = atlases.get(&atlas_handle.0) else { return; };
     // number of chunks that fit in the overworld grid
     let chunks_x = ((OVERWORLD_SIZE_WIDTH as i32 + CHUNK_SIZE.x as i32 - 1) / CHUNK_SIZE.x as i32);
     let chunks_y = ((OVERWORLD_SIZE_HEIGHT as i32 + CHUNK_SIZE.y as i32 - 1) / CHUNK_SIZE.y as i32);
@@ -202,7 +434,8 @@ fn spawn_chunk_around_camera(
                 let pos = IVec2::new(x, y);
                 if !chunk_manager.spawned_chunks.contains(&pos) {
                     chunk_manager.spawned_chunks.insert(pos);
-                    spawn_chunk(&mut commands, &asset_server, &map_config, pos);
+                    spawn_chunk(&mut commands, &asset_server, atlas, &world, clock.season(), pos);
+                    chunk_loaded.write(ChunkLoaded { chunk: pos });
                 }
             }
         }
@@ -216,8 +449,9 @@ fn spawn_chunk_around_camera(
 fn despawn_outofrange_chunks(
     mut commands: Commands,
     camera_query: Query<&Transform, With<Camera>>,
-    chunks_query: Query<(Entity, &Transform)>,
-    mut chunk_manager: ResMut<ChunkManager>
+    chunks_query: Query<(Entity, &Transform), With<TileStorage>>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut chunk_unloaded: MessageWriter<ChunkUnloaded>,
 ) {
     const CHUNK_DESPAWN_DISTANCE: f32 = (CHUNK_SIZE.x as f32 * TILE_SIZE.x) * 6.5;
 
@@ -228,7 +462,9 @@ fn despawn_outofrange_chunks(
             if distance > CHUNK_DESPAWN_DISTANCE {
                 let x = (chunk_pos.x / (CHUNK_SIZE.x as f32 * TILE_SIZE.x as f32)).floor() as i32;
                 let y = (chunk_pos.y / (CHUNK_SIZE.y as f32 * TILE_SIZE.y as f32)).floor() as i32;
-                chunk_manager.spawned_chunks.remove(&IVec2::new(x, y));
+                if chunk_manager.spawned_chunks.remove(&IVec2::new(x, y)) {
+                    chunk_unloaded.write(ChunkUnloaded { chunk: IVec2::new(x, y) });
+                }
                 commands.entity(entity).despawn();
             }
         }
@@ -242,28 +478,13 @@ fn despawn_outofrange_chunks(
 fn spawn_chunk(
     commands: &mut Commands, 
     asset_server: &AssetServer,
-    map_config: &OverWorldMapConfig,
+    atlas: &GroundAtlas,
+    world: &GeneratedWorld,
+    season: Season,
     chunk_pos: IVec2,
 ) {
-    let texture_handle = asset_server.load("tiles/grounds_tiles.png");
-    let tilemap_entity = commands.spawn_empty().id();
-    let mut tile_storage = TileStorage::empty(CHUNK_SIZE.into());
-
-    let open_simplex: OpenSimplex = OpenSimplex::new(map_config.e_seed as u32);
-    let ridged = RidgedMulti::<OpenSimplex>::new(map_config.e_seed as u32);
-    let fbm_main = Fbm::<OpenSimplex>::new(map_config.e_seed as u32)
-        .set_octaves(map_config.octaves as usize)
-        .set_frequency(map_config.frequency)
-        .set_persistence(map_config.persistance)
-        .set_lacunarity(map_config.lacunarity);
-    let fbm_warp = Fbm::<OpenSimplex>::new(map_config.e_seed as u32)
-        .set_octaves(map_config.octaves as usize)
-        .set_frequency(map_config.frequency)
-        .set_persistence(map_config.persistance)
-        .set_lacunarity(map_config.lacunarity);    
-    let e_noise: Blend<f64, _, _, _, 2> = Blend::new(open_simplex, ridged, fbm_main);
-    let m_noise = OpenSimplex::new(map_config.m_seed as u32);
-    let temp_noise = OpenSimplex::new((map_config.m_seed as u32).wrapping_add(12345)); // different seed for temperature
+    let ground_texture: Handle<Image> = asset_server.load(atlas.image.clone());
+    let objects_texture: Handle<Image> = asset_server.load(OBJECTS_TILESET);
 
     // gather simple stats to help diagnose elevation distribution
     let mut e_min = f64::INFINITY;
@@ -273,23 +494,12 @@ fn spawn_chunk(
 
     for x in 0..CHUNK_SIZE.x {        
         for y in 0..CHUNK_SIZE.y {            
-            let tile_pos = TilePos { x, y };
-            let world_x = chunk_pos.x as f64 * CHUNK_SIZE.x as f64 + x as f64;
-            let world_y = chunk_pos.y as f64 * CHUNK_SIZE.y as f64 + y as f64;
-            let nx: f64 = world_x / OVERWORLD_SIZE_WIDTH as f64 - 0.5;
-            let ny: f64 = world_y / OVERWORLD_SIZE_HEIGHT as f64 - 0.5;
-
-            // Domain-warp for more organic terrain
-            let warp_amp = 0.08; // tweakable
-            let warp = fbm_warp.get([nx * 2.0, ny * 2.0]) * warp_amp;
-            let mut e_value = e_noise.get([nx + warp, ny + warp]);
-
-            // multi-scale detail (kept but normalized)
-            e_value += 0.5 * e_noise.get([2.0 * (nx + warp), 2.0 * (ny + warp)]);
-            e_value += 0.25 * e_noise.get([4.0 * (nx + warp), 4.0 * (ny + warp)]);
-            e_value /= 1.0 + 0.5 + 0.25;
-            e_value = normalize_noise(e_value);
-            e_value = e_value.powf(map_config.pow_factor);
+            let tile = IVec2::new(
+                chunk_pos.x * CHUNK_SIZE.x as i32 + x as i32,
+                chunk_pos.y * CHUNK_SIZE.y as i32 + y as i32,
+            );
+            let Some(climate) = world.climate(tile) else { continue; };
+            let e_value = climate.elevation;
 
             // update stats
             if e_value.is_finite() {
@@ -298,52 +508,32 @@ fn spawn_chunk(
                 e_sum += e_value;
                 e_count += 1;
             }
-
-            // Moisture: base noise, biased by elevation (lowlands wetter) and some temperature influence
-            let mut m_value = normalize_noise(m_noise.get([nx * 1.5, ny * 1.5]));
-            m_value = m_value * 0.7 + (1.0 - e_value) * 0.3; // mountains drier
-
-            // Temperature: latitude gradient + noise + elevation penalty (higher = colder)
-            let lat = 1.0 - (ny + 0.5).abs() * 1.0; // center is warm, poles cold
-            let mut t_value = lat.clamp(0.0, 1.0);
-            t_value += normalize_noise(temp_noise.get([nx * 2.0, ny * 2.0])) * 0.12;
-            t_value -= e_value * 0.5; // elevation cools
-            let t_value = t_value.clamp(0.0, 1.0);
-
-            let texture_index = biome(e_value, m_value, t_value);
-
-            let tile_entity = commands
-                .spawn(TileBundle {
-                    position: tile_pos,
-                    tilemap_id: TilemapId(tilemap_entity),
-                    texture_index: TileTextureIndex(texture_index),
-                    ..Default::default()
-                })
-                .id();
-            commands.entity(tilemap_entity).add_child(tile_entity);
-            tile_storage.set(&tile_pos, tile_entity);
         }
     }
 
-    let transform = Transform::from_translation(Vec3::new(
-        chunk_pos.x as f32 * CHUNK_SIZE.x as f32 * TILE_SIZE.x,
-        chunk_pos.y as f32 * CHUNK_SIZE.y as f32 * TILE_SIZE.y,
-        0.0,
-    ));
-
-    commands.entity(tilemap_entity).insert(TilemapBundle {
-        grid_size: TILE_SIZE.into(),
-        size: CHUNK_SIZE.into(),
-        storage: tile_storage,
-        texture: TilemapTexture::Single(texture_handle),
-        tile_size: TILE_SIZE,
-        transform,
-        render_settings: TilemapRenderSettings {
-            render_chunk_size: RENDER_CHUNK_SIZE,
-            ..Default::default()
-        },
-        ..Default::default()
-    });
+    let layers = chunk_layers(world, season, chunk_pos);
+    let origin = (chunk_pos * CHUNK_SIZE.as_ivec2()).as_vec2() * TILE_SIZE_PX;
+    for layer in MapLayer::ALL {
+        let mut tiles = Vec::new();
+        for x in 0..CHUNK_SIZE.x {
+            for y in 0..CHUNK_SIZE.y {
+                let local = IVec2::new(x as i32, y as i32);
+                let texture_index = match layer {
+                    MapLayer::Ground => layers.get("ground", local),
+                    MapLayer::Overlay => None,
+                    _ => world.layer_tile(layer, chunk_pos * CHUNK_SIZE.as_ivec2() + local),
+                };
+                if let Some(texture_index) = texture_index {
+                    tiles.push((TilePos { x, y }, texture_index));
+                }
+            }
+        }
+        let (texture, tile_size) = match layer {
+            MapLayer::Ground | MapLayer::Overlay => (ground_texture.clone(), TILE_SIZE),
+            MapLayer::Decoration | MapLayer::Structures => (objects_texture.clone(), OBJECTS_TILE_SIZE),
+        };
+        spawn_layer(commands, layer, texture, tile_size, origin, tiles);
+    }
 
     // report simple elevation stats for this chunk
     if e_count > 0 {
@@ -354,6 +544,118 @@ fn spawn_chunk(
     }
 }
 
+///
+/// Spawns one of the tilemaps of a chunk, with its tiles given by their position in the
+/// chunk. Tiles smaller than the ground ones are scaled up to cover a ground tile.
+///
+fn spawn_layer(
+    commands: &mut Commands,
+    layer: MapLayer,
+    texture: Handle<Image>,
+    tile_size: TilemapTileSize,
+    origin: Vec2,
+    tiles: Vec<(TilePos, u32)>,
+) {
+    let tilemap_entity = commands.spawn_empty().id();
+    let mut tile_storage = TileStorage::empty(CHUNK_SIZE.into());
+    let first_tile = (origin / TILE_SIZE_PX).round().as_ivec2();
+    for (tile_pos, texture_index) in tiles {
+        let tile_entity = commands
+            .spawn(TileBundle {
+                position: tile_pos,
+                tilemap_id: TilemapId(tilemap_entity),
+                texture_index: TileTextureIndex(texture_index),
+                ..Default::default()
+            })
+            .id();
+        if layer == MapLayer::Ground && GroundTiles::from(texture_index).animation().is_some() {
+            let tile = first_tile + IVec2::new(tile_pos.x as i32, tile_pos.y as i32);
+            commands.entity(tile_entity).insert(AnimatedTile::at(tile));
+        }
+        commands.entity(tilemap_entity).add_child(tile_entity);
+        tile_storage.set(&tile_pos, tile_entity);
+    }
+
+    let scale = TILE_SIZE.x / tile_size.x;
+    let mut tilemap = commands.entity(tilemap_entity);
+    tilemap.insert((
+        TilemapBundle {
+            grid_size: tile_size.into(),
+            size: CHUNK_SIZE.into(),
+            storage: tile_storage,
+            texture: TilemapTexture::Single(texture),
+            tile_size,
+            transform: Transform::from_translation(origin.extend(layer.z())).with_scale(Vec3::new(scale, scale, 1.0)),
+            render_settings: TilemapRenderSettings {
+                render_chunk_size: RENDER_CHUNK_SIZE,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        layer,
+    ));
+    match layer {
+        MapLayer::Ground => {}
+        // Houses stand in the way like the walls of the Tiled maps.
+        MapLayer::Structures => {
+            tilemap.insert(structure_properties());
+        }
+        MapLayer::Decoration | MapLayer::Overlay => {
+            tilemap.insert(TileOverlay);
+        }
+    }
+}
+
+///
+/// Ground of the tiles of a chunk in the given season.
+///
+fn chunk_layers(world: &GeneratedWorld, season: Season, chunk_pos: IVec2) -> TileLayers {
+    let mut layers = TileLayers::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32, GROUND_TILESET_NAME);
+    for x in 0..CHUNK_SIZE.x as i32 {
+        for y in 0..CHUNK_SIZE.y as i32 {
+            let tile = chunk_pos * CHUNK_SIZE.as_ivec2() + IVec2::new(x, y);
+            let (Some(climate), Some(ground)) = (world.climate(tile), world.ground(tile)) else { continue; };
+            let ground = forest_floor(seasonal_ground(ground, season, climate.temperature));
+            layers.set("ground", IVec2::new(x, y), Some(ground as u32));
+        }
+    }
+    layers
+}
+
+///
+/// Dresses the spawned chunks for the new season.
+///
+fn apply_season(
+    mut season_changed: MessageReader<SeasonChanged>,
+    chunk_manager: Res<ChunkManager>,
+    world: Option<Res<GeneratedWorld>>,
+    mut tile_grid: TileGrid,
+    mut ground_changed: MessageWriter<GroundChanged>,
+) {
+    let Some(season) = season_changed.read().last().map(|changed| changed.season) else { return; };
+    let Some(world) = world else { return; };
+    for chunk_pos in chunk_manager.spawned_chunks.iter() {
+        let layers = chunk_layers(&world, season, *chunk_pos);
+        for x in 0..CHUNK_SIZE.x as i32 {
+            for y in 0..CHUNK_SIZE.y as i32 {
+                let Some(index) = layers.get("ground", IVec2::new(x, y)) else { continue; };
+                tile_grid.set_ground(*chunk_pos * CHUNK_SIZE.as_ivec2() + IVec2::new(x, y), GroundTiles::from(index));
+            }
+        }
+        ground_changed.write(GroundChanged { chunk: *chunk_pos });
+    }
+}
+
+///
+/// Noise coordinates of a tile, the world spans `-0.5..0.5` on both axes.
+///
+fn normalized(tile: IVec2) -> (f64, f64) {
+    (
+        tile.x as f64 / OVERWORLD_SIZE_WIDTH as f64 - 0.5,
+        tile.y as f64 / OVERWORLD_SIZE_HEIGHT as f64 - 0.5,
+    )
+}
+
 fn normalize_noise(v: f64) -> f64 {
     ((v + 1.0) / 2.0).clamp(0.0, 1.0)
 }
@@ -388,7 +690,9 @@ fn biome(e: f64, m: f64, t: f64) -> u32 {
 
     // Biomes based on moisture & temperature (unchanged logic, smoothed where helpful)
     if t < 0.35 && m > 0.45 { return GroundTiles::BrightPineForest as u32; }
-    if m < 0.15 && t > 0.6 { return GroundTiles::LightSandyMountain as u32; }
+    // dry land, mostly behind the mountains in the rain shadow
+    if m < 0.15 && t > 0.6 { return GroundTiles::LightSandDesert as u32; }
+    if m < 0.2 && t > 0.4 { return GroundTiles::LightTemperateDesert as u32; }
     if m > 0.7 { return GroundTiles::BrightLushForest as u32; }
     if m > 0.45 { return GroundTiles::BrightDeciduousForest as u32; }
     if e > 0.5 { return GroundTiles::MediumGrass as u32; }
@@ -396,67 +700,40 @@ fn biome(e: f64, m: f64, t: f64) -> u32 {
 }
 
 ///
-/// This method is used to check for event. The player system sends a MoveEvent and this system
-/// reads it. It then determines whether the destination tile is walkable or not. It then sends
-/// a MoveLegal event.
-/// 
-fn move_event_listener(
-    mut move_events: MessageReader<MoveEvent>,
-    tilemap_q: Query<(
-        &TilemapSize,
-        &TilemapGridSize,
-        &TilemapType,
-        &TileStorage,
-        &Transform,
-    )>,
-    tile_query: Query<&mut TileTextureIndex>,
-    mut move_legal: MessageWriter<MoveLegal>,
-) {
-    for move_event in move_events.read() {
-        for (map_size, grid_size, map_type, tile_storage, map_transform) in tilemap_q.iter() {
-            if move_event.destination.is_none() {
-                return;
-            }
-            // Make sure that the destination is correct relative to the map due to any map transformation.
-            let dest_in_map_pos: Vec2 = {
-                let destination_pos = Vec4::from((move_event.destination.unwrap(), 1.0));
-                let dest_in_map_pos = map_transform.to_matrix().inverse() * destination_pos;
-                dest_in_map_pos.xy()
-            };
-            // Once we have a world position we can transform it into a possible tile position.
-            if let Some(tile_pos) =
-                TilePos::from_world_pos(&dest_in_map_pos, map_size, grid_size, &TILE_SIZE, map_type, &TilemapAnchor::None)
-            {
-                if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-                    {
-                        if let Ok(tile_texture) = tile_query.get(tile_entity) {
-                            let walkable = tile_walkable(tile_texture.0);
-                            if walkable {
-                                move_legal.write(MoveLegal { 
-                                    legal_move: true,
-                                    destination: move_event.destination, 
-                                });
-                            } else {
-                                // move_legal.write(MoveLegal {
-                                //     legal_move: false,
-                                //     destination: None,
-                                //  });
-                                move_legal.write(MoveLegal {
-                                    legal_move: true,
-                                    destination: move_event.destination,
-                                });
-                            }
-                        }
-                    }
-                }
-
-                // Is the player about to move to the edge?
-                // if tile_pos.x == 0 || tile_pos.x == OVERWORLD_SIZE_WIDTH - 1 || tile_pos.y == 0 || tile_pos.y == OVERWORLD_SIZE_HEIGHT - 1 {
-                //     println!("Edge detected...");
-                // }
-            }
+/// Ground of a tile in the given season. Where the season is colder than the freezing point,
+/// the land turns to its frozen variant and the shallow water freezes over, which the player
+/// can then walk on. Crop fields go from green in spring to yellow in autumn.
+///
+fn seasonal_ground(ground: GroundTiles, season: Season, temperature: f64) -> GroundTiles {
+    use GroundTiles::*;
+    if temperature < season.freezing_point() {
+        match ground {
+            LightShallowWater | MediumShallowWater | LightWater1 | LightGrass | LightGrassyDirt => return LightFrozenField,
+            MediumGrass | MediumGrassyDirt | LightCropField | MediumCropField | DarkCropField => return MediumFrozenField,
+            DarkGrass | DarkGrassyDirt => return DarkFrozenField,
+            BrightPineForest | MediumPineForest => return MediumFrozenPineForest,
+            DarkPineForest | BrightDeciduousForest | MediumDeciduousForest | DarkDeciduousForest => return DarkFrozenPineForest,
+            LightGrassyHills => return LightFrozenHills,
+            MediumGrassyHills => return MediumFrozenHills,
+            DarkGrassyHills => return DarkFrozenHills,
+            LightRockyDirt => return LightFrozenRockyDirt,
+            MediumRockyDirt => return MediumFrozenRockyDirt,
+            DarkRockyDirt => return DarkFrozenRockyDirt,
+            _ => {}
         }
     }
+    match (ground, season) {
+        (LightCropField, Season::Spring) => LightGreenyCropField,
+        (MediumCropField, Season::Spring) => MediumGreenyCropField,
+        (DarkCropField, Season::Spring) => DarkGreenyCropField,
+        (LightCropField, Season::Summer) => LightLushCropField,
+        (MediumCropField, Season::Summer) => MediumLushCropField,
+        (DarkCropField, Season::Summer) => DarkLushCropField,
+        (LightCropField, Season::Autumn) => LightYellowCropField,
+        (MediumCropField, Season::Autumn) => MediumYellowCropField,
+        (DarkCropField, Season::Autumn) => DarkYellowCropField,
+        _ => ground,
+    }
 }
 
 // pub fn detect_player_edge(
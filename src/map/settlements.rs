@@ -0,0 +1,441 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::map::overworld_map::{MapLayer, PoiKind, PointOfInterest};
+use crate::map::tile_grid::TileProperties;
+use crate::map::world_gen::GeneratedWorld;
+use crate::tile_type::GroundTiles;
+
+// Distance between the candidate sites, and from the sites to the edge of the world.
+const CANDIDATE_STEP: i32 = 4;
+const CANDIDATE_MARGIN: i32 = 4;
+// Water further than this does not make a site more attractive.
+const WATER_RADIUS: i32 = 8;
+// Elevation difference over two tiles from which a site counts as steep.
+const STEEP_SLOPE: f64 = 0.05;
+// Costs of the road pathfinding, in tenths of a flat grass tile.
+const ROAD_COST_FLAT: u32 = 10;
+const ROAD_COST_FOREST: u32 = 25;
+const ROAD_COST_EXISTING: u32 = 3;
+// Roads ford rivers where they have to.
+const ROAD_COST_RIVER: u32 = 60;
+const ROAD_COST_PER_SLOPE: f64 = 400.0;
+const ROAD_GROUND: GroundTiles = GroundTiles::MediumCobbledDirt;
+const TOWN_GROUND: GroundTiles = GroundTiles::LightGreyCobble;
+const VILLAGE_GROUND: GroundTiles = GroundTiles::LightCobbledDirt;
+// Houses of the overworld objects tileset, and the share of the edge of a square they line.
+const TOWN_HOUSES: [u32; 6] = [16, 17, 48, 49, 80, 81];
+const VILLAGE_HOUSES: [u32; 2] = [18, 50];
+const HOUSE_SHARE: f64 = 0.6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SettlementParams {
+    pub towns: usize,
+    pub villages: usize,
+    pub ruins: usize,
+    /// Smallest distance between two towns, in tiles.
+    pub town_spacing: i32,
+    /// Smallest distance between any two places.
+    pub min_spacing: i32,
+    /// Radius of the paved square of a town, villages get half of it.
+    pub town_radius: i32,
+}
+
+impl Default for SettlementParams {
+    fn default() -> Self {
+        SettlementParams {
+            towns: 3,
+            villages: 8,
+            ruins: 6,
+            town_spacing: 60,
+            min_spacing: 20,
+            town_radius: 3,
+        }
+    }
+}
+
+///
+/// Places the towns, villages and ruins of a world and the roads between them. Candidate
+/// sites are scored on flatness, nearby water and a temperate climate; towns take the best
+/// ones, then villages, each keeping its distance from the others. Ruins go to forests away
+/// from everything. Towns and villages are then joined by the cheapest roads over the terrain,
+/// following a minimum spanning tree. Everything only depends on the world seed and its
+/// climate, so the same world always gets the same places.
+///
+/// The places are added to the points of interest of the world, the roads and town squares
+/// are painted over its biomes and houses line the squares on the structure layer.
+///
+pub fn place_settlements(world: &mut GeneratedWorld, params: &SettlementParams) {
+    let grid = &*world;
+    let mut rng = StdRng::seed_from_u64(grid.seed ^ 0x5E77_1E00_0000_0001);
+
+    let mut candidates: Vec<(f64, IVec2)> = Vec::new();
+    for y in (CANDIDATE_MARGIN..grid.height - CANDIDATE_MARGIN).step_by(CANDIDATE_STEP as usize) {
+        for x in (CANDIDATE_MARGIN..grid.width - CANDIDATE_MARGIN).step_by(CANDIDATE_STEP as usize) {
+            let tile = IVec2::new(x, y);
+            // A little noise so that equal sites are not always settled in the same order.
+            let jitter = rng.random_range(0.0..0.05);
+            if let Some(score) = site_score(grid, tile) {
+                candidates.push((score + jitter, tile));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    // Ground painted over the generated terrain (roads, town squares).
+    let mut paint: HashMap<IVec2, GroundTiles> = HashMap::new();
+    let far_from = |placed: &[PointOfInterest], tile: IVec2, kind: Option<PoiKind>, spacing: i32| {
+        placed
+            .iter()
+            .filter(|poi| kind.is_none_or(|kind| poi.kind == kind))
+            .all(|poi| poi.tile.distance_squared(tile) >= spacing * spacing)
+    };
+
+    let mut placed: Vec<PointOfInterest> = Vec::new();
+    for (kind, count) in [(PoiKind::Town, params.towns), (PoiKind::Village, params.villages)] {
+        let mut remaining = count;
+        for (_, tile) in &candidates {
+            if remaining == 0 {
+                break;
+            }
+            if !far_from(&placed, *tile, None, params.min_spacing)
+                || (kind == PoiKind::Town && !far_from(&placed, *tile, Some(PoiKind::Town), params.town_spacing))
+            {
+                continue;
+            }
+            placed.push(PointOfInterest { kind, tile: *tile, seed: rng.random() });
+            remaining -= 1;
+        }
+    }
+
+    let mut ruin_sites: Vec<(f64, IVec2)> = Vec::new();
+    for y in (CANDIDATE_MARGIN..grid.height - CANDIDATE_MARGIN).step_by(CANDIDATE_STEP as usize) {
+        for x in (CANDIDATE_MARGIN..grid.width - CANDIDATE_MARGIN).step_by(CANDIDATE_STEP as usize) {
+            let tile = IVec2::new(x, y);
+            let roll: f64 = rng.random();
+            if grid.ground(tile).is_some_and(is_forest) {
+                ruin_sites.push((roll, tile));
+            }
+        }
+    }
+    ruin_sites.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut remaining = params.ruins;
+    for (_, tile) in &ruin_sites {
+        if remaining == 0 {
+            break;
+        }
+        if far_from(&placed, *tile, None, params.min_spacing) {
+            placed.push(PointOfInterest { kind: PoiKind::Ruins, tile: *tile, seed: rng.random() });
+            remaining -= 1;
+        }
+    }
+
+    // Paved squares first, so that roads run through them for free.
+    for poi in &placed {
+        let (radius, ground) = match poi.kind {
+            PoiKind::Town => (params.town_radius, TOWN_GROUND),
+            PoiKind::Village => ((params.town_radius / 2).max(1), VILLAGE_GROUND),
+            _ => continue,
+        };
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let tile = poi.tile + IVec2::new(x, y);
+                if IVec2::new(x, y).length_squared() <= radius * radius && grid.ground(tile).is_some_and(|ground| ground.is_walkable()) {
+                    paint.insert(tile, ground);
+                }
+            }
+        }
+    }
+
+    let inhabited: Vec<IVec2> = placed
+        .iter()
+        .filter(|poi| matches!(poi.kind, PoiKind::Town | PoiKind::Village))
+        .map(|poi| poi.tile)
+        .collect();
+    let mut roads: HashSet<IVec2> = HashSet::new();
+    for (from, to) in spanning_tree(&inhabited) {
+        let Some(road) = find_road(grid, &paint, inhabited[from], inhabited[to]) else {
+            debug!("No road between {:?} and {:?}.", inhabited[from], inhabited[to]);
+            continue;
+        };
+        for tile in road {
+            paint.entry(tile).or_insert(ROAD_GROUND);
+            roads.insert(tile);
+        }
+    }
+
+    info!(
+        "Placed {} settlements and {} road tiles in the overworld.",
+        placed.len(),
+        paint.values().filter(|ground| **ground == ROAD_GROUND).count()
+    );
+    for (tile, ground) in paint {
+        if let Some(index) = world.index(tile) {
+            world.biomes[index] = ground;
+        }
+    }
+
+    // Houses stand on the edge of the squares, where no road leaves them.
+    for poi in &placed {
+        let (radius, ground, houses) = match poi.kind {
+            PoiKind::Town => (params.town_radius, TOWN_GROUND, &TOWN_HOUSES[..]),
+            PoiKind::Village => ((params.town_radius / 2).max(1), VILLAGE_GROUND, &VILLAGE_HOUSES[..]),
+            _ => continue,
+        };
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let offset = IVec2::new(x, y);
+                let tile = poi.tile + offset;
+                let edge = offset.length_squared() <= radius * radius && offset.length_squared() > (radius - 1) * (radius - 1);
+                if edge && world.ground(tile) == Some(ground) && !roads.contains(&tile) && rng.random_bool(HOUSE_SHARE) {
+                    world.set_layer_tile(MapLayer::Structures, tile, Some(houses[rng.random_range(0..houses.len())]));
+                }
+            }
+        }
+    }
+    world.points_of_interest.extend(placed);
+}
+
+///
+/// Collision of the structures layer of the overworld: the houses block and stop sight.
+///
+pub fn structure_properties() -> TileProperties {
+    let houses: HashSet<u32> = TOWN_HOUSES.iter().chain(VILLAGE_HOUSES.iter()).copied().collect();
+    TileProperties {
+        blocked: houses.clone(),
+        opaque: houses,
+        ..default()
+    }
+}
+
+fn is_forest(ground: GroundTiles) -> bool {
+    matches!(ground, GroundTiles::BrightPineForest | GroundTiles::BrightLushForest | GroundTiles::BrightDeciduousForest)
+}
+
+///
+/// How good a tile is for a settlement, `None` when nobody would build there.
+///
+fn site_score(grid: &GeneratedWorld, tile: IVec2) -> Option<f64> {
+    let climate = grid.climate(tile)?;
+    let ground = grid.ground(tile)?;
+    if !ground.is_walkable() || is_forest(ground) {
+        return None;
+    }
+
+    let slope = [IVec2::new(2, 0), IVec2::new(-2, 0), IVec2::new(0, 2), IVec2::new(0, -2)]
+        .iter()
+        .filter_map(|offset| grid.climate(tile + *offset))
+        .map(|other| (other.elevation - climate.elevation).abs())
+        .fold(0.0, f64::max);
+    let flatness = 1.0 - (slope / STEEP_SLOPE).min(1.0);
+
+    let mut water_distance = None;
+    for y in -WATER_RADIUS..=WATER_RADIUS {
+        for x in -WATER_RADIUS..=WATER_RADIUS {
+            let offset = IVec2::new(x, y);
+            if grid.ground(tile + offset).is_some_and(is_water) {
+                let distance = (offset.length_squared() as f64).sqrt();
+                water_distance = Some(water_distance.map_or(distance, |best: f64| best.min(distance)));
+            }
+        }
+    }
+    let near_water = water_distance.map_or(0.0, |distance| 1.0 - (distance / WATER_RADIUS as f64).min(1.0));
+
+    let temperate = 1.0 - ((climate.temperature - 0.55).abs() * 2.0).min(1.0);
+    let moderate = 1.0 - ((climate.moisture - 0.5).abs() * 2.0).min(1.0);
+    Some(0.35 * flatness + 0.35 * near_water + 0.2 * temperate + 0.1 * moderate)
+}
+
+fn is_water(ground: GroundTiles) -> bool {
+    matches!(
+        ground,
+        GroundTiles::LightShallowWater | GroundTiles::MediumShallowWater | GroundTiles::DarkShallowWater | GroundTiles::LightWater1
+    )
+}
+
+///
+/// Edges of the minimum spanning tree of the sites (Prim), as pairs of indices.
+///
+fn spanning_tree(sites: &[IVec2]) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    if sites.is_empty() {
+        return edges;
+    }
+    let mut in_tree = vec![false; sites.len()];
+    // Closest site of the tree for every site outside of it.
+    let mut closest: Vec<(i32, usize)> = sites.iter().map(|site| (site.distance_squared(sites[0]), 0)).collect();
+    in_tree[0] = true;
+    for _ in 1..sites.len() {
+        let Some(next) = (0..sites.len()).filter(|index| !in_tree[*index]).min_by_key(|index| closest[*index].0) else { break; };
+        in_tree[next] = true;
+        edges.push((closest[next].1, next));
+        for index in 0..sites.len() {
+            let distance = sites[index].distance_squared(sites[next]);
+            if !in_tree[index] && distance < closest[index].0 {
+                closest[index] = (distance, next);
+            }
+        }
+    }
+    edges
+}
+
+///
+/// Cheapest road between two sites (A*, four directions). Roads avoid slopes and forests, go
+/// around water and mountains, ford rivers when there is no way around and follow the roads
+/// already built.
+///
+fn find_road(grid: &GeneratedWorld, paint: &HashMap<IVec2, GroundTiles>, from: IVec2, to: IVec2) -> Option<Vec<IVec2>> {
+    let heuristic = |tile: IVec2| {
+        let delta = (tile - to).abs();
+        (delta.x + delta.y) as u32 * ROAD_COST_EXISTING
+    };
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut cost: HashMap<IVec2, u32> = HashMap::new();
+    open.push(Reverse((heuristic(from), 0, from.x, from.y)));
+    cost.insert(from, 0);
+
+    while let Some(Reverse((_, current_cost, x, y))) = open.pop() {
+        let current = IVec2::new(x, y);
+        if current == to {
+            let mut road = vec![current];
+            let mut tile = current;
+            while let Some(previous) = came_from.get(&tile) {
+                tile = *previous;
+                road.push(tile);
+            }
+            road.reverse();
+            return Some(road);
+        }
+        if current_cost > cost.get(&current).copied().unwrap_or(u32::MAX) {
+            continue;
+        }
+        let Some(here) = grid.climate(current) else { continue; };
+
+        for neighbour in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|offset| current + offset) {
+            let (Some(climate), Some(ground)) = (grid.climate(neighbour), grid.ground(neighbour)) else { continue; };
+            let river = grid.is_river(neighbour);
+            if !ground.is_walkable() && !river {
+                continue;
+            }
+            let step = if paint.contains_key(&neighbour) {
+                ROAD_COST_EXISTING
+            } else if river {
+                ROAD_COST_RIVER
+            } else {
+                let base = if is_forest(ground) { ROAD_COST_FOREST } else { ROAD_COST_FLAT };
+                base + ((climate.elevation - here.elevation).abs() * ROAD_COST_PER_SLOPE) as u32
+            };
+            let neighbour_cost = current_cost + step;
+            if neighbour_cost < cost.get(&neighbour).copied().unwrap_or(u32::MAX) {
+                cost.insert(neighbour, neighbour_cost);
+                came_from.insert(neighbour, current);
+                open.push(Reverse((neighbour_cost + heuristic(neighbour), neighbour_cost, neighbour.x, neighbour.y)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: SettlementParams = SettlementParams {
+        towns: 2,
+        villages: 4,
+        ruins: 2,
+        town_spacing: 24,
+        min_spacing: 10,
+        town_radius: 3,
+    };
+
+    ///
+    /// Rolling temperate grassland with a sea along the west edge and a forest in the north
+    /// east corner.
+    ///
+    fn world() -> GeneratedWorld {
+        let mut world = GeneratedWorld::new(64, 48, 9);
+        for index in 0..(world.width * world.height) as usize {
+            let tile = world.tile(index);
+            world.elevation[index] = 0.4 + 0.01 * ((tile.x * 7 + tile.y * 3) % 5) as f64;
+            world.moisture[index] = 0.5;
+            world.temperature[index] = 0.55;
+            let ground = if tile.x < 14 {
+                GroundTiles::LightShallowWater
+            } else if tile.x >= 44 && tile.y >= 28 {
+                GroundTiles::BrightPineForest
+            } else {
+                GroundTiles::LightGrass
+            };
+            world.biomes.push(ground);
+        }
+        world
+    }
+
+    fn settle() -> (GeneratedWorld, Vec<GroundTiles>) {
+        let mut world = world();
+        let terrain = world.biomes.clone();
+        place_settlements(&mut world, &PARAMS);
+        (world, terrain)
+    }
+
+    #[test]
+    fn places_keep_their_distance_on_land() {
+        let (world, terrain) = settle();
+        let places = &world.points_of_interest;
+        let count = |kind| places.iter().filter(|poi| poi.kind == kind).count();
+        assert_eq!((count(PoiKind::Town), count(PoiKind::Village), count(PoiKind::Ruins)), (2, 4, 2));
+
+        for (index, poi) in places.iter().enumerate() {
+            assert!(terrain[world.index(poi.tile).unwrap()].is_walkable(), "{:?} in the sea", poi);
+            for other in &places[index + 1..] {
+                let spacing = match (poi.kind, other.kind) {
+                    (PoiKind::Town, PoiKind::Town) => PARAMS.town_spacing,
+                    _ => PARAMS.min_spacing,
+                };
+                assert!(poi.tile.distance_squared(other.tile) >= spacing * spacing, "{:?} next to {:?}", poi, other);
+            }
+        }
+        for ruins in places.iter().filter(|poi| poi.kind == PoiKind::Ruins) {
+            assert!(is_forest(terrain[world.index(ruins.tile).unwrap()]));
+        }
+    }
+
+    #[test]
+    fn roads_join_every_settlement() {
+        let (world, _) = settle();
+        let paved = |tile: IVec2| world.ground(tile).is_some_and(|ground| [ROAD_GROUND, TOWN_GROUND, VILLAGE_GROUND].contains(&ground));
+        let inhabited: Vec<IVec2> = world
+            .points_of_interest
+            .iter()
+            .filter(|poi| matches!(poi.kind, PoiKind::Town | PoiKind::Village))
+            .map(|poi| poi.tile)
+            .collect();
+
+        let mut reached = HashSet::from([inhabited[0]]);
+        let mut pending = vec![inhabited[0]];
+        while let Some(tile) = pending.pop() {
+            for next in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].map(|offset| tile + offset) {
+                if paved(next) && reached.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+        for settlement in &inhabited {
+            assert!(reached.contains(settlement), "no road to {}", settlement);
+        }
+    }
+
+    #[test]
+    fn the_same_world_gets_the_same_places() {
+        let (first, _) = settle();
+        let (second, _) = settle();
+        let tiles = |world: &GeneratedWorld| world.points_of_interest.iter().map(|poi| (poi.kind, poi.tile)).collect::<Vec<_>>();
+        assert_eq!(tiles(&first), tiles(&second));
+        assert_eq!(first.biomes, second.biomes);
+    }
+}
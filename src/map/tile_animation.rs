@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::map::tile_hash;
+use crate::tile_type::GroundTiles;
+
+///
+/// Speed of the water and lava animations of the overworld.
+///
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource)]
+pub struct TileAnimationConfig {
+    /// Frames shown per second, 0 stops the animations.
+    pub frames_per_second: f32,
+}
+
+impl Default for TileAnimationConfig {
+    fn default() -> Self {
+        TileAnimationConfig { frames_per_second: 2.0 }
+    }
+}
+
+///
+/// Ground tile showing an animated ground, the only tiles the animation goes through. `phase`
+/// comes from the position of the tile, so that the surface of a lake does not pulse in sync.
+///
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AnimatedTile {
+    phase: u32,
+}
+
+impl AnimatedTile {
+    pub fn at(tile: IVec2) -> Self {
+        AnimatedTile { phase: tile_hash(tile) }
+    }
+}
+
+///
+/// Cycles the frames of the animated ground tiles (see [`GroundTiles::animation`]) of the
+/// spawned chunks.
+///
+pub struct TileAnimationPlugin;
+
+impl Plugin for TileAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TileAnimationConfig>()
+            .register_type::<TileAnimationConfig>()
+            .add_systems(Update, animate_ground_tiles);
+    }
+}
+
+fn animate_ground_tiles(
+    time: Res<Time>,
+    config: Res<TileAnimationConfig>,
+    mut last_frame: Local<Option<u64>>,
+    mut tiles: Query<(&AnimatedTile, &mut TileTextureIndex)>,
+) {
+    if config.frames_per_second <= 0.0 {
+        return;
+    }
+    // The tiles only change when the frame does.
+    let frame = (time.elapsed_secs_f64() * config.frames_per_second as f64) as u64;
+    if *last_frame == Some(frame) {
+        return;
+    }
+    *last_frame = Some(frame);
+
+    for (animated, mut texture) in tiles.iter_mut() {
+        let Some((first, frames)) = GroundTiles::from(texture.0).animation() else { continue; };
+        let index = first as u32 + ((frame + animated.phase as u64) % frames as u64) as u32;
+        if texture.0 != index {
+            texture.0 = index;
+        }
+    }
+}
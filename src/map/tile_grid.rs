@@ -0,0 +1,471 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet}};
+
+use bevy::{ecs::system::SystemParam, math::Vec4Swizzles, prelude::*, transform::TransformSystems};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::map::tile_animation::AnimatedTile;
+use crate::map::{tile_coords_to_world, tile_to_chunk_coords, world_to_tile_coords};
+use crate::tile_type::GroundTiles;
+use crate::weather::Weather;
+
+///
+/// Collision of a tilemap whose texture indices are not [`GroundTiles`] (dungeons, Tiled maps).
+/// Tilemaps without it are read as overworld ground.
+///
+#[derive(Component, Debug, Default, Clone)]
+pub struct TileProperties {
+    /// Texture indices actors cannot walk on.
+    pub blocked: HashSet<u32>,
+    /// Texture indices that stop sight and projectiles.
+    pub opaque: HashSet<u32>,
+    /// Cost of walking onto a tile, for the texture indices that do not cost 1.
+    pub costs: HashMap<u32, u32>,
+}
+
+///
+/// Tilemap drawn over the ground for looks only (terrain transitions, trees...). [`TileGrid`] ignores
+/// it.
+///
+#[derive(Component, Debug, Default)]
+pub struct TileOverlay;
+
+///
+/// Tilemaps read by the [`TileGrid`], by the chunks they cover. Kept up to date once the
+/// transforms of the spawned or moved tilemaps are propagated.
+///
+#[derive(Resource, Debug, Default)]
+pub struct TilemapIndex {
+    chunks: HashMap<IVec2, Vec<Entity>>,
+    covered: HashMap<Entity, Vec<IVec2>>,
+}
+
+impl TilemapIndex {
+    fn insert(&mut self, tilemap: Entity, chunks: Vec<IVec2>) {
+        self.remove(tilemap);
+        for chunk in chunks.iter() {
+            self.chunks.entry(*chunk).or_default().push(tilemap);
+        }
+        self.covered.insert(tilemap, chunks);
+    }
+
+    fn remove(&mut self, tilemap: Entity) {
+        let Some(chunks) = self.covered.remove(&tilemap) else { return; };
+        for chunk in chunks {
+            if let Some(tilemaps) = self.chunks.get_mut(&chunk) {
+                tilemaps.retain(|entity| *entity != tilemap);
+                if tilemaps.is_empty() {
+                    self.chunks.remove(&chunk);
+                }
+            }
+        }
+    }
+
+    fn tilemaps(&self, chunk: IVec2) -> &[Entity] {
+        self.chunks.get(&chunk).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub struct TileGridPlugin;
+
+impl Plugin for TileGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TilemapIndex>()
+            .add_systems(PostUpdate, index_tilemaps.after(TransformSystems::Propagate));
+    }
+}
+
+type IndexedTilemap = (
+    Entity,
+    &'static TilemapSize,
+    &'static TilemapGridSize,
+    &'static TilemapTileSize,
+    &'static TilemapType,
+    &'static GlobalTransform,
+    Option<&'static TilemapAnchor>,
+);
+
+///
+/// Indexes the tilemaps spawned or moved this frame by the chunks their corners span, and
+/// forgets the despawned ones.
+///
+fn index_tilemaps(
+    mut index: ResMut<TilemapIndex>,
+    tilemaps: Query<IndexedTilemap, (With<TileStorage>, Without<TileOverlay>, Changed<GlobalTransform>)>,
+    mut removed: RemovedComponents<TileStorage>,
+) {
+    for tilemap in removed.read() {
+        index.remove(tilemap);
+    }
+    for (tilemap, map_size, grid_size, tile_size, map_type, map_transform, anchor) in tilemaps.iter() {
+        let anchor = anchor.cloned().unwrap_or(TilemapAnchor::None);
+        let (last_x, last_y) = (map_size.x.saturating_sub(1), map_size.y.saturating_sub(1));
+        let corners = [(0, 0), (last_x, 0), (0, last_y), (last_x, last_y)].map(|(x, y)| {
+            let center = TilePos { x, y }.center_in_world(map_size, grid_size, tile_size, map_type, &anchor);
+            tile_to_chunk_coords(world_to_tile_coords(map_transform.transform_point(center.extend(0.0))))
+        });
+        let min = corners.into_iter().fold(corners[0], IVec2::min);
+        let max = corners.into_iter().fold(corners[0], IVec2::max);
+        let chunks = (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |y| IVec2::new(x, y))).collect();
+        index.insert(tilemap, chunks);
+    }
+}
+
+///
+/// Access to the spawned tilemaps by global tile coordinates. This is the one place gameplay
+/// systems should go through to know what lies on a tile (walkability, sight, terrain changes).
+/// Where tilemaps are stacked (layers of a Tiled map), a tile is blocked or opaque as soon as
+/// one of its layers is.
+///
+#[derive(SystemParam)]
+pub struct TileGrid<'w, 's> {
+    tilemaps: Query<'w, 's, (
+        &'static TilemapSize,
+        &'static TilemapGridSize,
+        &'static TilemapTileSize,
+        &'static TilemapType,
+        &'static TileStorage,
+        &'static GlobalTransform,
+        Option<&'static TilemapAnchor>,
+        Option<&'static TileProperties>,
+    ), Without<TileOverlay>>,
+    tiles: Query<'w, 's, &'static mut TileTextureIndex>,
+    index: Res<'w, TilemapIndex>,
+    weather: Option<Res<'w, Weather>>,
+    commands: Commands<'w, 's>,
+}
+
+impl TileGrid<'_, '_> {
+    ///
+    /// Entity of the tile at the given global tile coordinates, if a spawned tilemap contains it.
+    ///
+    pub fn tile_entity(&self, tile: IVec2) -> Option<Entity> {
+        self.locate(tile).next().map(|(tile_entity, _)| tile_entity)
+    }
+
+    ///
+    /// Tiles of the spawned tilemaps covering the chunk of the given coordinates. Tilemaps are
+    /// children of the Tiled maps, so their global transform is used.
+    ///
+    fn locate(&self, tile: IVec2) -> impl Iterator<Item = (Entity, Option<&TileProperties>)> {
+        let world_pos = Vec4::from((tile_coords_to_world(tile, 0.0), 1.0));
+        self.index.tilemaps(tile_to_chunk_coords(tile)).iter().filter_map(move |tilemap| {
+            let (map_size, grid_size, tile_size, map_type, tile_storage, map_transform, anchor, properties) =
+                self.tilemaps.get(*tilemap).ok()?;
+            // Make sure that the position is correct relative to the map due to any map transformation.
+            let pos_in_map = (map_transform.to_matrix().inverse() * world_pos).xy();
+            let anchor = anchor.cloned().unwrap_or(TilemapAnchor::None);
+            let tile_pos = TilePos::from_world_pos(&pos_in_map, map_size, grid_size, tile_size, map_type, &anchor)?;
+            tile_storage.get(&tile_pos).map(|tile_entity| (tile_entity, properties))
+        })
+    }
+
+    fn textures_at(&self, tile: IVec2) -> impl Iterator<Item = (u32, Option<&TileProperties>)> {
+        self.locate(tile)
+            .filter_map(|(tile_entity, properties)| self.tiles.get(tile_entity).ok().map(|texture| (texture.0, properties)))
+    }
+
+    ///
+    /// Overworld ground of the tile. `None` when the tile is not spawned or belongs to a
+    /// tilemap with its own [`TileProperties`].
+    ///
+    pub fn ground_at(&self, tile: IVec2) -> Option<GroundTiles> {
+        self.textures_at(tile)
+            .find(|(_, properties)| properties.is_none())
+            .map(|(index, _)| GroundTiles::from(index))
+    }
+
+    ///
+    /// Whether an actor can stand on the tile. `None` when the tile is not spawned.
+    ///
+    pub fn walkable(&self, tile: IVec2) -> Option<bool> {
+        let mut textures = self.textures_at(tile).peekable();
+        textures.peek()?;
+        Some(textures.all(|texture| match texture {
+            (index, None) => GroundTiles::from(index).is_walkable(),
+            (index, Some(properties)) => !properties.blocked.contains(&index),
+        }))
+    }
+
+    ///
+    /// Whether the tile stops sight and projectiles. Tiles that are not spawned do not block.
+    ///
+    pub fn blocks_sight(&self, tile: IVec2) -> bool {
+        self.textures_at(tile).any(|texture| match texture {
+            (index, None) => GroundTiles::from(index).blocks_sight(),
+            (index, Some(properties)) => properties.opaque.contains(&index),
+        })
+    }
+
+    ///
+    /// Cost of walking onto the tile, the highest of its layers. Overworld ground costs 1, more
+    /// in snow and sandstorms.
+    ///
+    pub fn move_cost(&self, tile: IVec2) -> u32 {
+        self.textures_at(tile)
+            .map(|texture| match texture {
+                (_, None) => self.weather.as_deref().map_or(1, Weather::move_cost),
+                (index, Some(properties)) => properties.costs.get(&index).copied().unwrap_or(1),
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    ///
+    /// Replaces the ground of a spawned tile. Returns false when the tile is not spawned.
+    ///
+    pub fn set_ground(&mut self, tile: IVec2, ground: GroundTiles) -> bool {
+        let Some((tile_entity, _)) = self.locate(tile).find(|(_, properties)| properties.is_none()) else { return false; };
+        let Ok(mut texture) = self.tiles.get_mut(tile_entity) else { return false; };
+        texture.0 = ground as u32;
+        match ground.animation() {
+            Some(_) => self.commands.entity(tile_entity).insert(AnimatedTile::at(tile)),
+            None => self.commands.entity(tile_entity).remove::<AnimatedTile>(),
+        };
+        true
+    }
+
+    ///
+    /// True when no tile strictly between `from` and `to` blocks sight.
+    ///
+    pub fn line_of_sight(&self, from: IVec2, to: IVec2) -> bool {
+        let line = tile_line(from, to);
+        line.iter()
+            .skip(1)
+            .take(line.len().saturating_sub(2))
+            .all(|tile| !self.blocks_sight(*tile))
+    }
+
+    ///
+    /// Whether an actor can step from `from` to the neighbouring tile `to`. Diagonal steps are
+    /// refused when both tiles sharing the corner are blocked, like for the player.
+    ///
+    pub fn can_step(&self, from: IVec2, to: IVec2) -> bool {
+        if self.walkable(to) != Some(true) {
+            return false;
+        }
+        if from.x != to.x && from.y != to.y {
+            let blocked_x = self.walkable(IVec2::new(to.x, from.y)) != Some(true);
+            let blocked_y = self.walkable(IVec2::new(from.x, to.y)) != Some(true);
+            return !(blocked_x && blocked_y);
+        }
+        true
+    }
+
+    ///
+    /// Cheapest walkable path from `from` to `to` (A*, eight directions), both ends included.
+    /// Gives up after exploring `max_nodes` tiles so that unreachable targets stay cheap.
+    ///
+    pub fn find_path(&self, from: IVec2, to: IVec2, max_nodes: usize) -> Option<Vec<IVec2>> {
+        if from == to {
+            return Some(vec![from]);
+        }
+        let heuristic = |tile: IVec2| {
+            let delta = (tile - to).abs();
+            delta.max_element()
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+        let mut cost: HashMap<IVec2, i32> = HashMap::new();
+        open.push(Reverse((heuristic(from), 0, from.x, from.y)));
+        cost.insert(from, 0);
+        let mut explored = 0;
+
+        while let Some(Reverse((_, current_cost, x, y))) = open.pop() {
+            let current = IVec2::new(x, y);
+            if current == to {
+                let mut path = vec![current];
+                let mut tile = current;
+                while let Some(previous) = came_from.get(&tile) {
+                    tile = *previous;
+                    path.push(tile);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if current_cost > cost.get(&current).copied().unwrap_or(i32::MAX) {
+                continue;
+            }
+            explored += 1;
+            if explored > max_nodes {
+                return None;
+            }
+
+            for neighbour in NEIGHBOURS.iter().map(|offset| current + *offset) {
+                if !self.can_step(current, neighbour) {
+                    continue;
+                }
+                let neighbour_cost = current_cost + self.move_cost(neighbour).max(1) as i32;
+                if neighbour_cost < cost.get(&neighbour).copied().unwrap_or(i32::MAX) {
+                    cost.insert(neighbour, neighbour_cost);
+                    came_from.insert(neighbour, current);
+                    open.push(Reverse((neighbour_cost + heuristic(neighbour), neighbour_cost, neighbour.x, neighbour.y)));
+                }
+            }
+        }
+        None
+    }
+}
+
+///
+/// Offsets of the eight tiles around a tile.
+///
+pub const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 0),
+    IVec2::new(-1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(1, 1),
+];
+
+///
+/// Tiles crossed by a straight line from `from` to `to` (Bresenham), both ends included.
+///
+pub fn tile_line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+    let mut tiles = Vec::new();
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut current = from;
+
+    loop {
+        tiles.push(current);
+        if current == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            current.x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            current.y += step_y;
+        }
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::constants::TILE_SIZE_PX;
+
+    // Texture indices of the test maps: floor, wall and mud that costs 3 to walk into.
+    const FLOOR: u32 = 0;
+    const WALL: u32 = 1;
+    const MUD: u32 = 2;
+
+    ///
+    /// World with a single tilemap drawn from rows of `.`, `#` and `~`, the first row at the top.
+    ///
+    fn world_with_map(rows: &[&str]) -> World {
+        let mut world = World::new();
+        let size = TilemapSize { x: rows[0].len() as u32, y: rows.len() as u32 };
+        let tilemap = world.spawn_empty().id();
+        let mut storage = TileStorage::empty(size);
+        for (row, line) in rows.iter().enumerate() {
+            for (x, cell) in line.chars().enumerate() {
+                let texture = match cell {
+                    '#' => WALL,
+                    '~' => MUD,
+                    _ => FLOOR,
+                };
+                let tile_pos = TilePos { x: x as u32, y: (rows.len() - 1 - row) as u32 };
+                let tile = world.spawn((tile_pos, TilemapId(tilemap), TileTextureIndex(texture))).id();
+                storage.set(&tile_pos, tile);
+            }
+        }
+        let tile_size = TilemapTileSize { x: TILE_SIZE_PX, y: TILE_SIZE_PX };
+        world.entity_mut(tilemap).insert((
+            size,
+            TilemapGridSize::from(tile_size),
+            tile_size,
+            TilemapType::Square,
+            storage,
+            GlobalTransform::default(),
+            TileProperties {
+                blocked: HashSet::from([WALL]),
+                opaque: HashSet::from([WALL]),
+                costs: HashMap::from([(MUD, 3)]),
+            },
+        ));
+        let mut index = TilemapIndex::default();
+        index.insert(tilemap, vec![IVec2::ZERO]);
+        world.insert_resource(index);
+        world
+    }
+
+    fn find_path(world: &mut World, from: IVec2, to: IVec2, max_nodes: usize) -> Option<Vec<IVec2>> {
+        world.run_system_once(move |grid: TileGrid| grid.find_path(from, to, max_nodes)).unwrap()
+    }
+
+    fn textures(world: &mut World, path: &[IVec2]) -> Vec<u32> {
+        let path = path.to_vec();
+        world
+            .run_system_once(move |grid: TileGrid| path.iter().map(|tile| grid.textures_at(*tile).next().unwrap().0).collect::<Vec<_>>())
+            .unwrap()
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let mut world = world_with_map(&[
+            ".....",
+            "..#..",
+            "..#..",
+            "..#..",
+            ".....",
+        ]);
+        let (from, to) = (IVec2::new(0, 2), IVec2::new(4, 2));
+        let path = find_path(&mut world, from, to, 100).unwrap();
+        assert_eq!((path.first(), path.last()), (Some(&from), Some(&to)));
+        assert_eq!(path.len(), 5);
+        assert!(path.windows(2).all(|step| (step[1] - step[0]).abs().max_element() == 1));
+        assert!(!textures(&mut world, &path).contains(&WALL));
+
+        assert_eq!(find_path(&mut world, from, from, 100), Some(vec![from]));
+    }
+
+    #[test]
+    fn paths_avoid_costly_tiles() {
+        let mut world = world_with_map(&[
+            ".......",
+            ".~~~~~.",
+            ".......",
+        ]);
+        let path = find_path(&mut world, IVec2::new(0, 1), IVec2::new(6, 1), 100).unwrap();
+        assert_eq!(path.len(), 7);
+        assert!(!textures(&mut world, &path).contains(&MUD));
+    }
+
+    #[test]
+    fn unreachable_targets_give_up() {
+        let mut world = world_with_map(&[
+            ".....",
+            ".###.",
+            ".#.#.",
+            ".###.",
+            ".....",
+        ]);
+        assert_eq!(find_path(&mut world, IVec2::new(0, 0), IVec2::new(2, 2), 100), None);
+        // Reachable, but further than the search is allowed to look.
+        assert_eq!(find_path(&mut world, IVec2::new(0, 0), IVec2::new(4, 4), 2), None);
+        assert!(find_path(&mut world, IVec2::new(0, 0), IVec2::new(4, 4), 100).is_some());
+    }
+
+    #[test]
+    fn diagonal_steps_do_not_squeeze_between_walls() {
+        let mut world = world_with_map(&[
+            ".#",
+            "#.",
+        ]);
+        assert_eq!(find_path(&mut world, IVec2::new(0, 1), IVec2::new(1, 0), 100), None);
+    }
+}
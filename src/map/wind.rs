@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+
+// Humidity of the air blowing in from beyond the edge of the world.
+const EDGE_HUMIDITY: f64 = 0.4;
+// Share of the missing humidity the air takes from each sea tile it crosses.
+const SEA_EVAPORATION: f64 = 0.1;
+// Share of its humidity the air rains on each flat land tile, with the weakest wind. Stronger
+// winds carry the humidity further inland.
+const RAIN_RATE: f64 = 0.008;
+// Extra rain per unit of elevation the air is lifted above the height it already reached.
+const OROGRAPHIC_RAIN: f64 = 4.0;
+// Height the air loses on each tile once past a mountain, until it follows the ground again.
+const AIR_SINK: f64 = 0.002;
+// Moisture of a land tile per unit of rain, on top of the humidity of its air.
+const RAIN_MOISTURE: f64 = 6.0;
+
+///
+/// Prevailing winds of the overworld. They carry the humidity of the sea over the land and
+/// rain it on the slopes facing them, leaving dry land behind the mountain ranges.
+///
+#[derive(Reflect, Debug, Clone)]
+pub struct WindConfig {
+    /// Direction the wind blows towards, in degrees: 0 blows east, 90 blows north.
+    pub direction: f32,
+    /// How much the winds decide the moisture instead of the noise, in `0..=1`. Stronger
+    /// winds also carry the humidity further inland.
+    pub strength: f64,
+}
+
+impl Default for WindConfig {
+    fn default() -> Self {
+        WindConfig {
+            direction: 0.0,
+            strength: 0.6,
+        }
+    }
+}
+
+impl WindConfig {
+    pub fn vector(&self) -> Vec2 {
+        Vec2::from_angle(self.direction.to_radians())
+    }
+}
+
+///
+/// Moisture left by the wind on a row-major heightmap of `width` by `height` tiles. The air
+/// is followed downwind from tile to tile: it picks humidity up over the tiles below
+/// `sea_level`, and over land rains part of it, more where the ground lifts it higher than it
+/// has been. Past a range the air stays high and dry for a while, leaving a rain shadow.
+///
+pub fn wind_moisture(elevation: &[f64], width: usize, height: usize, sea_level: f64, config: &WindConfig) -> Vec<f64> {
+    let wind = config.vector().as_dvec2();
+    let rain_rate = RAIN_RATE * (1.5 - config.strength.clamp(0.0, 1.0));
+    let tile = |index: usize| IVec2::new((index % width) as i32, (index / width) as i32);
+
+    // The air over a tile comes from the tile upwind of it, which is always earlier in this
+    // order.
+    let mut order: Vec<usize> = (0..elevation.len()).collect();
+    order.sort_by(|a, b| tile(*a).as_dvec2().dot(wind).total_cmp(&tile(*b).as_dvec2().dot(wind)));
+
+    let mut humidity = vec![0.0; elevation.len()];
+    let mut altitude = vec![0.0; elevation.len()];
+    let mut moisture = vec![0.0; elevation.len()];
+    for index in order {
+        let upwind = (tile(index).as_dvec2() - wind).round().as_ivec2();
+        let inside = upwind.x >= 0 && upwind.y >= 0 && upwind.x < width as i32 && upwind.y < height as i32;
+        let (air, air_height) = if inside {
+            let upwind = upwind.y as usize * width + upwind.x as usize;
+            (humidity[upwind], altitude[upwind])
+        } else {
+            (EDGE_HUMIDITY, elevation[index].max(sea_level))
+        };
+
+        let ground = elevation[index].max(sea_level);
+        altitude[index] = ground.max(air_height - AIR_SINK);
+        if elevation[index] < sea_level {
+            humidity[index] = air + (1.0 - air) * SEA_EVAPORATION;
+            moisture[index] = humidity[index];
+            continue;
+        }
+        let lift = (ground - air_height).max(0.0);
+        let rain = air * (rain_rate + lift * OROGRAPHIC_RAIN).min(1.0);
+        humidity[index] = air - rain;
+        moisture[index] = (humidity[index] + rain * RAIN_MOISTURE).clamp(0.0, 1.0);
+    }
+    moisture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 20;
+    const HEIGHT: usize = 3;
+    const SEA_LEVEL: f64 = 0.3;
+
+    ///
+    /// Sea on the west side, plains with a ridge in the middle.
+    ///
+    fn ridge_map() -> Vec<f64> {
+        (0..WIDTH * HEIGHT)
+            .map(|index| match index % WIDTH {
+                0..=4 => 0.1,
+                10 => 0.9,
+                _ => 0.5,
+            })
+            .collect()
+    }
+
+    fn row(moisture: &[f64]) -> &[f64] {
+        &moisture[WIDTH..2 * WIDTH]
+    }
+
+    #[test]
+    fn air_picks_humidity_up_over_the_sea() {
+        let moisture = wind_moisture(&ridge_map(), WIDTH, HEIGHT, SEA_LEVEL, &WindConfig::default());
+        let row = row(&moisture);
+        assert!(row[..5].windows(2).all(|pair| pair[1] > pair[0]));
+        assert!(moisture.iter().all(|value| (0.0..=1.0).contains(value)));
+    }
+
+    #[test]
+    fn ridges_leave_a_rain_shadow_downwind() {
+        let moisture = wind_moisture(&ridge_map(), WIDTH, HEIGHT, SEA_LEVEL, &WindConfig::default());
+        let row = row(&moisture);
+        assert!(row[10] > row[9]);
+        assert!(row[12] < row[8]);
+    }
+
+    #[test]
+    fn the_shadow_follows_the_wind() {
+        // Blowing west, the sea is downwind and the plains east of the ridge only get the
+        // humidity of the edge of the world.
+        let config = WindConfig { direction: 180.0, ..default() };
+        let moisture = wind_moisture(&ridge_map(), WIDTH, HEIGHT, SEA_LEVEL, &config);
+        let row = row(&moisture);
+        assert!(row[8] < row[12]);
+    }
+}
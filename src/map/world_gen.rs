@@ -0,0 +1,555 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
+use rand::prelude::*;
+
+use crate::constants::{CHUNK_SIZE, OVERWORLD_SIZE_HEIGHT, OVERWORLD_SIZE_WIDTH};
+use crate::map::continents::{continent_elevation, island_elevation, WorldShape};
+use crate::map::erosion::erode;
+use crate::map::decorations::place_decorations;
+use crate::map::overworld_map::{Climate, ClimateSampler, MapLayer, OverWorldMapConfig, PoiKind, PointOfInterest};
+use crate::map::settlements::{place_settlements, SettlementParams};
+use crate::map::tile_to_chunk_coords;
+use crate::map::wind::wind_moisture;
+use crate::states::GameState;
+use crate::tile_type::GroundTiles;
+
+// Elevation under which tiles are sea, the biomes put water there and rivers end there.
+const SEA_LEVEL: f64 = 0.5;
+// Rivers spring from the highest and wettest tiles, this share of the land, far enough apart.
+const RIVER_SOURCES: usize = 24;
+const RIVER_SOURCE_SHARE: f64 = 0.05;
+const RIVER_SOURCE_MOISTURE: f64 = 0.3;
+const RIVER_SPACING: i32 = 12;
+// A river spills out of a hollow this much higher than its bed, deeper ones end it.
+const RIVER_MAX_CLIMB: f64 = 0.01;
+const RIVER_MIN_LENGTH: usize = 8;
+const RIVER_MAX_LENGTH: usize = 600;
+// Moisture given to the tiles along a river, fading out over the radius.
+const RIVER_MOISTURE: f64 = 0.15;
+const RIVER_MOISTURE_RADIUS: i32 = 3;
+const RIVER_GROUND: GroundTiles = GroundTiles::LightWater1;
+// Chance for a chunk to hold a cave mouth, in percent, and tiles tried to place it.
+const CAVE_CHANCE: u32 = 30;
+const CAVE_ATTEMPTS: u32 = 12;
+
+///
+/// The whole overworld, generated once by the [`WorldGenPipeline`] and kept until the map is
+/// regenerated. Every layer holds one value per tile, row by row. Chunks are cut from it when
+/// they are spawned, and anything that needs the climate of a tile (monster spawns, dungeon
+/// entrances...) reads it here so that it always agrees with the map.
+///
+#[derive(Resource, Debug, Default)]
+pub struct GeneratedWorld {
+    pub width: i32,
+    pub height: i32,
+    /// Seed of the configuration the world was generated from.
+    pub seed: u64,
+    pub elevation: Vec<f64>,
+    pub moisture: Vec<f64>,
+    pub temperature: Vec<f64>,
+    /// Tiles a river flows through.
+    pub rivers: Vec<bool>,
+    /// Ground of the tiles, roads and town squares included. Empty until the biomes pass.
+    pub biomes: Vec<GroundTiles>,
+    /// Caves, ruins, villages and towns.
+    pub points_of_interest: Vec<PointOfInterest>,
+    /// Tiles of the layers drawn over the ground (trees, houses...), few tiles have one.
+    layer_tiles: HashMap<(MapLayer, IVec2), u32>,
+}
+
+impl GeneratedWorld {
+    pub fn new(width: i32, height: i32, seed: u64) -> Self {
+        let size = (width * height) as usize;
+        GeneratedWorld {
+            width,
+            height,
+            seed,
+            elevation: vec![0.0; size],
+            moisture: vec![0.0; size],
+            temperature: vec![0.0; size],
+            rivers: vec![false; size],
+            biomes: Vec::new(),
+            points_of_interest: Vec::new(),
+            layer_tiles: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Index of a tile in the layers, `None` outside of the world.
+    ///
+    pub fn index(&self, tile: IVec2) -> Option<usize> {
+        if tile.x < 0 || tile.y < 0 || tile.x >= self.width || tile.y >= self.height {
+            return None;
+        }
+        Some((tile.y * self.width + tile.x) as usize)
+    }
+
+    pub fn tile(&self, index: usize) -> IVec2 {
+        IVec2::new(index as i32 % self.width, index as i32 / self.width)
+    }
+
+    pub fn climate(&self, tile: IVec2) -> Option<Climate> {
+        self.index(tile).map(|index| self.climate_at(index))
+    }
+
+    fn climate_at(&self, index: usize) -> Climate {
+        Climate {
+            elevation: self.elevation[index],
+            moisture: self.moisture[index],
+            temperature: self.temperature[index],
+        }
+    }
+
+    pub fn ground(&self, tile: IVec2) -> Option<GroundTiles> {
+        self.index(tile).and_then(|index| self.biomes.get(index).copied())
+    }
+
+    ///
+    /// Texture index of a tile on one of the chunk layers. The ground layer is the biomes.
+    ///
+    pub fn layer_tile(&self, layer: MapLayer, tile: IVec2) -> Option<u32> {
+        match layer {
+            MapLayer::Ground => self.ground(tile).map(|ground| ground as u32),
+            _ => self.layer_tiles.get(&(layer, tile)).copied(),
+        }
+    }
+
+    ///
+    /// Places a tile on one of the chunk layers, `None` clears it. Passes write their trees,
+    /// rocks and buildings here. Writing to the ground layer replaces the biome, which cannot
+    /// be cleared.
+    ///
+    pub fn set_layer_tile(&mut self, layer: MapLayer, tile: IVec2, index: Option<u32>) {
+        let Some(tile_index) = self.index(tile) else { return; };
+        match (layer, index) {
+            (MapLayer::Ground, Some(index)) => {
+                if let Some(ground) = self.biomes.get_mut(tile_index) {
+                    *ground = GroundTiles::from(index);
+                }
+            }
+            (MapLayer::Ground, None) => {}
+            (_, Some(index)) => {
+                self.layer_tiles.insert((layer, tile), index);
+            }
+            (_, None) => {
+                self.layer_tiles.remove(&(layer, tile));
+            }
+        }
+    }
+
+    pub fn is_river(&self, tile: IVec2) -> bool {
+        self.index(tile).is_some_and(|index| self.rivers[index])
+    }
+
+    pub fn in_chunk(&self, chunk: IVec2) -> impl Iterator<Item = &PointOfInterest> {
+        self.points_of_interest.iter().filter(move |poi| tile_to_chunk_coords(poi.tile) == chunk)
+    }
+}
+
+///
+/// Step of the world generation. The passes run in order over the same [`GeneratedWorld`],
+/// each one reading the layers filled by the passes before it.
+///
+pub trait WorldGenPass: Send + Sync {
+    /// Shown on the loading screen while the pass runs.
+    fn name(&self) -> &str;
+    fn run(&self, world: &mut GeneratedWorld, config: &OverWorldMapConfig);
+}
+
+///
+/// Ordered passes generating the overworld: elevation, erosion, temperature, moisture,
+/// rivers, biomes, settlements, caves and decorations. Plugins extend the generation by adding their own
+/// passes to the resource.
+///
+#[derive(Resource)]
+pub struct WorldGenPipeline {
+    passes: Vec<Box<dyn WorldGenPass>>,
+}
+
+impl Default for WorldGenPipeline {
+    fn default() -> Self {
+        let mut pipeline = WorldGenPipeline { passes: Vec::new() };
+        pipeline
+            .add_pass(ElevationPass)
+            .add_pass(ErosionPass)
+            .add_pass(TemperaturePass)
+            .add_pass(MoisturePass)
+            .add_pass(RiversPass)
+            .add_pass(BiomesPass)
+            .add_pass(SettlementsPass(SettlementParams::default()))
+            .add_pass(CavesPass)
+            .add_pass(DecorationsPass);
+        pipeline
+    }
+}
+
+impl WorldGenPipeline {
+    ///
+    /// Runs a pass after the ones already in the pipeline.
+    ///
+    pub fn add_pass(&mut self, pass: impl WorldGenPass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+}
+
+///
+/// Generation in progress. One pass runs each frame so that the loading screen follows it.
+///
+#[derive(Resource)]
+struct WorldGenTask {
+    world: GeneratedWorld,
+    config: OverWorldMapConfig,
+    next_pass: usize,
+}
+
+///
+/// Generates the overworld whenever there is none, behind a loading screen. The map is
+/// generated again once the overworld removes the [`GeneratedWorld`].
+///
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.init_resource::<WorldGenPipeline>()
+            .add_systems(
+                Update,
+                start_world_gen.run_if(
+                    in_state(GameState::GameRunning)
+                        .and(not(resource_exists::<GeneratedWorld>))
+                        .and(not(resource_exists::<WorldGenTask>)),
+                ),
+            )
+            .add_systems(Update, run_world_gen.run_if(in_state(GameState::GeneratingWorld).and(resource_exists::<WorldGenTask>)))
+            .add_systems(EguiPrimaryContextPass, loading_screen_ui.run_if(in_state(GameState::GeneratingWorld)));
+    }
+}
+
+fn start_world_gen(
+    mut commands: Commands,
+    map_config: Res<OverWorldMapConfig>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    commands.insert_resource(WorldGenTask {
+        world: GeneratedWorld::new(OVERWORLD_SIZE_WIDTH as i32, OVERWORLD_SIZE_HEIGHT as i32, map_config.seed()),
+        config: map_config.clone(),
+        next_pass: 0,
+    });
+    next_state.set(GameState::GeneratingWorld);
+}
+
+///
+/// Runs the next pass, and hands the world over once they have all run.
+///
+fn run_world_gen(
+    mut commands: Commands,
+    pipeline: Res<WorldGenPipeline>,
+    mut task: ResMut<WorldGenTask>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let task = &mut *task;
+    if let Some(pass) = pipeline.passes.get(task.next_pass) {
+        let start = Instant::now();
+        pass.run(&mut task.world, &task.config);
+        info!("World generation pass {} done in {:.2?}.", pass.name(), start.elapsed());
+        task.next_pass += 1;
+        return;
+    }
+    commands.insert_resource(std::mem::take(&mut task.world));
+    commands.remove_resource::<WorldGenTask>();
+    next_state.set(GameState::GameRunning);
+}
+
+fn loading_screen_ui(
+    mut contexts: EguiContexts,
+    pipeline: Res<WorldGenPipeline>,
+    task: Option<Res<WorldGenTask>>,
+) -> Result {
+    let Some(task) = task else { return Ok(()); };
+    let ctx = contexts.ctx_mut()?;
+    let total = pipeline.passes.len();
+    let current = pipeline.passes.get(task.next_pass).map_or("Done", |pass| pass.name());
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 3.0);
+            ui.heading("Generating the world");
+            ui.add(
+                egui::ProgressBar::new(task.next_pass as f32 / total.max(1) as f32)
+                    .desired_width(320.0)
+                    .show_percentage(),
+            );
+            ui.label(format!("{} ({}/{})", current, (task.next_pass + 1).min(total), total));
+        });
+    });
+    Ok(())
+}
+
+///
+/// Elevation from the noise, shaped into an island or continents as configured, see
+/// [`WorldShape`].
+///
+struct ElevationPass;
+
+impl WorldGenPass for ElevationPass {
+    fn name(&self) -> &str {
+        "Elevation"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, config: &OverWorldMapConfig) {
+        let sampler = ClimateSampler::new(config);
+        let noise: Vec<f64> = (0..world.elevation.len()).map(|index| sampler.elevation(world.tile(index))).collect();
+        let (width, height) = (world.width as usize, world.height as usize);
+        world.elevation = match config.shape() {
+            WorldShape::Noise => noise,
+            WorldShape::Island => island_elevation(&noise, width, height),
+            WorldShape::Continents => continent_elevation(&noise, width, height, config.continents(), world.seed),
+        };
+    }
+}
+
+///
+/// Carves valleys into the noise, see [`erode`].
+///
+struct ErosionPass;
+
+impl WorldGenPass for ErosionPass {
+    fn name(&self) -> &str {
+        "Erosion"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, config: &OverWorldMapConfig) {
+        erode(&mut world.elevation, world.width as usize, world.height as usize, config.erosion(), world.seed);
+    }
+}
+
+struct TemperaturePass;
+
+impl WorldGenPass for TemperaturePass {
+    fn name(&self) -> &str {
+        "Temperature"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, config: &OverWorldMapConfig) {
+        let sampler = ClimateSampler::new(config);
+        world.temperature = (0..world.temperature.len())
+            .map(|index| sampler.temperature(world.tile(index), world.elevation[index]))
+            .collect();
+    }
+}
+
+///
+/// Moisture from the noise, blended with the moisture the winds bring from the sea, see
+/// [`wind_moisture`].
+///
+struct MoisturePass;
+
+impl WorldGenPass for MoisturePass {
+    fn name(&self) -> &str {
+        "Moisture"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, config: &OverWorldMapConfig) {
+        let sampler = ClimateSampler::new(config);
+        let winds = wind_moisture(&world.elevation, world.width as usize, world.height as usize, SEA_LEVEL, config.wind());
+        let strength = config.wind().strength.clamp(0.0, 1.0);
+        world.moisture = (0..world.moisture.len())
+            .map(|index| sampler.moisture(world.tile(index), world.elevation[index]) * (1.0 - strength) + winds[index] * strength)
+            .collect();
+    }
+}
+
+///
+/// Rivers spring from high and wet tiles and run down to the sea, or to another river. They
+/// water the land along their banks.
+///
+struct RiversPass;
+
+impl WorldGenPass for RiversPass {
+    fn name(&self) -> &str {
+        "Rivers"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, _config: &OverWorldMapConfig) {
+        let mut rng = StdRng::seed_from_u64(world.seed ^ 0x21BE_5000_0000_0001);
+        let mut highlands: Vec<usize> = (0..world.elevation.len()).filter(|index| world.elevation[*index] >= SEA_LEVEL).collect();
+        highlands.sort_by(|a, b| world.elevation[*b].total_cmp(&world.elevation[*a]));
+        highlands.truncate(((highlands.len() as f64 * RIVER_SOURCE_SHARE) as usize).max(1));
+        highlands.shuffle(&mut rng);
+
+        let mut sources: Vec<IVec2> = Vec::new();
+        for index in highlands {
+            if sources.len() >= RIVER_SOURCES {
+                break;
+            }
+            let tile = world.tile(index);
+            if world.moisture[index] >= RIVER_SOURCE_MOISTURE
+                && sources.iter().all(|source| source.distance_squared(tile) >= RIVER_SPACING * RIVER_SPACING)
+            {
+                sources.push(tile);
+            }
+        }
+
+        let mut count = 0;
+        for source in sources {
+            let Some(river) = trace_river(world, source) else { continue; };
+            for tile in river {
+                if let Some(index) = world.index(tile) {
+                    world.rivers[index] = true;
+                }
+            }
+            count += 1;
+        }
+
+        // Wettest banks win, so that rivers joining do not flood the land around them.
+        let mut watered = vec![0.0_f64; world.moisture.len()];
+        for index in (0..world.rivers.len()).filter(|index| world.rivers[*index]) {
+            let tile = world.tile(index);
+            for y in -RIVER_MOISTURE_RADIUS..=RIVER_MOISTURE_RADIUS {
+                for x in -RIVER_MOISTURE_RADIUS..=RIVER_MOISTURE_RADIUS {
+                    let distance = (IVec2::new(x, y).length_squared() as f64).sqrt();
+                    let Some(bank) = world.index(tile + IVec2::new(x, y)) else { continue; };
+                    let water = RIVER_MOISTURE * (1.0 - distance / (RIVER_MOISTURE_RADIUS + 1) as f64).max(0.0);
+                    watered[bank] = watered[bank].max(water);
+                }
+            }
+        }
+        for (moisture, water) in world.moisture.iter_mut().zip(watered) {
+            *moisture = (*moisture + water).min(1.0);
+        }
+        info!("Traced {} rivers in the overworld.", count);
+    }
+}
+
+///
+/// Path of a river from its source, always flowing to the lowest neighbouring tile it has not
+/// been through. `None` when the river is too short to keep.
+///
+fn trace_river(world: &GeneratedWorld, source: IVec2) -> Option<Vec<IVec2>> {
+    let mut river = vec![source];
+    let mut visited = HashSet::from([source]);
+    let mut current = source;
+    while river.len() < RIVER_MAX_LENGTH {
+        let here = world.elevation[world.index(current)?];
+        let next = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .map(|offset| current + offset)
+            .into_iter()
+            .filter(|tile| !visited.contains(tile))
+            .filter_map(|tile| world.index(tile).map(|index| (world.elevation[index], tile)))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let Some((elevation, next)) = next else { break; };
+        if elevation > here + RIVER_MAX_CLIMB {
+            break;
+        }
+        // The river ends in the sea or joins another one.
+        if elevation < SEA_LEVEL || world.is_river(next) {
+            break;
+        }
+        visited.insert(next);
+        river.push(next);
+        current = next;
+    }
+    (river.len() >= RIVER_MIN_LENGTH).then_some(river)
+}
+
+///
+/// Ground of every tile from its climate, rivers drawn over the land.
+///
+struct BiomesPass;
+
+impl WorldGenPass for BiomesPass {
+    fn name(&self) -> &str {
+        "Biomes"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, _config: &OverWorldMapConfig) {
+        world.biomes = (0..world.elevation.len())
+            .map(|index| if world.rivers[index] { RIVER_GROUND } else { world.climate_at(index).ground() })
+            .collect();
+    }
+}
+
+///
+/// Towns, villages, ruins and their roads, see [`place_settlements`].
+///
+struct SettlementsPass(SettlementParams);
+
+impl WorldGenPass for SettlementsPass {
+    fn name(&self) -> &str {
+        "Settlements"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, _config: &OverWorldMapConfig) {
+        place_settlements(world, &self.0);
+    }
+}
+
+///
+/// Cave mouths at the foot of the mountains. Each chunk rolls for one with its own seed, so a
+/// chunk keeps its cave whatever the other chunks get.
+///
+struct CavesPass;
+
+impl WorldGenPass for CavesPass {
+    fn name(&self) -> &str {
+        "Caves"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, _config: &OverWorldMapConfig) {
+        let chunk_size = IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32);
+        let chunks = (IVec2::new(world.width, world.height) + chunk_size - IVec2::ONE) / chunk_size;
+        for y in 0..chunks.y {
+            for x in 0..chunks.x {
+                if let Some(cave) = chunk_cave(world, IVec2::new(x, y)) {
+                    world.points_of_interest.push(cave);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Trees, rocks and cacti over the land, see [`place_decorations`]. It runs last so that
+/// nothing grows on the roads, the town squares and the cave mouths.
+///
+struct DecorationsPass;
+
+impl WorldGenPass for DecorationsPass {
+    fn name(&self) -> &str {
+        "Decorations"
+    }
+
+    fn run(&self, world: &mut GeneratedWorld, _config: &OverWorldMapConfig) {
+        place_decorations(world);
+    }
+}
+
+fn chunk_cave(world: &GeneratedWorld, chunk: IVec2) -> Option<PointOfInterest> {
+    let chunk_key = ((chunk.x as u32 as u64) << 32) | chunk.y as u32 as u64;
+    let seed = world.seed ^ chunk_key.wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    let mut rng = StdRng::seed_from_u64(seed);
+    if rng.random_range(0..100) >= CAVE_CHANCE {
+        return None;
+    }
+
+    let chunk_origin = chunk * IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32);
+    for _ in 0..CAVE_ATTEMPTS {
+        let tile = chunk_origin
+            + IVec2::new(rng.random_range(0..CHUNK_SIZE.x as i32), rng.random_range(0..CHUNK_SIZE.y as i32));
+        if !world.ground(tile).is_some_and(|ground| ground.is_walkable()) {
+            continue;
+        }
+        let against_mountain = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .iter()
+            .any(|offset| world.ground(tile + *offset).is_some_and(|ground| ground.blocks_sight()));
+        if against_mountain {
+            return Some(PointOfInterest { kind: PoiKind::CaveMouth, tile, seed: rng.random() });
+        }
+    }
+    None
+}
@@ -4,6 +4,10 @@ use bevy_ecs_tilemap::helpers::*;
 use noise::{Fbm, NoiseFn, Perlin, OpenSimplex};
 use rand::Rng;
 
+use crate::input::{ActionState, InputAction};
+use crate::map::erosion::{erode, ErosionConfig};
+use crate::map::ground_atlas::{ground_atlas_loaded, GroundAtlas, GroundAtlasHandle};
+use crate::player::Player;
 use crate::tile_type::GroundTiles;
 
 
@@ -15,10 +19,13 @@ impl Plugin for WorldGenIslandPlugin {
         app
             .add_plugins(TilemapPlugin)
             .add_systems(Startup, startup)
-            .add_systems(Startup, spawn_chunk)
+            // The tiles are drawn from the ground atlas, the island waits for it.
+            .add_systems(Update, spawn_chunk.run_if(ground_atlas_loaded.and(run_once)))
             // .add_systems(Update, spawn_chunk_around_camera)
             // .add_systems(Update, despawn_outofrange_chunks)
-            .add_systems(Update, camera_movement);
+            // The debug camera shares the movement actions with the player, only fly it when
+            // there is no player to follow.
+            .add_systems(Update, camera_movement.run_if(not(any_with_component::<Player>)));
     }
 }
 
@@ -45,37 +52,21 @@ fn startup(mut commands: Commands) {
 
 fn camera_movement(
     time: Res<Time>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut query: Query<(&mut Transform, &mut Projection), With<Camera>>,
 ) {
     for (mut transform, mut projection) in query.iter_mut() {
-        let mut direction = Vec3::ZERO;
-
-        if keyboard_input.pressed(KeyCode::KeyA) {
-            direction -= Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyD) {
-            direction += Vec3::new(1.0, 0.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyW) {
-            direction += Vec3::new(0.0, 1.0, 0.0);
-        }
-
-        if keyboard_input.pressed(KeyCode::KeyS) {
-            direction -= Vec3::new(0.0, 1.0, 0.0);
-        }
+        let direction = actions.move_vector().extend(0.0);
 
         let Projection::Orthographic(ortho) = &mut *projection else {
             continue;
         };
 
-        if keyboard_input.pressed(KeyCode::KeyZ) {
+        if actions.pressed(InputAction::ZoomOut) {
             ortho.scale += 0.1;
         }
 
-        if keyboard_input.pressed(KeyCode::KeyX) {
+        if actions.pressed(InputAction::ZoomIn) {
             ortho.scale -= 0.1;
         }
 
@@ -151,9 +142,12 @@ fn camera_pos_to_chunk_pos(camera_pos: &Vec2) -> IVec2 {
 fn spawn_chunk(
     mut commands: Commands, 
     asset_server: Res<AssetServer>,
+    atlas_handle: Res<GroundAtlasHandle>,
+    atlases: Res<Assets<GroundAtlas>>,
 ) {
+    let Some(atlas) = atlases.get(&atlas_handle.0) else { return; };
     // let texture_handle = asset_server.load("tiles/overworld_tiles.png");
-    let texture_handle = asset_server.load("tiles/grounds_tiles.png");
+    let texture_handle = asset_server.load(atlas.image.clone());
     let tilemap_entity = commands.spawn_empty().id();
     let tile_map_size = TilemapSize::new(WIDTH, HEIGHT);
     let mut tile_storage = TileStorage::empty(tile_map_size.into());
@@ -206,13 +200,17 @@ fn spawn_chunk(
             // Warped Moisture
             let qx = warp_gen.get([nx * 2.0, ny * 2.0]) * 0.4;
             let qy = warp_gen.get([nx * 2.0 + 5.2, ny * 2.0 + 1.3]) * 0.4;
-            let mut m = (moist_gen.get([nx + qx, ny + qy]) + 1.0) / 2.0;
-            
-            // Terrain coupling: Lower areas near water are naturally wetter
-            let height_factor = 1.0 - elevation_map[idx];
-            moisture_map[idx] = (m * 0.6 + height_factor * 0.4).clamp(0.0, 1.0);
+            moisture_map[idx] = (moist_gen.get([nx + qx, ny + qy]) + 1.0) / 2.0;
         }
     }   
+
+    // 3. Erosion turns the noise blobs into ridges and valleys, before moisture depends on it.
+    erode(&mut elevation_map, WIDTH as usize, HEIGHT as usize, &ErosionConfig::default(), seed as u64);
+    for (m, e) in moisture_map.iter_mut().zip(&elevation_map) {
+        // Terrain coupling: Lower areas near water are naturally wetter
+        let height_factor = 1.0 - e;
+        *m = (*m * 0.6 + height_factor * 0.4).clamp(0.0, 1.0);
+    }
     info!("Elevation and Moisture maps generated.");
 
     // 4. Final Rendering
@@ -312,24 +310,24 @@ fn spawn_chunk(
 /// 
 fn biome(e: f64, m: f64) -> u32 {
     if e < SEALEVEL {
-        return if e < SEALEVEL - 0.1 { Rgb([20, 50, 100]) } else { Rgb([40, 90, 160]) };
+        return if e < SEALEVEL - 0.1 { GroundTiles::MediumDeepWater as u32 } else { GroundTiles::LightShallowWater as u32 };
     }
 
-    if e < SEALEVEL + 0.03 { return Rgb([230, 220, 160]); } // Beach
+    if e < SEALEVEL + 0.03 { return GroundTiles::LightDirt as u32; } // Beach
 
     if e > 0.45 {
-        return if m > 0.4 { Rgb([255, 255, 255]) } else { Rgb([100, 100, 100]) }; // Snow vs Rock
+        return if m > 0.4 { GroundTiles::LightSnowyMountain as u32 } else { GroundTiles::MediumGreyRock as u32 }; // Snow vs Rock
     }
 
     if e > 0.4 {
-        if m > 0.6 { return Rgb([34, 139, 34]); }    // Forest
-        if m > 0.3 { return Rgb([100, 150, 70]); }   // Shrubland
-        return Rgb([180, 160, 120]);                 // Tundra/Barren
+        if m > 0.6 { return GroundTiles::BrightDeciduousForest as u32; } // Forest
+        if m > 0.3 { return GroundTiles::MediumGrass as u32; }           // Shrubland
+        return GroundTiles::LightRockyDirt as u32;                       // Tundra/Barren
     }
 
     // Lowlands
-    if m > 0.7 { return Rgb([0, 80, 40]); }      // Jungle
-    if m > 0.4 { return Rgb([60, 160, 60]); }    // Grassland
-    if m > 0.15 { return Rgb([160, 180, 90]); }  // Savannah
-    Rgb([210, 180, 110])                         // Desertm
+    if m > 0.7 { return GroundTiles::BrightLushForest as u32; } // Jungle
+    if m > 0.4 { return GroundTiles::LightGrass as u32; }       // Grassland
+    if m > 0.15 { return GroundTiles::LightGrassyDirt as u32; } // Savannah
+    GroundTiles::LightSandDesert as u32                         // Desert
 }
\ No newline at end of file
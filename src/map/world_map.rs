@@ -5,9 +5,10 @@ pub struct WorldMapPlugin;
 
 impl Plugin for WorldMapPlugin {
     fn build(&self, app: &mut App) {
-        app
-            .add_systems(Startup, setup_world_map)
-            .add_plugins(TiledPlugin::default());
+        if !app.is_plugin_added::<TiledPlugin>() {
+            app.add_plugins(TiledPlugin::default());
+        }
+        app.add_systems(Startup, setup_world_map);
     }
 }
 
@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::animation::AnimatedCharacter;
+use crate::assets::RonAssetAppExt;
+use crate::combat::{CombatProfile, Dead, ExperienceReward, Faction, Resistances};
+use crate::constants::CHUNK_SIZE;
+use crate::events::{ChunkLoaded, ChunkUnloaded};
+use crate::map::overworld_map::Climate;
+use crate::map::world_gen::GeneratedWorld;
+use crate::map::{tile_coords_to_world, tile_to_chunk_coords, world_to_tile_coords};
+use crate::player::{Facing, Player};
+use crate::rng::RunRng;
+use crate::stats::{Attributes, Health};
+use crate::tile_type::GroundTiles;
+
+const BESTIARY_PATH: &str = "monsters/bestiary.bestiary.ron";
+// Same depth as the player so that monsters and player are drawn over the ground items.
+const MONSTER_Z: f32 = 10.0;
+
+///
+/// Every monster of the game, loaded from a `*.bestiary.ron` file.
+///
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct Bestiary {
+    pub monsters: Vec<MonsterDef>,
+}
+
+impl Bestiary {
+    pub fn get(&self, id: &str) -> Option<&MonsterDef> {
+        self.monsters.iter().find(|monster| monster.id == id)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonsterDef {
+    pub id: String,
+    pub name: String,
+    /// Animation set (`*.anim.ron`) of the monster's sprite sheet.
+    pub animations: String,
+    pub attributes: Attributes,
+    #[serde(default)]
+    pub resistances: Resistances,
+    #[serde(default)]
+    pub combat: CombatProfile,
+    #[serde(default)]
+    pub experience: u64,
+    pub behaviour: AiBehaviour,
+    /// Number of monsters spawned together.
+    #[serde(default = "default_pack_size")]
+    pub pack_size: u32,
+    /// Where the monster can appear. The weights of every matching rule are added.
+    pub spawns: Vec<SpawnWeight>,
+}
+
+fn default_pack_size() -> u32 {
+    1
+}
+
+impl MonsterDef {
+    pub fn spawn_weight(&self, climate: &Climate, ground: GroundTiles) -> f32 {
+        self.spawns.iter().map(|rule| rule.weight_for(climate, ground)).sum()
+    }
+}
+
+///
+/// Spawn weight of a monster on the tiles matching every condition. Missing conditions match
+/// any tile.
+///
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnWeight {
+    #[serde(default)]
+    pub biomes: Vec<GroundTiles>,
+    #[serde(default = "full_range")]
+    pub elevation: (f64, f64),
+    #[serde(default = "full_range")]
+    pub moisture: (f64, f64),
+    #[serde(default = "full_range")]
+    pub temperature: (f64, f64),
+    pub weight: f32,
+}
+
+fn full_range() -> (f64, f64) {
+    (0.0, 1.0)
+}
+
+impl SpawnWeight {
+    fn weight_for(&self, climate: &Climate, ground: GroundTiles) -> f32 {
+        let within = |value: f64, (min, max): (f64, f64)| value >= min && value <= max;
+        let matches = (self.biomes.is_empty() || self.biomes.contains(&ground))
+            && within(climate.elevation, self.elevation)
+            && within(climate.moisture, self.moisture)
+            && within(climate.temperature, self.temperature);
+        if matches { self.weight } else { 0.0 }
+    }
+}
+
+///
+/// How a monster behaves when left to itself.
+///
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[reflect(Component)]
+pub enum AiBehaviour {
+    /// Walks around randomly and attacks the player when close.
+    Wander,
+    /// Hunts the player as soon as it is seen.
+    Chase,
+    /// Runs away from the player.
+    Flee,
+    /// Stays around its spawn tile and attacks whoever comes near.
+    Guard,
+    /// Follows the other members of its pack and attacks together with them.
+    Pack,
+}
+
+///
+/// A monster spawned from the bestiary.
+///
+#[derive(Component, Debug, Clone)]
+pub struct Monster {
+    pub id: String,
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnloadPolicy {
+    /// Monsters are saved with their chunk and come back where they were.
+    Freeze,
+    /// Monsters are removed with their chunk, a reloaded chunk rolls new ones.
+    Despawn,
+}
+
+#[derive(Resource, Reflect, InspectorOptions, Debug, Clone)]
+#[reflect(Resource, InspectorOptions)]
+pub struct SpawnerConfig {
+    /// Maximum number of monsters a chunk can hold.
+    pub max_per_chunk: u32,
+    /// Random tiles tried per chunk to place monsters.
+    pub attempts_per_chunk: u32,
+    /// Monsters are never spawned this close to the player, in tiles.
+    pub safe_radius: i32,
+    pub on_unload: UnloadPolicy,
+}
+
+impl Default for SpawnerConfig {
+    fn default() -> Self {
+        SpawnerConfig {
+            max_per_chunk: 4,
+            attempts_per_chunk: 6,
+            safe_radius: 6,
+            on_unload: UnloadPolicy::Freeze,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FrozenMonster {
+    id: String,
+    tile: IVec2,
+    health: i32,
+}
+
+///
+/// Monsters of the chunks that are not spawned, when the unload policy is to freeze them. A
+/// chunk that was cleared keeps an empty entry so that it is not populated again.
+///
+#[derive(Resource, Debug, Default)]
+struct FrozenMonsters {
+    chunks: HashMap<IVec2, Vec<FrozenMonster>>,
+    /// Chunks that are loaded and were populated.
+    populated: HashSet<IVec2>,
+}
+
+#[derive(Resource, Debug, Default)]
+struct BestiaryHandle(Handle<Bestiary>);
+
+pub struct MonstersPlugin;
+
+impl Plugin for MonstersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_ron_asset::<Bestiary>(&["bestiary.ron"])
+            .init_resource::<BestiaryHandle>()
+            .init_resource::<SpawnerConfig>()
+            .init_resource::<FrozenMonsters>()
+            .register_type::<SpawnerConfig>()
+            .register_type::<AiBehaviour>()
+            .add_systems(Startup, load_bestiary)
+            .add_systems(Update, (unload_chunk_monsters, populate_chunks).chain())
+            .add_systems(PostUpdate, restore_frozen_health);
+    }
+}
+
+fn load_bestiary(
+    asset_server: Res<AssetServer>,
+    mut bestiary_handle: ResMut<BestiaryHandle>,
+) {
+    bestiary_handle.0 = asset_server.load(BESTIARY_PATH);
+}
+
+pub fn spawn_monster(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    def: &MonsterDef,
+    tile: IVec2,
+) -> Entity {
+    commands
+        .spawn((
+            Monster { id: def.id.clone() },
+            Name::new(def.name.clone()),
+            AnimatedCharacter {
+                set: asset_server.load(&def.animations),
+                initial: Facing::default().idle_animation().to_string(),
+            },
+            Facing::default(),
+            Transform::from_translation(tile_coords_to_world(tile, MONSTER_Z)),
+            def.attributes,
+            Faction::Monster,
+            def.resistances,
+            def.combat,
+            ExperienceReward(def.experience),
+            def.behaviour,
+        ))
+        .id()
+}
+
+///
+/// Fills the chunks that just streamed in, either with the monsters frozen there or with new
+/// ones picked from the bestiary by the climate of their tile. The roll only depends on the
+/// run seed and the chunk, so a chunk always gets the same monsters in a given run.
+///
+fn populate_chunks(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    bestiary_handle: Res<BestiaryHandle>,
+    bestiaries: Res<Assets<Bestiary>>,
+    world: Option<Res<GeneratedWorld>>,
+    config: Res<SpawnerConfig>,
+    run_rng: Res<RunRng>,
+    mut loaded: MessageReader<ChunkLoaded>,
+    mut frozen: ResMut<FrozenMonsters>,
+    player_query: Query<&Transform, With<Player>>,
+    mut pending: Local<Vec<IVec2>>,
+) {
+    pending.extend(loaded.read().map(|event| event.chunk));
+    // Chunks loaded before the bestiary are populated once it is available.
+    let (Some(bestiary), Some(world)) = (bestiaries.get(&bestiary_handle.0), world) else { return; };
+    if pending.is_empty() {
+        return;
+    }
+
+    let player_tile = player_query.single().ok().map(|transform| world_to_tile_coords(transform.translation));
+
+    for chunk in pending.drain(..) {
+        frozen.populated.insert(chunk);
+        if let Some(monsters) = frozen.chunks.remove(&chunk) {
+            for monster in monsters {
+                let Some(def) = bestiary.get(&monster.id) else { continue; };
+                let entity = spawn_monster(&mut commands, &asset_server, def, monster.tile);
+                // Keep the damage taken, the stats systems start new actors at full health.
+                commands.entity(entity).insert(FrozenHealth(monster.health));
+            }
+            continue;
+        }
+
+        let mut rng = StdRng::seed_from_u64(chunk_seed(run_rng.seed(), chunk));
+        let chunk_origin = chunk * IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32);
+        let mut occupied: HashSet<IVec2> = HashSet::new();
+
+        for _ in 0..config.attempts_per_chunk {
+            if occupied.len() as u32 >= config.max_per_chunk {
+                break;
+            }
+            let tile = chunk_origin
+                + IVec2::new(rng.random_range(0..CHUNK_SIZE.x as i32), rng.random_range(0..CHUNK_SIZE.y as i32));
+            if player_tile.is_some_and(|player| (player - tile).abs().max_element() < config.safe_radius) {
+                continue;
+            }
+            let (Some(climate), Some(ground)) = (world.climate(tile), world.ground(tile)) else { continue; };
+            if !ground.is_walkable() {
+                continue;
+            }
+
+            let weights: Vec<f32> = bestiary.monsters.iter().map(|def| def.spawn_weight(&climate, ground)).collect();
+            let total: f32 = weights.iter().sum();
+            if total <= 0.0 {
+                continue;
+            }
+            let mut roll = rng.random_range(0.0..total);
+            let Some(def) = bestiary.monsters.iter().zip(weights).find_map(|(def, weight)| {
+                roll -= weight;
+                (roll < 0.0).then_some(def)
+            }) else {
+                continue;
+            };
+
+            // Pack members are placed on the free walkable tiles around the first one.
+            let mut candidates = vec![tile];
+            candidates.extend((-1..=1).flat_map(|y| (-1..=1).map(move |x| tile + IVec2::new(x, y))));
+            let mut placed = 0;
+            for candidate in candidates {
+                if placed >= def.pack_size || occupied.len() as u32 >= config.max_per_chunk {
+                    break;
+                }
+                if tile_to_chunk_coords(candidate) != chunk
+                    || occupied.contains(&candidate)
+                    || !world.ground(candidate).is_some_and(|ground| ground.is_walkable())
+                {
+                    continue;
+                }
+                occupied.insert(candidate);
+                spawn_monster(&mut commands, &asset_server, def, candidate);
+                placed += 1;
+            }
+        }
+    }
+}
+
+///
+/// Health of a monster that was frozen, restored once its stats have been computed.
+///
+#[derive(Component, Debug, Clone, Copy)]
+struct FrozenHealth(i32);
+
+fn restore_frozen_health(
+    mut commands: Commands,
+    mut monsters: Query<(Entity, &FrozenHealth, &mut Health)>,
+) {
+    for (entity, frozen_health, mut health) in monsters.iter_mut() {
+        if health.max == 0 {
+            continue;
+        }
+        health.current = frozen_health.0.min(health.max);
+        commands.entity(entity).remove::<FrozenHealth>();
+    }
+}
+
+fn chunk_seed(run_seed: u64, chunk: IVec2) -> u64 {
+    let chunk_key = ((chunk.x as u32 as u64) << 32) | chunk.y as u32 as u64;
+    run_seed ^ chunk_key.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+///
+/// Freezes or despawns the monsters of the chunks that were unloaded.
+///
+fn unload_chunk_monsters(
+    mut commands: Commands,
+    config: Res<SpawnerConfig>,
+    mut unloaded: MessageReader<ChunkUnloaded>,
+    monsters: Query<(Entity, &Monster, &Transform, &Health), Without<Dead>>,
+    mut frozen: ResMut<FrozenMonsters>,
+) {
+    for event in unloaded.read() {
+        if frozen.populated.remove(&event.chunk) && config.on_unload == UnloadPolicy::Freeze {
+            frozen.chunks.entry(event.chunk).or_default();
+        }
+        for (entity, monster, transform, health) in monsters.iter() {
+            let tile = world_to_tile_coords(transform.translation);
+            if tile_to_chunk_coords(tile) != event.chunk {
+                continue;
+            }
+            if config.on_unload == UnloadPolicy::Freeze {
+                frozen.chunks.entry(event.chunk).or_default().push(FrozenMonster {
+                    id: monster.id.clone(),
+                    tile,
+                    health: health.current,
+                });
+            }
+            commands.entity(entity).despawn();
+        }
+    }
+}
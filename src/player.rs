@@ -1,8 +1,4 @@
-use bevy::{
-    input::{keyboard::KeyCode, ButtonInput},
-    prelude::*
-};
-use bevy::input::mouse::{MouseWheel, MouseScrollUnit};
+use bevy::prelude::*;
 use bevy_spritesheet_animation::prelude::*;
 
 use crate::events::{
@@ -10,10 +6,20 @@ use crate::events::{
     MoveLegal
 };
 
-use crate::states::GameState;
+use crate::animation::{AnimatedCharacter, AnimationSets, CharacterAnimationPlugin};
+use crate::input::{ActionState, ActionSystems, InputAction};
+use crate::combat::{AttackProfile, CombatProfile, DamageType, Faction, Resistances};
+use crate::inventory::{Equipment, Inventory, ItemStack};
+use crate::map::{world_to_tile_coords, ActorTile};
+use crate::spells::Spellbook;
+use crate::stats::Attributes;
+use crate::states::{in_play, GameState};
+use crate::weather::Weather;
 
 const MOVE_SPEED: f32 = 20.0;
 const PLAYER_TILE_SIZE: f32 = 32.0;
+const PLAYER_ANIMATION_SET: &str = "animations/male_01.anim.ron";
+const PLAYER_MAX_WEIGHT: f32 = 40.0;
 
 //#[derive(Component, Inspectable)]
 #[derive(Component)]
@@ -26,81 +32,101 @@ pub struct Player {
 #[derive(Component)]
 pub struct PlayerCamera;
 
+///
+/// Direction the character is looking at. Used to pick the run and idle animations.
+///
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    #[default]
+    Down,
+    Up,
+    Left,
+    Right,
+}
+
+impl Facing {
+    ///
+    /// Picks the facing matching the dominant axis of the direction. Horizontal wins ties so
+    /// that pure diagonals show the side view.
+    ///
+    pub fn from_direction(direction: Vec2) -> Self {
+        if direction.x.abs() >= direction.y.abs() {
+            if direction.x < 0.0 { Facing::Left } else { Facing::Right }
+        } else if direction.y < 0.0 {
+            Facing::Down
+        } else {
+            Facing::Up
+        }
+    }
+
+    pub fn run_animation(&self) -> &'static str {
+        match self {
+            Facing::Down => "run_down",
+            Facing::Up => "run_up",
+            Facing::Left => "run_left",
+            Facing::Right => "run_right",
+        }
+    }
+
+    pub fn idle_animation(&self) -> &'static str {
+        match self {
+            Facing::Down => "idle_down",
+            Facing::Up => "idle_up",
+            Facing::Left => "idle_left",
+            Facing::Right => "idle_right",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(SpritesheetAnimationPlugin::default())
-            .add_systems(Startup, spawn_caracter)
-            .add_systems(PreUpdate, try_move_player)
+        if !app.is_plugin_added::<CharacterAnimationPlugin>() {
+            app.add_plugins(CharacterAnimationPlugin);
+        }
+        app.add_systems(Startup, spawn_caracter)
+            .add_systems(PreUpdate, try_move_player.after(ActionSystems).run_if(in_play))
             .add_systems(Update, (move_player, update_camera).chain())
-            .add_systems(Update, zoom_map.run_if(in_state(GameState::GameRunning)));
+            .add_systems(Update, zoom_map.run_if(in_play));
     }
 }
 
+///
+/// Reads the movement actions and sends a MoveEvent towards the combined direction. Diagonal
+/// movement is normalised so the player is not faster on diagonals. The animation follows the
+/// dominant axis of the movement and falls back to the idle animation of the last facing.
+/// The player walks slower through the weather that makes the overworld harder to cross.
+///
 fn try_move_player(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    library: Res<AnimationLibrary>,
+    actions: Res<ActionState>,
+    animation_sets: Res<AnimationSets>,
     time: Res<Time>,
-    mut player_query: Query<(&mut Sprite, &mut SpritesheetAnimation, &Transform, &Player)>,
+    weather: Res<Weather>,
+    mut player_query: Query<(&mut SpritesheetAnimation, &AnimatedCharacter, &mut Facing, &Transform, &Player)>,
     mut move_event: MessageWriter<MoveEvent>,
 ) {
-    let mut direction = Vec3::ZERO;
-    let Ok((mut _sprite, mut animation, player_transform, player)) = player_query.single_mut() else { return; };
-    let mut player_move_event = MoveEvent {
-        origin: Some(player_transform.translation),
-        destination: None,
-    };
-    let mut send_event = false;
-    let mut destination = player_transform.translation;
-    
-    if keyboard.pressed(KeyCode::KeyA) {
-        if let Some(run_animation_id) = library.animation_with_name("run_left") {
-            if animation.animation_id != run_animation_id {
-                animation.switch(run_animation_id);
-            }
-        }
-        destination.x -=  player.speed * player.size * time.delta_secs();
-        send_event = true;
-        direction -= Vec3::new(1.0, 0.0, 0.0);
-    } else if keyboard.pressed(KeyCode::KeyD) {
-        if let Some(run_animation_id) = library.animation_with_name("run_right") {
-            if animation.animation_id != run_animation_id {
-                animation.switch(run_animation_id);
-            }
-        }
-        destination.x +=  player.speed * player.size * time.delta_secs();
-        send_event = true;
-        direction += Vec3::new(1.0, 0.0, 0.0);
-    } else if keyboard.pressed(KeyCode::KeyS) {
-        if let Some(run_animation_id) = library.animation_with_name("run_down") {
-            if animation.animation_id != run_animation_id {
-                animation.switch(run_animation_id);
-            }
-        }
-        destination.y -=  player.speed * player.size * time.delta_secs();
-        send_event = true;
-        direction -= Vec3::new(0.0, 1.0, 0.0);
-    } else if keyboard.pressed(KeyCode::KeyW) {
-        if let Some(run_animation_id) = library.animation_with_name("run_up") {
-            if animation.animation_id != run_animation_id {
-                animation.switch(run_animation_id);
-            }
-        }
-        destination.y +=  player.speed * player.size * time.delta_secs();
-        send_event = true;
-        direction += Vec3::new(0.0, 1.0, 0.0);
-    }
-    if keyboard.any_just_released([KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyW]) {
-        send_event = false;
-        // Need to create an idle animation!
-    }
+    let Ok((mut animation, character, mut facing, player_transform, player)) = player_query.single_mut() else { return; };
 
-    if send_event {
-        player_move_event.destination = Some(destination);
-        move_event.write(player_move_event);
+    let input = actions.move_vector();
+    if input == Vec2::ZERO {
+        character.play(&animation_sets, &mut animation, facing.idle_animation());
+        return;
     }
+
+    let direction = input.normalize();
+    *facing = Facing::from_direction(direction);
+    character.play(&animation_sets, &mut animation, facing.run_animation());
+
+    let mut destination = player_transform.translation;
+    let speed = player.speed / weather.move_cost() as f32;
+    destination += (direction * speed * player.size * time.delta_secs()).extend(0.0);
+
+    move_event.write(MoveEvent {
+        origin: Some(player_transform.translation),
+        destination: Some(destination),
+    });
 }
 
 fn update_camera(
@@ -122,74 +148,61 @@ fn update_camera(
 fn spawn_caracter(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    mut library: ResMut<AnimationLibrary>,
 ) {
     commands.spawn((Camera2d::default(), PlayerCamera));
-    
-    let spritesheet = Spritesheet::new(3, 4);
-
-    // Move right
-    let run_right_clip = Clip::from_frames(spritesheet.row(2));
-    let run_right_clip_id = library.register_clip(run_right_clip);
-    let run_animation_right = Animation::from_clip(run_right_clip_id);
-    let run_animation_right_id = library.register_animation(run_animation_right);
-    library.name_animation(run_animation_right_id, "run_right").unwrap();
-
-    // Move left
-    let run_left_clip = Clip::from_frames(spritesheet.row(1));
-    let run_left_clip_id = library.register_clip(run_left_clip);
-    let run_animation_left = Animation::from_clip(run_left_clip_id);
-    let run_animation_left_id = library.register_animation(run_animation_left);
-    library.name_animation(run_animation_left_id, "run_left").unwrap();
-
-    // Move up
-    let run_up_clip = Clip::from_frames(spritesheet.row(3));
-    let run_up_clip_id = library.register_clip(run_up_clip);
-    let run_animation_up = Animation::from_clip(run_up_clip_id);
-    let run_animation_up_id = library.register_animation(run_animation_up);
-    library.name_animation(run_animation_up_id, "run_up").unwrap();
-
-    // Move down
-    let run_down_clip = Clip::from_frames(spritesheet.row(0));
-    let run_down_clip_id = library.register_clip(run_down_clip);
-    let run_animation_down = Animation::from_clip(run_down_clip_id);
-    let run_animation_down_id = library.register_animation(run_animation_down);
-    library.name_animation(run_animation_down_id, "run_down").unwrap();
-
-    // Spawn the player sprite with the animations
-    let image = asset_server.load("Male 01-1.png");
-
-    let atlas = TextureAtlas {
-        layout: atlas_layouts.add(Spritesheet::new(3,4).atlas_layout(32, 32)),
-        ..default()
-    };
-
-    let mut player = commands.spawn((
-        Sprite::from_atlas_image(image, atlas),
-        SpritesheetAnimation::from_id(run_animation_down_id),
-        Transform::from_translation(Vec3::Z * 10.0) * Transform::from_scale(Vec3::splat(1.0))
+
+    // The sprite and its animations are attached once the animation set is loaded.
+    commands.spawn((
+        AnimatedCharacter {
+            set: asset_server.load(PLAYER_ANIMATION_SET),
+            initial: Facing::default().idle_animation().to_string(),
+        },
+        Transform::from_translation(Vec3::Z * 10.0) * Transform::from_scale(Vec3::splat(1.0)),
+        Player {
+            speed: MOVE_SPEED,
+            size: PLAYER_TILE_SIZE,
+        },
+        Facing::default(),
+        Name::new("Player"),
+        Attributes::new(6, 6, 5, 6),
+        Faction::Player,
+        Resistances::default(),
+        CombatProfile {
+            melee: AttackProfile { damage_type: DamageType::Physical, bonus_damage: 2, range: 1 },
+            ranged: Some(AttackProfile { damage_type: DamageType::Physical, bonus_damage: 0, range: 6 }),
+        },
+        Spellbook {
+            spells: ["firebolt", "heal", "blink", "frost_path", "poison_cloud"].map(String::from).to_vec(),
+        },
+        Inventory::new(PLAYER_MAX_WEIGHT, vec![
+            ItemStack::new("healing_potion", 3),
+            ItemStack::new("mana_potion", 2),
+            ItemStack::new("leather_armor", 1),
+            ItemStack::new("short_sword", 1),
+        ]),
+        Equipment::default(),
     ));
-    player.insert(Player {
-        speed: MOVE_SPEED,
-        size: PLAYER_TILE_SIZE,
-    });
 }
 
 fn move_player(
     mut q: Query<&mut Transform, With<Player>>,
+    actors: Query<ActorTile, (With<Faction>, Without<Player>)>,
     mut valid_move: MessageReader<MoveLegal>,
 ) {
     for event in valid_move.read() {
-        if event.destination.is_none() {
-            return;
+        // A refused move has no destination, the moves after it still apply.
+        let Some(destination) = event.destination else { continue; };
+        // Other actors block the way, moving into a hostile one is handled as an attack.
+        let destination_tile = world_to_tile_coords(destination);
+        if actors.iter().any(|actor| actor.tile() == destination_tile) {
+            continue;
         }
         if event.legal_move {
             //info!("Moving player to {:?}", event.destination);
             for mut transform in q.iter_mut() {
                 transform.translation = Vec3::new(
-                    event.destination.unwrap().x, 
-                    event.destination.unwrap().y,
+                    destination.x,
+                    destination.y,
                     10.0,
                 );
             }
@@ -199,8 +212,7 @@ fn move_player(
 
 fn zoom_map(
     mut query_camera: Query<&mut Projection, With<PlayerCamera>>,
-    mut scroll_evr: MessageReader<MouseWheel>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     game_state: Res<State<GameState>>,
 ) {
     match game_state.get() {
@@ -210,25 +222,12 @@ fn zoom_map(
         _ => {},
     }
     let mut projection = query_camera.single_mut().unwrap();
-    // Camera zoom controls
+    // Camera zoom controls (mouse wheel, keys and gamepad triggers are bound in InputBindings)
     if let Projection::Orthographic(projection2d) = &mut *projection {
-        for ev in scroll_evr.read() {
-            if ev.unit == MouseScrollUnit::Line {
-                if ev.y == -1.0 {
-                    // zoom in
-                    projection2d.scale *= 1.25;
-                } else if ev.y == 1.0 {
-                    // zoom out
-                    projection2d.scale /= 1.25;                
-                }
-            }
-        }           
-        if keyboard.just_pressed(KeyCode::KeyQ) {
-            // zoom in
+        if actions.just_pressed(InputAction::ZoomOut) {
             projection2d.scale *= 1.25;
-        } else if keyboard.just_pressed(KeyCode::KeyE) {
-            // zoom out
+        } else if actions.just_pressed(InputAction::ZoomIn) {
             projection2d.scale /= 1.25;
         }    
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+///
+/// Random number generator of the current run. Every gameplay roll (combat, loot, spawns...)
+/// draws from it so that a run can be reproduced from its seed.
+///
+#[derive(Resource, Debug)]
+pub struct RunRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl Default for RunRng {
+    fn default() -> Self {
+        RunRng::from_seed(rand::rng().random())
+    }
+}
+
+impl RunRng {
+    pub fn from_seed(seed: u64) -> Self {
+        RunRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    ///
+    /// Percentage roll, true with a `chance` in 100 probability.
+    ///
+    pub fn chance(&mut self, chance: i32) -> bool {
+        self.rng.random_range(0..100) < chance
+    }
+}
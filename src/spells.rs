@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_ecs_tilemap::prelude::TileStorage;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass, EguiTextureHandle};
+use serde::Deserialize;
+
+use crate::assets::RonAssetAppExt;
+use crate::combat::{actor_name, chebyshev_distance, ApplyDamage, DamageType, Dead};
+use crate::constants::TILE_SIZE_PX;
+use crate::game_log::GameLog;
+use crate::input::{ActionState, InputAction};
+use crate::map::{tile_coords_to_world, tile_grid::TileGrid, world_to_tile_coords, ActorTile};
+use crate::player::{Player, PlayerCamera};
+use crate::states::in_play;
+use crate::stats::{Health, Mana, StatusEffect, StatusEffects};
+use crate::tile_type::GroundTiles;
+use crate::turn::{PassTurn, TurnSystems, TurnTick};
+
+const SPELL_LIBRARY_PATH: &str = "spells/spells.spells.ron";
+// Mana regained by every caster at the end of each turn.
+const MANA_REGEN_PER_TURN: i32 = 1;
+// Depth of the targeting markers, above the tilemap and below the characters.
+const TARGETING_Z: f32 = 5.0;
+const HOTBAR_ICON_SIZE: f32 = 40.0;
+
+///
+/// All the spells of the game, loaded from a `*.spells.ron` file.
+///
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct SpellLibrary {
+    pub spells: Vec<SpellDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpellDef {
+    pub id: String,
+    pub name: String,
+    /// Icon path, relative to the assets folder.
+    pub icon: String,
+    pub mana_cost: i32,
+    /// Maximum distance in tiles between the caster and the targeted tile.
+    #[serde(default)]
+    pub range: i32,
+    #[serde(default)]
+    pub area: AreaShape,
+    pub targeting: TargetingMode,
+    pub effects: Vec<SpellEffect>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum AreaShape {
+    /// Only the targeted tile.
+    #[default]
+    Single,
+    /// Every tile within `radius` (square distance) of the target.
+    Square { radius: i32 },
+    /// Every tile within `radius` (round distance) of the target.
+    Circle { radius: i32 },
+    /// Tiles on a straight line from the caster to the target, `length` tiles long.
+    Line { length: i32 },
+    /// The target and `radius` tiles in each cardinal direction.
+    Cross { radius: i32 },
+}
+
+impl AreaShape {
+    pub fn tiles(&self, caster: IVec2, target: IVec2) -> Vec<IVec2> {
+        match *self {
+            AreaShape::Single => vec![target],
+            AreaShape::Square { radius } => {
+                let mut tiles = Vec::new();
+                for y in -radius..=radius {
+                    for x in -radius..=radius {
+                        tiles.push(target + IVec2::new(x, y));
+                    }
+                }
+                tiles
+            }
+            AreaShape::Circle { radius } => {
+                let mut tiles = Vec::new();
+                for y in -radius..=radius {
+                    for x in -radius..=radius {
+                        if x * x + y * y <= radius * radius {
+                            tiles.push(target + IVec2::new(x, y));
+                        }
+                    }
+                }
+                tiles
+            }
+            AreaShape::Line { length } => {
+                let direction = (target - caster).as_vec2();
+                if direction == Vec2::ZERO {
+                    return vec![target];
+                }
+                let direction = direction / direction.abs().max_element();
+                (1..=length)
+                    .map(|step| caster + (direction * step as f32).round().as_ivec2())
+                    .collect()
+            }
+            AreaShape::Cross { radius } => {
+                let mut tiles = vec![target];
+                for step in 1..=radius {
+                    tiles.push(target + IVec2::new(step, 0));
+                    tiles.push(target + IVec2::new(-step, 0));
+                    tiles.push(target + IVec2::new(0, step));
+                    tiles.push(target + IVec2::new(0, -step));
+                }
+                tiles
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TargetingMode {
+    /// Cast on the caster's own tile, no cursor.
+    OnSelf,
+    /// Any tile in range and in sight.
+    Tile,
+    /// A tile in range and in sight holding an actor.
+    Actor,
+    /// An empty walkable tile in range and in sight.
+    EmptyTile,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SpellEffect {
+    Damage { amount: i32, damage_type: DamageType },
+    Heal { amount: i32 },
+    /// Moves the caster to the targeted tile.
+    Teleport,
+    /// Replaces every `from` ground in the area by the matching `to` ground.
+    ChangeTerrain { changes: Vec<(GroundTiles, GroundTiles)> },
+    ApplyStatus(StatusEffect),
+}
+
+///
+/// Spells known by an actor, in hotbar order.
+///
+#[derive(Component, Debug, Default, Clone)]
+pub struct Spellbook {
+    pub spells: Vec<String>,
+}
+
+///
+/// Spell waiting for the player to pick a target.
+///
+#[derive(Resource, Debug, Default)]
+pub struct SpellTargeting {
+    pub spell: Option<String>,
+    pub cursor: Option<IVec2>,
+}
+
+#[derive(Resource, Debug, Default)]
+struct SpellLibraryHandle(Handle<SpellLibrary>);
+
+#[derive(Resource, Debug, Default)]
+struct SpellIcons {
+    textures: HashMap<String, (Handle<Image>, egui::TextureId)>,
+}
+
+///
+/// Marker of the sprites previewing the area of the spell being targeted.
+///
+#[derive(Component)]
+struct TargetingMarker;
+
+///
+/// A spell cast, after its target has been chosen.
+///
+#[derive(Message, Debug, Clone)]
+pub struct CastSpell {
+    pub caster: Entity,
+    pub spell: String,
+    pub target: IVec2,
+}
+
+pub struct SpellsPlugin;
+
+impl Plugin for SpellsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.add_ron_asset::<SpellLibrary>(&["spells.ron"])
+            .init_resource::<SpellLibraryHandle>()
+            .init_resource::<SpellTargeting>()
+            .init_resource::<SpellIcons>()
+            .add_message::<CastSpell>()
+            .add_systems(Startup, load_spell_library)
+            .add_systems(Update, (
+                (
+                    (select_spell, update_targeting).run_if(in_play),
+                    resolve_spell_casts,
+                )
+                    .chain()
+                    .before(TurnSystems),
+                regenerate_mana.after(TurnSystems),
+            ))
+            .add_systems(EguiPrimaryContextPass, hotbar_ui);
+    }
+}
+
+impl SpellLibrary {
+    pub fn get(&self, id: &str) -> Option<&SpellDef> {
+        self.spells.iter().find(|spell| spell.id == id)
+    }
+}
+
+fn load_spell_library(
+    asset_server: Res<AssetServer>,
+    mut library_handle: ResMut<SpellLibraryHandle>,
+) {
+    library_handle.0 = asset_server.load(SPELL_LIBRARY_PATH);
+}
+
+///
+/// Hotbar keys pick a spell. Self spells are cast at once, the others enter targeting mode.
+///
+fn select_spell(
+    actions: Res<ActionState>,
+    library_handle: Res<SpellLibraryHandle>,
+    libraries: Res<Assets<SpellLibrary>>,
+    player_query: Query<(Entity, &Spellbook, &Transform), (With<Player>, Without<Dead>)>,
+    mut targeting: ResMut<SpellTargeting>,
+    mut casts: MessageWriter<CastSpell>,
+) {
+    let Some(slot) = InputAction::CAST_SPELL.iter().position(|action| actions.just_pressed(*action)) else { return; };
+    let Ok((player, spellbook, transform)) = player_query.single() else { return; };
+    let Some(library) = libraries.get(&library_handle.0) else { return; };
+    let Some(spell) = spellbook.spells.get(slot).and_then(|id| library.get(id)) else { return; };
+
+    start_casting(player, spell, world_to_tile_coords(transform.translation), &mut targeting, &mut casts);
+}
+
+fn start_casting(
+    caster: Entity,
+    spell: &SpellDef,
+    caster_tile: IVec2,
+    targeting: &mut SpellTargeting,
+    casts: &mut MessageWriter<CastSpell>,
+) {
+    if spell.targeting == TargetingMode::OnSelf {
+        targeting.spell = None;
+        casts.write(CastSpell { caster, spell: spell.id.clone(), target: caster_tile });
+    } else {
+        targeting.spell = Some(spell.id.clone());
+        targeting.cursor = Some(caster_tile);
+    }
+}
+
+///
+/// Moves the targeting cursor with the mouse and casts the spell on confirm. The area of the
+/// spell is previewed in green when the target is valid, red otherwise.
+///
+fn update_targeting(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut contexts: EguiContexts,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
+    library_handle: Res<SpellLibraryHandle>,
+    libraries: Res<Assets<SpellLibrary>>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Dead>)>,
+    actors: Query<ActorTile, (With<Health>, Without<Dead>)>,
+    tile_grid: TileGrid,
+    markers: Query<Entity, With<TargetingMarker>>,
+    mut targeting: ResMut<SpellTargeting>,
+    mut log: ResMut<GameLog>,
+    mut casts: MessageWriter<CastSpell>,
+    mut previewed: Local<Option<(IVec2, bool)>>,
+) {
+    let spell = targeting
+        .spell
+        .as_ref()
+        .and_then(|id| libraries.get(&library_handle.0)?.get(id));
+    let (Some(spell), Ok((player, player_transform))) = (spell, player_query.single()) else {
+        if previewed.take().is_some() {
+            markers.iter().for_each(|marker| commands.entity(marker).despawn());
+        }
+        return;
+    };
+
+    if actions.just_pressed(InputAction::Cancel) {
+        targeting.spell = None;
+        return;
+    }
+
+    let pointer_over_ui = contexts.ctx_mut().is_ok_and(|ctx| ctx.is_pointer_over_area());
+    if !pointer_over_ui
+        && let (Ok(window), Ok((camera, camera_transform))) = (window_query.single(), camera_query.single())
+        && let Some(world_pos) = window
+            .cursor_position()
+            .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    {
+        targeting.cursor = Some(world_to_tile_coords(world_pos.extend(0.0)));
+    }
+    let Some(cursor) = targeting.cursor else { return; };
+
+    let caster_tile = world_to_tile_coords(player_transform.translation);
+    let actor_on_target = actors.iter().any(|actor| actor.tile() == cursor);
+    let valid = target_is_valid(spell, caster_tile, cursor, actor_on_target, &tile_grid);
+
+    if *previewed != Some((cursor, valid)) {
+        *previewed = Some((cursor, valid));
+        markers.iter().for_each(|marker| commands.entity(marker).despawn());
+        let color = if valid { Color::srgba(0.2, 0.9, 0.3, 0.35) } else { Color::srgba(0.9, 0.2, 0.2, 0.35) };
+        for tile in spell.area.tiles(caster_tile, cursor) {
+            commands.spawn((
+                TargetingMarker,
+                Sprite::from_color(color, Vec2::splat(TILE_SIZE_PX)),
+                Transform::from_translation(tile_coords_to_world(tile, TARGETING_Z)),
+            ));
+        }
+    }
+
+    if actions.just_pressed(InputAction::Confirm) && !pointer_over_ui {
+        if valid {
+            casts.write(CastSpell { caster: player, spell: spell.id.clone(), target: cursor });
+            targeting.spell = None;
+        } else {
+            log.info("Invalid target.");
+        }
+    }
+}
+
+fn target_is_valid(
+    spell: &SpellDef,
+    caster_tile: IVec2,
+    target: IVec2,
+    actor_on_target: bool,
+    tile_grid: &TileGrid,
+) -> bool {
+    if chebyshev_distance(caster_tile, target) > spell.range || !tile_grid.line_of_sight(caster_tile, target) {
+        return false;
+    }
+    match spell.targeting {
+        TargetingMode::OnSelf => target == caster_tile,
+        TargetingMode::Tile => true,
+        TargetingMode::Actor => actor_on_target,
+        TargetingMode::EmptyTile => !actor_on_target && tile_grid.walkable(target) == Some(true),
+    }
+}
+
+///
+/// Pays the mana cost and applies every effect of the spells cast this frame.
+///
+fn resolve_spell_casts(
+    mut cast_events: MessageReader<CastSpell>,
+    library_handle: Res<SpellLibraryHandle>,
+    libraries: Res<Assets<SpellLibrary>>,
+    mut casters: Query<(&mut Mana, Option<&Name>), Without<Dead>>,
+    // Tilemaps are excluded so that moving actors does not conflict with the tile grid. The
+    // transforms are only written by teleports, the tiles of the actors are read apart.
+    mut actors: ParamSet<(
+        Query<(Entity, ActorTile, &mut Health, &mut StatusEffects, Option<&Name>), (Without<Dead>, Without<TileStorage>)>,
+        Query<&mut Transform, (Without<Dead>, Without<TileStorage>)>,
+    )>,
+    mut tile_grid: TileGrid,
+    mut log: ResMut<GameLog>,
+    mut damage: MessageWriter<ApplyDamage>,
+    mut pass_turn: MessageWriter<PassTurn>,
+    player_query: Query<(), With<Player>>,
+) {
+    let Some(library) = libraries.get(&library_handle.0) else {
+        cast_events.clear();
+        return;
+    };
+
+    for cast in cast_events.read() {
+        let Some(spell) = library.get(&cast.spell) else { continue; };
+        let Ok((mut mana, caster_name)) = casters.get_mut(cast.caster) else { continue; };
+        let caster_name = actor_name(caster_name);
+        if mana.current < spell.mana_cost {
+            log.info(format!("{} does not have enough mana for {}.", caster_name, spell.name));
+            continue;
+        }
+        mana.current -= spell.mana_cost;
+        log.combat(format!("{} casts {}.", caster_name, spell.name));
+
+        let Ok(caster_tile) = actors.p0().get(cast.caster).map(|(_, tile, _, _, _)| tile.tile()) else { continue; };
+        let area = spell.area.tiles(caster_tile, cast.target);
+
+        for effect in &spell.effects {
+            match effect {
+                SpellEffect::Damage { amount, damage_type } => {
+                    for (entity, tile, _, _, _) in actors.p0().iter() {
+                        if area.contains(&tile.tile()) {
+                            damage.write(ApplyDamage {
+                                source: Some(cast.caster),
+                                target: entity,
+                                amount: *amount,
+                                damage_type: *damage_type,
+                            });
+                        }
+                    }
+                }
+                SpellEffect::Heal { amount } => {
+                    for (_, tile, mut health, _, name) in actors.p0().iter_mut() {
+                        if area.contains(&tile.tile()) {
+                            health.current = (health.current + amount).min(health.max);
+                            log.combat(format!("{} is healed for {}.", actor_name(name), amount));
+                        }
+                    }
+                }
+                SpellEffect::Teleport => {
+                    if let Ok(mut transform) = actors.p1().get_mut(cast.caster) {
+                        transform.translation = tile_coords_to_world(cast.target, transform.translation.z);
+                    }
+                }
+                SpellEffect::ChangeTerrain { changes } => {
+                    for tile in &area {
+                        let Some(ground) = tile_grid.ground_at(*tile) else { continue; };
+                        if let Some((_, to)) = changes.iter().find(|(from, _)| *from == ground) {
+                            tile_grid.set_ground(*tile, *to);
+                        }
+                    }
+                }
+                SpellEffect::ApplyStatus(status) => {
+                    for (_, tile, _, mut effects, name) in actors.p0().iter_mut() {
+                        if area.contains(&tile.tile()) {
+                            effects.apply(*status);
+                            log.combat(format!("{} is affected by {:?}.", actor_name(name), status.kind));
+                        }
+                    }
+                }
+            }
+        }
+
+        if player_query.contains(cast.caster) {
+            pass_turn.write(PassTurn);
+        }
+    }
+}
+
+fn regenerate_mana(
+    mut turns: MessageReader<TurnTick>,
+    mut casters: Query<&mut Mana, (With<Spellbook>, Without<Dead>)>,
+) {
+    let elapsed = turns.read().count() as i32;
+    if elapsed == 0 {
+        return;
+    }
+    for mut mana in casters.iter_mut() {
+        mana.current = (mana.current + elapsed * MANA_REGEN_PER_TURN).min(mana.max);
+    }
+}
+
+///
+/// Hotbar at the bottom of the screen showing the player's spells. Clicking a spell selects it
+/// like its hotbar key.
+///
+fn hotbar_ui(
+    mut contexts: EguiContexts,
+    asset_server: Res<AssetServer>,
+    library_handle: Res<SpellLibraryHandle>,
+    libraries: Res<Assets<SpellLibrary>>,
+    player_query: Query<(Entity, &Spellbook, &Transform, &Mana), With<Player>>,
+    mut icons: ResMut<SpellIcons>,
+    mut targeting: ResMut<SpellTargeting>,
+    mut casts: MessageWriter<CastSpell>,
+) -> Result {
+    let Ok((player, spellbook, transform, mana)) = player_query.single() else { return Ok(()); };
+    let Some(library) = libraries.get(&library_handle.0) else { return Ok(()); };
+
+    // Icons must be registered with egui before the context is borrowed for drawing.
+    for id in &spellbook.spells {
+        let Some(spell) = library.get(id) else { continue; };
+        if !icons.textures.contains_key(id) {
+            let handle: Handle<Image> = asset_server.load(&spell.icon);
+            let texture_id = contexts.add_image(EguiTextureHandle::Strong(handle.clone()));
+            icons.textures.insert(id.clone(), (handle, texture_id));
+        }
+    }
+
+    let ctx = contexts.ctx_mut()?;
+    egui::Area::new(egui::Id::new("spell_hotbar"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -8.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (slot, id) in spellbook.spells.iter().enumerate().take(InputAction::CAST_SPELL.len()) {
+                    let Some(spell) = library.get(id) else { continue; };
+                    let loaded = icons
+                        .textures
+                        .get(id)
+                        .filter(|(handle, _)| asset_server.is_loaded(handle));
+                    let selected = targeting.spell.as_deref() == Some(id.as_str());
+                    let button = match loaded {
+                        Some((_, texture_id)) => egui::Button::image(egui::Image::new(
+                            egui::load::SizedTexture::new(*texture_id, [HOTBAR_ICON_SIZE, HOTBAR_ICON_SIZE]),
+                        )),
+                        None => egui::Button::new(format!("{}\n{}", slot + 1, spell.name)),
+                    }
+                    .selected(selected);
+
+                    let response = ui
+                        .add_enabled(mana.current >= spell.mana_cost, button)
+                        .on_hover_text(format!("[{}] {} - {} mana", slot + 1, spell.name, spell.mana_cost));
+                    if response.clicked() {
+                        start_casting(player, spell, world_to_tile_coords(transform.translation), &mut targeting, &mut casts);
+                    }
+                }
+                ui.label(format!("Mana {}/{}", mana.current, mana.max));
+            });
+        });
+    Ok(())
+}
@@ -5,4 +5,16 @@ pub enum GameState {
     #[default]
     GameRunning,
     DirtyMap,
-}
\ No newline at end of file
+    /// The overworld is being generated behind a loading screen.
+    GeneratingWorld,
+    GameOver,
+    /// The player is below the overworld, in a dungeon level.
+    Dungeon,
+}
+
+///
+/// Run condition of the systems driven by the player, true on the overworld and in dungeons.
+///
+pub fn in_play(state: Option<Res<State<GameState>>>) -> bool {
+    matches!(state.as_deref().map(State::get), Some(GameState::GameRunning | GameState::Dungeon))
+}
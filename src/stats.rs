@@ -0,0 +1,389 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+use serde::Deserialize;
+
+use crate::combat::{ApplyDamage, DamageType};
+use crate::turn::{TurnSystems, TurnTick};
+
+///
+/// Base attributes of an actor. Players, monsters and NPCs all use the same model; adding
+/// this component brings every other stat component with it.
+///
+/// When read from data files, missing attributes are 0 so that the same type can describe
+/// modifiers (`(strength: 2)`).
+///
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Deserialize)]
+#[reflect(Component)]
+#[require(DerivedStats, Health, Mana, Experience, StatusEffects)]
+pub struct Attributes {
+    #[serde(default)]
+    pub strength: i32,
+    #[serde(default)]
+    pub dexterity: i32,
+    #[serde(default)]
+    pub intelligence: i32,
+    #[serde(default)]
+    pub vitality: i32,
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes {
+            strength: 5,
+            dexterity: 5,
+            intelligence: 5,
+            vitality: 5,
+        }
+    }
+}
+
+impl Attributes {
+    pub fn new(strength: i32, dexterity: i32, intelligence: i32, vitality: i32) -> Self {
+        Attributes { strength, dexterity, intelligence, vitality }
+    }
+
+    pub fn add(&self, other: &Attributes) -> Attributes {
+        Attributes {
+            strength: self.strength + other.strength,
+            dexterity: self.dexterity + other.dexterity,
+            intelligence: self.intelligence + other.intelligence,
+            vitality: self.vitality + other.vitality,
+        }
+    }
+
+    pub const ZERO: Attributes = Attributes { strength: 0, dexterity: 0, intelligence: 0, vitality: 0 };
+}
+
+///
+/// Stats computed from the attributes, the level and the active status effects.
+/// Never edit these directly, they are recomputed by [`update_derived_stats`].
+///
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct DerivedStats {
+    pub max_hp: i32,
+    pub max_mana: i32,
+    pub attack: i32,
+    pub defence: i32,
+    /// Chance to avoid a hit, in percent.
+    pub evasion: i32,
+}
+
+impl DerivedStats {
+    pub fn compute(attributes: &Attributes, level: u32) -> Self {
+        let level = level as i32;
+        DerivedStats {
+            max_hp: (10 + attributes.vitality * 5 + level * 2).max(1),
+            max_mana: (5 + attributes.intelligence * 3 + level).max(0),
+            attack: (attributes.strength * 2 + attributes.dexterity / 2).max(0),
+            defence: (attributes.vitality + attributes.strength / 2).max(0),
+            evasion: (attributes.dexterity * 2).clamp(0, 75),
+        }
+    }
+}
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+}
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Mana {
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct Experience {
+    pub level: u32,
+    /// Experience gathered towards the next level.
+    pub current: u64,
+    pub total: u64,
+    /// Attribute points earned by leveling up and not spent yet.
+    pub unspent_points: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience {
+            level: 1,
+            current: 0,
+            total: 0,
+            unspent_points: 0,
+        }
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum LevelCurve {
+    /// `base + increment * (level - 1)` experience per level.
+    Linear { base: f64, increment: f64 },
+    /// `base * factor ^ (level - 1)` experience per level.
+    Exponential { base: f64, factor: f64 },
+}
+
+///
+/// How much experience each level requires and what a level up grants.
+///
+#[derive(Resource, Reflect, InspectorOptions, Debug, Clone)]
+#[reflect(Resource, InspectorOptions)]
+pub struct LevelingConfig {
+    pub curve: LevelCurve,
+    pub max_level: u32,
+    pub attribute_points_per_level: u32,
+}
+
+impl Default for LevelingConfig {
+    fn default() -> Self {
+        LevelingConfig {
+            curve: LevelCurve::Exponential { base: 100.0, factor: 1.5 },
+            max_level: 50,
+            attribute_points_per_level: 3,
+        }
+    }
+}
+
+impl LevelingConfig {
+    ///
+    /// Experience needed to go from `level` to `level + 1`.
+    ///
+    pub fn experience_to_next(&self, level: u32) -> u64 {
+        let steps = level.saturating_sub(1) as f64;
+        let needed = match self.curve {
+            LevelCurve::Linear { base, increment } => base + increment * steps,
+            LevelCurve::Exponential { base, factor } => base * factor.powf(steps),
+        };
+        needed.max(1.0).round() as u64
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StatusKind {
+    /// Loses `magnitude` HP every turn.
+    Poison,
+    /// Regains `magnitude` HP every turn.
+    Regeneration,
+    /// Strength and dexterity lowered by `magnitude`.
+    Weakness,
+    /// All attributes raised by `magnitude`.
+    Blessed,
+    /// Cannot act while active.
+    Stunned,
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub magnitude: i32,
+    pub remaining_turns: u32,
+}
+
+impl StatusEffect {
+    fn attribute_modifier(&self) -> Attributes {
+        match self.kind {
+            StatusKind::Weakness => Attributes::new(-self.magnitude, -self.magnitude, 0, 0),
+            StatusKind::Blessed => Attributes::new(self.magnitude, self.magnitude, self.magnitude, self.magnitude),
+            _ => Attributes::ZERO,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    ///
+    /// Adds an effect. Applying an effect that is already active refreshes its duration and
+    /// keeps the strongest magnitude instead of stacking.
+    ///
+    pub fn apply(&mut self, effect: StatusEffect) {
+        if let Some(existing) = self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            existing.remaining_turns = existing.remaining_turns.max(effect.remaining_turns);
+            existing.magnitude = existing.magnitude.max(effect.magnitude);
+        } else {
+            self.effects.push(effect);
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    fn attribute_modifier(&self) -> Attributes {
+        self.effects
+            .iter()
+            .fold(Attributes::ZERO, |total, effect| total.add(&effect.attribute_modifier()))
+    }
+}
+
+///
+/// Extra attributes granted by other systems (equipment, auras...). Summed with the base
+/// attributes and the status effects when the derived stats are computed.
+///
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct AttributeBonus(pub Attributes);
+
+impl Default for AttributeBonus {
+    fn default() -> Self {
+        AttributeBonus(Attributes::ZERO)
+    }
+}
+
+///
+/// Ask for an actor to gain experience.
+///
+#[derive(Message, Debug, Clone, Copy)]
+pub struct GainExperience {
+    pub entity: Entity,
+    pub amount: u64,
+}
+
+#[derive(Message, Debug, Clone, Copy)]
+pub struct LevelUp {
+    pub entity: Entity,
+    pub level: u32,
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelingConfig>()
+            .register_type::<LevelingConfig>()
+            .register_type::<Attributes>()
+            .register_type::<DerivedStats>()
+            .register_type::<Health>()
+            .register_type::<Mana>()
+            .register_type::<Experience>()
+            .register_type::<StatusEffects>()
+            .register_type::<AttributeBonus>()
+            .add_message::<GainExperience>()
+            .add_message::<LevelUp>()
+            .add_systems(Update, (
+                gain_experience,
+                tick_status_effects.after(TurnSystems),
+                update_derived_stats,
+            ).chain());
+    }
+}
+
+///
+/// Recomputes the derived stats when anything they depend on changed, and keeps health and
+/// mana within their new maximum. Newly spawned actors start at full health and mana.
+///
+fn update_derived_stats(
+    mut actors: Query<
+        (
+            Ref<Attributes>,
+            Option<Ref<AttributeBonus>>,
+            Ref<Experience>,
+            Ref<StatusEffects>,
+            &mut DerivedStats,
+            &mut Health,
+            &mut Mana,
+        ),
+        Or<(Changed<Attributes>, Changed<AttributeBonus>, Changed<Experience>, Changed<StatusEffects>)>,
+    >,
+) {
+    for (attributes, bonus, experience, effects, mut derived, mut health, mut mana) in actors.iter_mut() {
+        let mut effective = attributes.add(&effects.attribute_modifier());
+        if let Some(bonus) = bonus {
+            effective = effective.add(&bonus.0);
+        }
+        let new_stats = DerivedStats::compute(&effective, experience.level);
+        if *derived != new_stats {
+            *derived = new_stats;
+        }
+
+        if attributes.is_added() {
+            health.current = new_stats.max_hp;
+            mana.current = new_stats.max_mana;
+        }
+        health.max = new_stats.max_hp;
+        health.current = health.current.min(health.max);
+        mana.max = new_stats.max_mana;
+        mana.current = mana.current.min(mana.max);
+    }
+}
+
+fn gain_experience(
+    mut gains: MessageReader<GainExperience>,
+    config: Res<LevelingConfig>,
+    mut actors: Query<&mut Experience>,
+    mut level_up: MessageWriter<LevelUp>,
+) {
+    for gain in gains.read() {
+        let Ok(mut experience) = actors.get_mut(gain.entity) else { continue; };
+        experience.total += gain.amount;
+        if experience.level >= config.max_level {
+            continue;
+        }
+        experience.current += gain.amount;
+
+        loop {
+            let needed = config.experience_to_next(experience.level);
+            if experience.current < needed || experience.level >= config.max_level {
+                break;
+            }
+            experience.current -= needed;
+            experience.level += 1;
+            experience.unspent_points += config.attribute_points_per_level;
+            level_up.write(LevelUp { entity: gain.entity, level: experience.level });
+        }
+    }
+}
+
+///
+/// Applies the per-turn part of the status effects and removes the expired ones. Poison deals
+/// its damage like an attack would, so resistances, the combat log and deaths apply to it.
+///
+fn tick_status_effects(
+    mut turns: MessageReader<TurnTick>,
+    mut actors: Query<(Entity, &mut StatusEffects, &mut Health)>,
+    mut damage: MessageWriter<ApplyDamage>,
+) {
+    let elapsed = turns.read().count();
+    if elapsed == 0 {
+        return;
+    }
+
+    for (entity, mut effects, mut health) in actors.iter_mut() {
+        if effects.effects.is_empty() {
+            continue;
+        }
+        for _ in 0..elapsed {
+            for effect in effects.effects.iter_mut() {
+                match effect.kind {
+                    StatusKind::Poison => {
+                        damage.write(ApplyDamage {
+                            source: None,
+                            target: entity,
+                            amount: effect.magnitude,
+                            damage_type: DamageType::Poison,
+                        });
+                    }
+                    StatusKind::Regeneration => {
+                        health.current = (health.current + effect.magnitude).min(health.max);
+                    }
+                    _ => {}
+                }
+                effect.remaining_turns = effect.remaining_turns.saturating_sub(1);
+            }
+            effects.effects.retain(|effect| effect.remaining_turns > 0);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use std::convert::From;
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum GroundTiles {
     LightGreyObsidian = 0,
     MediumGreyObsidian = 1,
@@ -357,6 +359,75 @@ impl From<u32> for GroundTiles {
 }
 
 
+impl GroundTiles {
+    ///
+    /// Whether an actor can stand on this ground tile. Water, lava, mountains and peaks block movement.
+    ///
+    pub fn is_walkable(&self) -> bool {
+        use GroundTiles::*;
+        !matches!(self,
+            LightShallowWater | MediumShallowWater | DarkShallowWater
+            | LightDeepWater | MediumDeepWater | DarkDeepWater
+            | Water1 | Water2 | Water3
+            | LightWater1 | LightWater2 | LightWater3
+            | MediumBlueWater1 | MediumBlueWater2 | MediumBlueWater3
+            | Lava1 | Lava2 | Lava3
+            | BedRock | SnowyPeak
+            | LightRockSnowyMountain | MediumRockSnowyMountain | DarkRockSnowyMountain
+            | LightSnowyMountain | MediumSnowyMountain | DarkSnowyMountain
+            | LightGrassyMountain | MediumGrassyMountain | DarkGrassyMountain
+            | LightGrassyVolcanoMountain | MediumGrassyVolcanoMountain | DarkGrassyVolcanoMountain
+            | LightSandyMountain | MediumSandyMountain | DarkSandyMountain
+            | LightSandyVolcanoMountain | MediumSandyVolcanoMountain | DarkSandyVolcanoMountain
+            | LightGrassSandLavaVolcanoMountain | MediumGrassSandLavaVolcanoMountain | DarkGrassSandLavaVolcanoMountain
+            | LightGrassSandLavaVolcanoMountain2 | MediumGrassSandLavaVolcanoMountain2 | DarkGrassSandLavaVolcanoMountain2
+            | LightSandyRockVolcanoMountainLavaFlow | MediumSandyRockVolcanoMountainLavaFlow | DarkSandyRockVolcanoMountainLavaFlow
+            | LightRockyVolcanoMountainLavaFlow | MediumRockyVolcanoMountainLavaFlow | DarkRockyVolcanoMountainLavaFlow
+            | None
+        )
+    }
+}
+
+impl GroundTiles {
+    ///
+    /// Whether the tile stops line of sight (and projectiles). Only high ground does.
+    ///
+    pub fn blocks_sight(&self) -> bool {
+        use GroundTiles::*;
+        matches!(self,
+            BedRock | SnowyPeak
+            | LightRockSnowyMountain | MediumRockSnowyMountain | DarkRockSnowyMountain
+            | LightSnowyMountain | MediumSnowyMountain | DarkSnowyMountain
+            | LightGrassyMountain | MediumGrassyMountain | DarkGrassyMountain
+            | LightGrassyVolcanoMountain | MediumGrassyVolcanoMountain | DarkGrassyVolcanoMountain
+            | LightSandyMountain | MediumSandyMountain | DarkSandyMountain
+            | LightSandyVolcanoMountain | MediumSandyVolcanoMountain | DarkSandyVolcanoMountain
+        )
+    }
+}
+
+impl GroundTiles {
+    ///
+    /// First frame and number of frames of the animation the tile belongs to. Water and lava
+    /// come in sequences of frames that follow each other in the tileset.
+    ///
+    pub fn animation(&self) -> Option<(GroundTiles, u32)> {
+        use GroundTiles::*;
+        match self {
+            Water1 | Water2 | Water3 => Some((Water1, 3)),
+            LightWater1 | LightWater2 | LightWater3 => Some((LightWater1, 3)),
+            MediumBlueWater1 | MediumBlueWater2 | MediumBlueWater3 => Some((MediumBlueWater1, 3)),
+            Lava1 | Lava2 | Lava3 => Some((Lava1, 3)),
+            _ => Option::None,
+        }
+    }
+}
+
+pub fn ground_tile_walkable(tile_index: u32) -> bool {
+    GroundTiles::from(tile_index).is_walkable()
+}
+
+
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum TileType {
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+use crate::input::{ActionState, InputAction};
+use crate::map::world_to_tile_coords;
+use crate::player::Player;
+
+///
+/// Number of turns elapsed since the start of the game. A turn ends every time the player
+/// enters a new tile or waits.
+///
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Resource)]
+pub struct TurnClock {
+    pub turn: u64,
+}
+
+///
+/// Sent once per elapsed turn. Systems that act "per turn" (status effects, monsters,
+/// the world clock...) read this message.
+///
+#[derive(Message, Debug, Clone, Copy)]
+pub struct TurnTick {
+    pub turn: u64,
+}
+
+///
+/// Ends the player's turn without moving, for actions such as attacking or casting.
+///
+#[derive(Message, Debug, Clone, Copy, Default)]
+pub struct PassTurn;
+
+///
+/// Systems that must run after the turn counter has advanced this frame.
+///
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TurnSystems;
+
+pub struct TurnPlugin;
+
+impl Plugin for TurnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TurnClock>()
+            .register_type::<TurnClock>()
+            .add_message::<TurnTick>()
+            .add_message::<PassTurn>()
+            .add_systems(Update, end_player_turn.in_set(TurnSystems));
+    }
+}
+
+///
+/// Ends the turn when the player reaches a new tile, uses the wait action or performs an action
+/// that sent a [`PassTurn`].
+///
+fn end_player_turn(
+    actions: Res<ActionState>,
+    player_query: Query<&Transform, With<Player>>,
+    mut pass_turn: MessageReader<PassTurn>,
+    mut last_tile: Local<Option<IVec2>>,
+    mut clock: ResMut<TurnClock>,
+    mut turn_tick: MessageWriter<TurnTick>,
+) {
+    let Ok(player_transform) = player_query.single() else { return; };
+    let tile = world_to_tile_coords(player_transform.translation);
+
+    let moved = last_tile.is_some_and(|last| last != tile);
+    *last_tile = Some(tile);
+
+    let passed = pass_turn.read().count() > 0;
+    if moved || passed || actions.just_pressed(InputAction::Wait) {
+        clock.turn += 1;
+        turn_tick.write(TurnTick { turn: clock.turn });
+    }
+}
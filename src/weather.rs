@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
+use rand::prelude::*;
+
+use crate::game_log::GameLog;
+use crate::map::world_gen::GeneratedWorld;
+use crate::map::world_to_tile_coords;
+use crate::player::{Player, PlayerCamera};
+use crate::states::GameState;
+use crate::world_clock::WorldClock;
+
+// The overworld is split into square regions of this many tiles that share the same weather.
+const WEATHER_REGION: i32 = 24;
+// The weather of a region changes every six hours of game time.
+const WEATHER_PERIOD: u64 = 6 * 60;
+// Chance of a sandstorm over the deserts, in percent.
+const SANDSTORM_CHANCE: f64 = 25.0;
+// Chance of rain or snow over the wettest land, in percent, scaled by the moisture.
+const PRECIPITATION_CHANCE: f64 = 50.0;
+// Land under this moisture and above this temperature is desert.
+const DESERT_MOISTURE: f64 = 0.2;
+const DESERT_TEMPERATURE: f64 = 0.5;
+// Particles are spawned over an area this large around the camera and live this long.
+const PARTICLE_AREA: Vec2 = Vec2::new(1400.0, 1000.0);
+const PARTICLE_LIFETIME: f32 = 1.5;
+const PARTICLE_Z: f32 = 50.0;
+
+///
+/// Weather where the player stands. It is the same over a whole region of the overworld and
+/// only depends on the world seed and the [`WorldClock`], so a saved game sees the same
+/// weather again. Dungeons are always clear.
+///
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+    Sandstorm,
+}
+
+impl Weather {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Weather::Clear => "Clear",
+            Weather::Rain => "Rain",
+            Weather::Snow => "Snow",
+            Weather::Sandstorm => "Sandstorm",
+        }
+    }
+
+    ///
+    /// How far one sees through the weather, for a `radius` in tiles under a clear sky.
+    ///
+    pub fn sight(&self, radius: i32) -> i32 {
+        match self {
+            Weather::Clear => radius,
+            Weather::Rain => radius * 3 / 4,
+            Weather::Snow => radius / 2,
+            Weather::Sandstorm => radius / 3,
+        }
+        .max(1)
+    }
+
+    ///
+    /// Cost of walking onto an overworld tile, 1 under a clear sky.
+    ///
+    pub fn move_cost(&self) -> u32 {
+        match self {
+            Weather::Clear | Weather::Rain => 1,
+            Weather::Snow | Weather::Sandstorm => 2,
+        }
+    }
+
+    ///
+    /// Falling particles of the weather: how many are spawned per second, their velocity,
+    /// size and colour. `None` under a clear sky.
+    ///
+    fn particles(&self) -> Option<(f32, Vec2, Vec2, Color)> {
+        match self {
+            Weather::Clear => None,
+            Weather::Rain => Some((240.0, Vec2::new(-80.0, -900.0), Vec2::new(1.5, 12.0), Color::srgba(0.6, 0.7, 1.0, 0.6))),
+            Weather::Snow => Some((90.0, Vec2::new(-30.0, -120.0), Vec2::new(4.0, 4.0), Color::srgba(1.0, 1.0, 1.0, 0.85))),
+            Weather::Sandstorm => Some((300.0, Vec2::new(700.0, -60.0), Vec2::new(3.0, 2.0), Color::srgba(0.85, 0.7, 0.45, 0.7))),
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct WeatherParticle {
+    velocity: Vec2,
+    lifetime: f32,
+}
+
+///
+/// Weather of the overworld regions, with its particles, its effect on sight and movement
+/// and its indicator in the HUD.
+///
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.init_resource::<Weather>()
+            .register_type::<Weather>()
+            .add_systems(Update, (update_weather, spawn_weather_particles, move_weather_particles).chain())
+            .add_systems(EguiPrimaryContextPass, weather_ui);
+    }
+}
+
+///
+/// Weather of the region holding a tile at the time of the clock. Sandstorms blow over the
+/// deserts, elsewhere it rains or snows more often the wetter the land is, and snows where
+/// the season freezes the ground.
+///
+fn weather_at(world: &GeneratedWorld, clock: &WorldClock, tile: IVec2) -> Weather {
+    let region = tile.div_euclid(IVec2::splat(WEATHER_REGION));
+    let center = (region * WEATHER_REGION + WEATHER_REGION / 2).clamp(IVec2::ZERO, IVec2::new(world.width - 1, world.height - 1));
+    let Some(climate) = world.climate(center) else { return Weather::Clear; };
+
+    let period = clock.minutes / WEATHER_PERIOD;
+    let seed = world.seed ^ ((region.x as u32 as u64) << 40) ^ ((region.y as u32 as u64) << 20) ^ period;
+    let roll = StdRng::seed_from_u64(seed).random_range(0.0..100.0);
+
+    if climate.moisture < DESERT_MOISTURE && climate.temperature > DESERT_TEMPERATURE {
+        return if roll < SANDSTORM_CHANCE { Weather::Sandstorm } else { Weather::Clear };
+    }
+    if roll >= climate.moisture * PRECIPITATION_CHANCE {
+        Weather::Clear
+    } else if climate.temperature < clock.season().freezing_point() {
+        Weather::Snow
+    } else {
+        Weather::Rain
+    }
+}
+
+fn update_weather(
+    world: Option<Res<GeneratedWorld>>,
+    clock: Res<WorldClock>,
+    state: Res<State<GameState>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut weather: ResMut<Weather>,
+    mut log: ResMut<GameLog>,
+) {
+    let current = match (world, player_query.single()) {
+        (Some(world), Ok(transform)) if *state.get() != GameState::Dungeon => {
+            weather_at(&world, &clock, world_to_tile_coords(transform.translation))
+        }
+        _ => Weather::Clear,
+    };
+    let previous = *weather;
+    if weather.set_if_neq(current) && *state.get() != GameState::Dungeon {
+        match current {
+            Weather::Clear => log.info(format!("The {} stops.", previous.name().to_lowercase())),
+            Weather::Rain => log.info("It starts raining."),
+            Weather::Snow => log.info("It starts snowing."),
+            Weather::Sandstorm => log.info("A sandstorm rises."),
+        }
+    }
+}
+
+///
+/// Spawns the particles of the weather over the view of the camera. They are only for looks,
+/// so they are not drawn from the run random generator.
+///
+fn spawn_weather_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    weather: Res<Weather>,
+    camera_query: Query<&Transform, With<PlayerCamera>>,
+    mut pending: Local<f32>,
+) {
+    let Some((rate, velocity, size, color)) = weather.particles() else { return; };
+    let Ok(camera) = camera_query.single() else { return; };
+
+    *pending += rate * time.delta_secs();
+    let mut rng = rand::rng();
+    while *pending >= 1.0 {
+        *pending -= 1.0;
+        // Spawned upwind of the view, so that the particles cross it during their lifetime.
+        let offset = Vec2::new(
+            rng.random_range(-0.5..0.5) * PARTICLE_AREA.x,
+            rng.random_range(-0.5..0.5) * PARTICLE_AREA.y,
+        ) - velocity * PARTICLE_LIFETIME / 2.0;
+        commands.spawn((
+            Sprite::from_color(color, size),
+            Transform::from_translation((camera.translation.truncate() + offset).extend(PARTICLE_Z)),
+            WeatherParticle {
+                velocity: velocity * rng.random_range(0.8..1.2),
+                lifetime: PARTICLE_LIFETIME,
+            },
+        ));
+    }
+}
+
+fn move_weather_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Transform, &mut WeatherParticle)>,
+) {
+    for (entity, mut transform, mut particle) in particles.iter_mut() {
+        particle.lifetime -= time.delta_secs();
+        if particle.lifetime <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += (particle.velocity * time.delta_secs()).extend(0.0);
+    }
+}
+
+///
+/// Weather, season and time of the day in the top right corner of the screen.
+///
+fn weather_ui(
+    mut contexts: EguiContexts,
+    weather: Res<Weather>,
+    clock: Res<WorldClock>,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    egui::Area::new(egui::Id::new("weather_indicator"))
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!("{:?}, day {} - {:02}:00", clock.season(), clock.day() + 1, clock.hour()));
+                ui.label(format!("Weather: {}", weather.name()));
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::map::overworld_map::MapLayer;
+use crate::map::tile_grid::TileProperties;
+use crate::turn::{TurnSystems, TurnTick};
+
+// Game time that passes with each turn, in minutes.
+const MINUTES_PER_TURN: u64 = 10;
+const MINUTES_PER_DAY: u64 = 24 * 60;
+const DAYS_PER_SEASON: u64 = 10;
+// The game starts at eight in the morning of the first day of spring.
+const START_MINUTE: u64 = 8 * 60;
+// Colour the overworld is multiplied by in the middle of the night.
+const NIGHT_TINT: Color = Color::srgb(0.3, 0.35, 0.6);
+// Warm light of sunrise and sunset, strongest when the sun is on the horizon.
+const TWILIGHT_TINT: Color = Color::srgb(1.0, 0.75, 0.55);
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    ///
+    /// Temperature under which the ground freezes during the season. Winter freezes the
+    /// temperate lands too, spring and autumn only the coldest ones.
+    ///
+    pub fn freezing_point(&self) -> f64 {
+        match self {
+            Season::Spring | Season::Autumn => 0.2,
+            Season::Summer => 0.0,
+            Season::Winter => 0.45,
+        }
+    }
+}
+
+///
+/// Time of the game world, in minutes since the start of the game. It follows the
+/// [`TurnClock`](crate::turn::TurnClock), every turn lasts ten minutes.
+///
+#[derive(Resource, Reflect, Debug, Clone, Copy)]
+#[reflect(Resource)]
+pub struct WorldClock {
+    pub minutes: u64,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        WorldClock { minutes: START_MINUTE }
+    }
+}
+
+impl WorldClock {
+    ///
+    /// Days elapsed since the start of the game, the first day is day 0.
+    ///
+    pub fn day(&self) -> u64 {
+        self.minutes / MINUTES_PER_DAY
+    }
+
+    pub fn hour(&self) -> u64 {
+        self.minutes % MINUTES_PER_DAY / 60
+    }
+
+    ///
+    /// Share of the day elapsed since midnight, in `0..1`.
+    ///
+    pub fn time_of_day(&self) -> f32 {
+        (self.minutes % MINUTES_PER_DAY) as f32 / MINUTES_PER_DAY as f32
+    }
+
+    pub fn season(&self) -> Season {
+        match self.day() / DAYS_PER_SEASON % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    ///
+    /// Strength of the daylight, 0 at midnight and 1 at noon.
+    ///
+    pub fn daylight(&self) -> f32 {
+        (1.0 - (self.time_of_day() * std::f32::consts::TAU).cos()) / 2.0
+    }
+
+    ///
+    /// Colour the overworld tiles are multiplied by at this time of the day.
+    ///
+    pub fn tint(&self) -> Color {
+        let daylight = self.daylight();
+        let twilight = 1.0 - ((daylight - 0.5).abs() * 4.0).min(1.0);
+        NIGHT_TINT
+            .mix(&Color::WHITE, daylight.powf(0.5))
+            .mix(&TWILIGHT_TINT, twilight * 0.5)
+    }
+}
+
+///
+/// Sent when the season of the [`WorldClock`] changes.
+///
+#[derive(Message, Debug, Clone, Copy)]
+pub struct SeasonChanged {
+    pub season: Season,
+}
+
+///
+/// Advances the world clock with the turns and lights the overworld for the time of the day.
+/// The overworld swaps its tiles for the season on [`SeasonChanged`].
+///
+pub struct WorldClockPlugin;
+
+impl Plugin for WorldClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldClock>()
+            .register_type::<WorldClock>()
+            .add_message::<SeasonChanged>()
+            .add_systems(Update, (advance_world_clock.after(TurnSystems), tint_overworld).chain());
+    }
+}
+
+fn advance_world_clock(
+    mut turns: MessageReader<TurnTick>,
+    mut clock: ResMut<WorldClock>,
+    mut season_changed: MessageWriter<SeasonChanged>,
+) {
+    let Some(turn) = turns.read().last().map(|tick| tick.turn) else { return; };
+    let season = clock.season();
+    clock.minutes = START_MINUTE + turn * MINUTES_PER_TURN;
+    if clock.season() != season {
+        season_changed.write(SeasonChanged { season: clock.season() });
+    }
+}
+
+///
+/// Multiplies the colour of the overworld tiles by the light of the day. Dungeons and Tiled
+/// maps have their own [`TileProperties`] and keep their colours, like the overlay layer of
+/// the chunks.
+///
+fn tint_overworld(
+    clock: Res<WorldClock>,
+    overworld: Query<Option<&MapLayer>, (With<TileStorage>, Without<TileProperties>)>,
+    mut tiles: Query<(&TilemapId, &mut TileColor)>,
+) {
+    let tint = clock.tint();
+    for (tilemap, mut color) in tiles.iter_mut() {
+        // Only written when it differs, so that the tiles are not re-uploaded every frame.
+        if color.0 != tint && overworld.get(tilemap.0).is_ok_and(|layer| layer != Some(&MapLayer::Overlay)) {
+            color.0 = tint;
+        }
+    }
+}